@@ -93,6 +93,23 @@ export const getWxmlTsPathReverted = (fullPath: string): string | null => {
   return p
 }
 
+/**
+ * The environment of a WXML file when converting it to TypeScript
+ *
+ * `env` is the TypeScript code that declares `component` `data` `methods` and `tags` .
+ * `componentTypes.properties` maps custom component tag names to their property types,
+ * and `componentTypes.generics` maps tag names and generic names to the property types passed to the generics.
+ * `tagEvents` maps tag names and event names to the event types to check the event handlers against.
+ */
+export type WxmlEnv = {
+  env: string
+  componentTypes: {
+    properties: Record<string, string>
+    generics: Record<string, Record<string, string>>
+  }
+  tagEvents: Record<string, Record<string, string>>
+}
+
 type ConvertedExprCache = {
   expr?: TmplConvertedExpr
   source?: ts.SourceFile
@@ -105,6 +122,7 @@ export class ProjectDirManager {
   private tmplGroup = new TmplGroup()
   private trackingComponents = Object.create(null) as Record<string, ComponentJsonData>
   private convertedExpr = Object.create(null) as Record<string, ConvertedExprCache>
+  private wxmlDependants = Object.create(null) as Record<string, Set<string>>
   onEntranceFileAdded: (fullPath: string) => void = () => {}
   onEntranceFileRemoved: (fullPath: string) => void = () => {}
   onConvertedExprCacheInvalidated: (wxmlFullPath: string) => void = () => {}
@@ -189,8 +207,10 @@ export class ProjectDirManager {
     return this.vfs.trackFile(fullPath)
   }
 
-  removeConvertedExprCache(fullPath: string) {
+  removeConvertedExprCache(fullPath: string, visited = new Set<string>()) {
     if (isWxmlFile(fullPath)) {
+      if (visited.has(fullPath)) return
+      visited.add(fullPath)
       const ce = this.convertedExpr[fullPath]
       if (ce) {
         ce.expr?.free()
@@ -199,12 +219,36 @@ export class ProjectDirManager {
         ce.version += 1
         this.onConvertedExprCacheInvalidated(fullPath)
       }
+      // templates importing or including this file should be converted again
+      this.wxmlDependants[fullPath]?.forEach((dependant) => {
+        this.removeConvertedExprCache(dependant, visited)
+      })
     }
   }
 
+  private addWxmlDependencies(tmplPath: string, visited: Set<string>) {
+    const dependant = path.join(this.vfs.rootPath, `${tmplPath}.wxml`)
+    const deps = this.tmplGroup.getDirectDependencies(tmplPath) as string[]
+    deps.forEach((dep) => {
+      if (visited.has(dep)) return
+      visited.add(dep)
+      const fullPath = path.join(this.vfs.rootPath, `${dep}.wxml`)
+      let dependants = this.wxmlDependants[fullPath]
+      if (!dependants) {
+        dependants = new Set()
+        this.wxmlDependants[fullPath] = dependants
+      }
+      dependants.add(dependant)
+      const content = this.getFileContent(fullPath)
+      if (content === null) return
+      this.tmplGroup.addTmpl(dep, content)
+      this.addWxmlDependencies(dep, visited)
+    })
+  }
+
   getWxmlConvertedExpr(
     wxmlFullPath: string,
-    wxmlEnvGetter: (fullPath: string, importTargetFullPath: string) => string | null,
+    wxmlEnvGetter: (fullPath: string, importTargetFullPath: string) => WxmlEnv | null,
   ): string | null {
     const tsFullPath = `${wxmlFullPath.slice(0, -5)}.ts`
     let cache = this.convertedExpr[wxmlFullPath]
@@ -223,10 +267,18 @@ export class ProjectDirManager {
     if (!content) return null
     const relPath = path.relative(this.vfs.rootPath, wxmlFullPath).split(path.sep).join('/')
     if (relPath.startsWith('../')) return null
-    this.tmplGroup.addTmpl(relPath, content)
+    // imports and includes are resolved without the file extension
+    const tmplPath = relPath.slice(0, -5)
+    this.tmplGroup.addTmpl(tmplPath, content)
+    this.addWxmlDependencies(tmplPath, new Set([tmplPath]))
     const env = wxmlEnvGetter(tsFullPath, wxmlFullPath)
     if (!env) return null
-    const expr = this.tmplGroup.getTmplConvertedExpr(relPath, env)
+    const expr = this.tmplGroup.getTmplConvertedExpr(
+      tmplPath,
+      env.env,
+      JSON.stringify(env.componentTypes),
+      JSON.stringify(env.tagEvents),
+    )
     cache.expr = expr
     cache.source = ts.createSourceFile(wxmlFullPath, content, ts.ScriptTarget.Latest)
     cache.version += 1
@@ -280,7 +332,7 @@ export class ProjectDirManager {
 
   getFileTsContent(
     fullPath: string,
-    wxmlEnvGetter: (tsFullPath: string, wxmlFullPath: string) => string | null,
+    wxmlEnvGetter: (tsFullPath: string, wxmlFullPath: string) => WxmlEnv | null,
   ): string | null {
     const p = getWxmlTsPathReverted(fullPath)
     if (p !== null) {
@@ -7,6 +7,7 @@ import {
   type Position,
   type PositionRange,
   ProjectDirManager,
+  type WxmlEnv,
 } from './project'
 
 export const enum DiagnosticLevel {
@@ -27,6 +28,36 @@ export type Diagnostic = {
   message: string
 }
 
+// the `detail` types of the events dispatched by the built-in components
+const BUILTIN_EVENT_DETAILS: Record<string, Record<string, string>> = {
+  input: {
+    input: '{ value: string; cursor: number; keyCode: number }',
+    focus: '{ value: string; height: number }',
+    blur: '{ value: string; cursor: number }',
+    confirm: '{ value: string }',
+  },
+  textarea: {
+    input: '{ value: string; cursor: number; keyCode: number }',
+    focus: '{ value: string; height: number }',
+    blur: '{ value: string; cursor: number }',
+    confirm: '{ value: string }',
+    linechange: '{ height: number; heightRpx: number; lineCount: number }',
+  },
+  switch: {
+    change: '{ value: boolean }',
+  },
+  slider: {
+    change: '{ value: number }',
+    changing: '{ value: number }',
+  },
+  'checkbox-group': {
+    change: '{ value: string[] }',
+  },
+  'radio-group': {
+    change: '{ value: string }',
+  },
+}
+
 type LocationLink = {
   originSelectionRange: PositionRange
   targetUri: string
@@ -196,7 +227,7 @@ export class Server {
     this.tsLangService = ts.createLanguageService(servicesHost, docReg)
 
     // get the exports of a file
-    const wxmlEnvGetter = (tsFullPath: string, wxmlFullPath: string): string | null => {
+    const wxmlEnvGetter = (tsFullPath: string, wxmlFullPath: string): WxmlEnv | null => {
       const program = this.tsLangService.getProgram()
       if (!program) return null
       const source = program.getSourceFile(tsFullPath)
@@ -233,6 +264,11 @@ type Properties<T> = T extends glassEaselMiniprogramAdapter.behavior.ComponentTy
   : glassEaselMiniprogramAdapter.component.Empty`
       let usingComponentsImports = ''
       const usingComponensItems = [] as string[]
+      const componentTypes: WxmlEnv['componentTypes'] = {
+        properties: Object.create(null) as Record<string, string>,
+        // the properties passed to the generics are not declared in the component, so they are not checked
+        generics: Object.create(null) as Record<string, Record<string, string>>,
+      }
       const usingComponents = this.projectDirManager.getUsingComponents(compFullPath)
       Object.entries(usingComponents).forEach(([tagName, compPath]) => {
         const source = program.getSourceFile(`${compPath}.ts`)
@@ -243,6 +279,7 @@ type Properties<T> = T extends glassEaselMiniprogramAdapter.behavior.ComponentTy
         const entryName = `_component_${tagName.replace(/-/g, '_')}`
         usingComponentsImports += `import type ${entryName} from './${escapeJsString(relPath)}'\n`
         usingComponensItems.push(`'${tagName}': Properties<typeof ${entryName}>;\n`)
+        componentTypes.properties[tagName] = `Properties<typeof ${entryName}>`
       })
 
       // treat generics as any type tags
@@ -251,6 +288,17 @@ type Properties<T> = T extends glassEaselMiniprogramAdapter.behavior.ComponentTy
         usingComponensItems.push(`'${tagName}': any;\n`)
       })
 
+      // event types of the built-in components (unless overridden by using components)
+      const tagEvents = Object.create(null) as Record<string, Record<string, string>>
+      Object.entries(BUILTIN_EVENT_DETAILS).forEach(([tagName, details]) => {
+        if (usingComponents[tagName] || generics.includes(tagName)) return
+        const events = Object.create(null) as Record<string, string>
+        Object.entries(details).forEach(([eventName, detail]) => {
+          events[eventName] = `glassEaselMiniprogramAdapter.glassEasel.ShadowedEvent<${detail}>`
+        })
+        tagEvents[tagName] = events
+      })
+
       // TODO handling placeholders
 
       // compose tags types
@@ -261,7 +309,7 @@ type Properties<T> = T extends glassEaselMiniprogramAdapter.behavior.ComponentTy
 declare const tags: {
 ${usingComponensItems.join('')}[other: string]: UnknownElement }`
 
-      return [
+      const env = [
         adapterImportLine,
         unknownElementLine,
         tsImportLine,
@@ -272,6 +320,7 @@ ${usingComponensItems.join('')}[other: string]: UnknownElement }`
         tagsLine,
         '',
       ].join('\n')
+      return { env, componentTypes, tagEvents }
     }
 
     // collect config errors
@@ -22,6 +22,8 @@ pub struct ParseError {
     pub path: String,
     pub kind: ParseErrorKind,
     pub location: Range<Position>,
+    /// Additional message about the error, e.g. the reason of a failed import.
+    pub detail: Option<String>,
 }
 
 impl std::fmt::Display for ParseError {
@@ -35,7 +37,11 @@ impl std::fmt::Display for ParseError {
             self.location.end.line + 1,
             self.location.end.utf16_col + 1,
             self.kind,
-        )
+        )?;
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
     }
 }
 
@@ -63,6 +69,16 @@ pub enum ParseErrorKind {
     UnexpectedCharacter = 0x10001,
     IllegalImportPosition,
     HostSelectorCombination,
+    ImportNotFound,
+    CircularImport,
+    UndeclaredCssVariable,
+    UnclosedBlock,
+    InvalidSelector,
+    UnknownAtRule,
+    InvalidDeclaration,
+    StraySemicolon,
+    UnsupportedPseudoElement,
+    ImportFailed,
 }
 
 impl ParseErrorKind {
@@ -71,6 +87,16 @@ impl ParseErrorKind {
             Self::UnexpectedCharacter => "unexpected character",
             Self::IllegalImportPosition => "`@import` should be placed at the start of the stylesheet (according to CSS standard)",
             Self::HostSelectorCombination => "`:host` selector combined with other selectors are not supported",
+            Self::ImportNotFound => "the imported stylesheet cannot be found",
+            Self::CircularImport => "the stylesheet is imported circularly",
+            Self::UndeclaredCssVariable => "the CSS variable is not declared and has no fallback",
+            Self::UnclosedBlock => "the block is not closed",
+            Self::InvalidSelector => "invalid selector",
+            Self::UnknownAtRule => "unknown at-rule",
+            Self::InvalidDeclaration => "invalid declaration (the declaration is ignored)",
+            Self::StraySemicolon => "unnecessary semicolon",
+            Self::UnsupportedPseudoElement => "the pseudo-element is not supported",
+            Self::ImportFailed => "failed to load the imported stylesheet",
         }
     }
 
@@ -79,6 +105,16 @@ impl ParseErrorKind {
             Self::UnexpectedCharacter => ParseErrorLevel::Fatal,
             Self::IllegalImportPosition => ParseErrorLevel::Note,
             Self::HostSelectorCombination => ParseErrorLevel::Warn,
+            Self::ImportNotFound => ParseErrorLevel::Error,
+            Self::CircularImport => ParseErrorLevel::Error,
+            Self::UndeclaredCssVariable => ParseErrorLevel::Warn,
+            Self::UnclosedBlock => ParseErrorLevel::Fatal,
+            Self::InvalidSelector => ParseErrorLevel::Error,
+            Self::UnknownAtRule => ParseErrorLevel::Warn,
+            Self::InvalidDeclaration => ParseErrorLevel::Error,
+            Self::StraySemicolon => ParseErrorLevel::Note,
+            Self::UnsupportedPseudoElement => ParseErrorLevel::Warn,
+            Self::ImportFailed => ParseErrorLevel::Error,
         }
     }
 }
@@ -96,7 +132,7 @@ impl std::fmt::Display for ParseErrorKind {
 }
 
 #[repr(u8)]
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum ParseErrorLevel {
     /// Likely to be an mistake and should be noticed.
     ///
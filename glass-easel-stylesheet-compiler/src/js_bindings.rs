@@ -28,7 +28,10 @@ impl From<ParseError> for StyleSheetParseError {
             is_error: value.kind.level() >= ParseErrorLevel::Error,
             level: value.kind.level(),
             code: value.code() as u32,
-            message: value.kind.to_string(),
+            message: match &value.detail {
+                Some(detail) => format!("{} ({})", value.kind, detail),
+                None => value.kind.to_string(),
+            },
             path: value.path.to_string(),
             start_line: value.location.start.line,
             start_column: value.location.start.utf16_col,
@@ -38,6 +41,62 @@ impl From<ParseError> for StyleSheetParseError {
     }
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CssVariableUsage {
+    path: String,
+    name: String,
+    has_fallback: bool,
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+}
+
+impl From<&crate::CssVariableUsage> for CssVariableUsage {
+    fn from(value: &crate::CssVariableUsage) -> Self {
+        Self {
+            path: value.path.clone(),
+            name: value.name.clone(),
+            has_fallback: value.has_fallback,
+            start_line: value.location.start.line,
+            start_column: value.location.start.utf16_col,
+            end_line: value.location.end.line,
+            end_column: value.location.end.utf16_col,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ImportedStyleSheet {
+    path: String,
+    content: String,
+}
+
+/// Resolves `@import` with a js function `(importer, relPath) => { path, content } | null` .
+///
+/// The error thrown by the function (or a bad return value) is reported in the warnings.
+struct JsImportResolver(js_sys::Function);
+
+impl ImportResolver for JsImportResolver {
+    fn resolve(
+        &mut self,
+        importer: &str,
+        rel_path: &str,
+    ) -> Result<Option<(String, String)>, String> {
+        let ret = self
+            .0
+            .call2(&JsValue::NULL, &importer.into(), &rel_path.into())
+            .map_err(|err| match err.dyn_ref::<js_sys::Error>() {
+                Some(err) => String::from(err.message()),
+                None => err.as_string().unwrap_or_else(|| format!("{:?}", err)),
+            })?;
+        let ret: Option<ImportedStyleSheet> = serde_wasm_bindgen::from_value(ret)
+            .map_err(|err| format!("illegal import resolver result: {}", err))?;
+        Ok(ret.map(|x| (x.path, x.content)))
+    }
+}
+
 #[wasm_bindgen]
 pub struct StyleSheetTransformer {
     warnings: Vec<ParseError>,
@@ -45,10 +104,37 @@ pub struct StyleSheetTransformer {
     normal_source_map: String,
     low_priority_content: String,
     low_priority_source_map: String,
+    class_name_mapping: String,
+    class_names: Vec<String>,
+    custom_properties: Vec<CssVariableUsage>,
+    var_references: Vec<CssVariableUsage>,
+}
+
+/// The options of `StyleSheetTransformer` in js.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct JsStyleSheetOptions {
+    class_prefix: Option<String>,
+    rpx_ratio: Option<f32>,
+    convert_host: bool,
+    flatten_nesting: bool,
+    hash_class_names: bool,
+    rpx_strategy: Option<String>,
+    rpx_precision: Option<u32>,
+    rpx_min_one_px: bool,
+    minify: bool,
+    /// A js function `(importer, relPath) => { path, content } | null` .
+    #[serde(with = "serde_wasm_bindgen::preserve")]
+    import_resolver: JsValue,
+    known_css_variables: Option<Vec<String>>,
+    autoprefix: Option<Vec<String>>,
 }
 
 #[wasm_bindgen]
 impl StyleSheetTransformer {
+    /// Transform a stylesheet.
+    ///
+    /// Use `StyleSheetTransformer.withOptions` for more options.
     #[wasm_bindgen(constructor)]
     pub fn new(
         name: &str,
@@ -57,17 +143,85 @@ impl StyleSheetTransformer {
         rpx_ratio: f32,
         convert_host: bool,
     ) -> Self {
-        let mut sst = crate::StyleSheetTransformer::from_css(
-            name,
-            s,
-            StyleSheetOptions {
-                class_prefix,
-                rpx_ratio,
-                convert_host,
-                ..Default::default()
-            },
-        );
+        let options = StyleSheetOptions {
+            class_prefix,
+            rpx_ratio,
+            convert_host,
+            ..Default::default()
+        };
+        Self::transform(name, s, options, None)
+    }
+
+    /// Transform a stylesheet with an options object.
+    ///
+    /// The `options` is an object like
+    /// `{ classPrefix, rpxRatio, convertHost, flattenNesting, hashClassNames, rpxStrategy, rpxPrecision, rpxMinOnePx, minify, importResolver, knownCssVariables, autoprefix }` ,
+    /// in which all fields are optional.
+    #[wasm_bindgen(js_name = withOptions)]
+    pub fn with_options(
+        name: &str,
+        s: &str,
+        options: JsValue,
+    ) -> Result<StyleSheetTransformer, JsError> {
+        let options: JsStyleSheetOptions = if options.is_undefined() || options.is_null() {
+            Default::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)?
+        };
+        let rpx_strategy = match options.rpx_strategy {
+            Some(x) => x.parse().map_err(|x: String| JsError::new(&x))?,
+            None => RpxStrategy::Vw,
+        };
+        let autoprefix = match options.autoprefix {
+            Some(targets) => Some(
+                targets
+                    .iter()
+                    .map(|x| x.parse())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|x: String| JsError::new(&x))?,
+            ),
+            None => None,
+        };
+        let import_resolver = options.import_resolver.dyn_into::<js_sys::Function>().ok();
+        let options = StyleSheetOptions {
+            class_prefix: options.class_prefix,
+            rpx_ratio: options.rpx_ratio.unwrap_or(750.),
+            convert_host: options.convert_host,
+            flatten_nesting: options.flatten_nesting,
+            hash_class_names: options.hash_class_names,
+            rpx_strategy,
+            rpx_precision: options.rpx_precision,
+            rpx_min_one_px: options.rpx_min_one_px,
+            minify: options.minify,
+            known_css_variables: options.known_css_variables,
+            autoprefix,
+            ..Default::default()
+        };
+        Ok(Self::transform(name, s, options, import_resolver))
+    }
+}
+
+impl StyleSheetTransformer {
+    fn transform(
+        name: &str,
+        s: &str,
+        options: StyleSheetOptions,
+        import_resolver: Option<js_sys::Function>,
+    ) -> Self {
+        let mut sst = match import_resolver {
+            Some(f) => crate::StyleSheetTransformer::from_css_bundled(
+                name,
+                s,
+                options,
+                JsImportResolver(f),
+            ),
+            None => crate::StyleSheetTransformer::from_css(name, s, options),
+        };
         let warnings = sst.take_warnings();
+        let class_name_mapping = sst.class_name_mapping_json();
+        let class_names = sst.class_names().keys().cloned().collect();
+        let custom_properties = sst.custom_properties().iter().map(|x| x.into()).collect();
+        let var_references = sst.var_references().iter().map(|x| x.into()).collect();
         let (normal, low_priority) = sst.output_and_low_priority_output();
 
         let mut normal_content = String::new();
@@ -88,9 +242,16 @@ impl StyleSheetTransformer {
             normal_source_map: String::from_utf8(normal_source_map).unwrap(),
             low_priority_content,
             low_priority_source_map: String::from_utf8(low_priority_source_map).unwrap(),
+            class_name_mapping,
+            class_names,
+            custom_properties,
+            var_references,
         }
     }
+}
 
+#[wasm_bindgen]
+impl StyleSheetTransformer {
     #[wasm_bindgen(js_name = extractWarnings)]
     pub fn extrace_warnings(&mut self) -> JsValue {
         let ret: Vec<_> = self
@@ -120,6 +281,30 @@ impl StyleSheetTransformer {
     pub fn get_low_priority_source_map(&self) -> String {
         self.low_priority_source_map.clone()
     }
+
+    /// Get the JSON mapping from original class names to hashed ones.
+    #[wasm_bindgen(js_name = getClassNameMapping)]
+    pub fn get_class_name_mapping(&self) -> String {
+        self.class_name_mapping.clone()
+    }
+
+    /// Get the (original) class names used in selectors.
+    #[wasm_bindgen(js_name = getClassNames)]
+    pub fn get_class_names(&self) -> Vec<String> {
+        self.class_names.clone()
+    }
+
+    /// Get the custom properties declared in the stylesheet.
+    #[wasm_bindgen(js_name = getCustomProperties)]
+    pub fn get_custom_properties(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.custom_properties).unwrap()
+    }
+
+    /// Get the `var()` references in the stylesheet.
+    #[wasm_bindgen(js_name = getVarReferences)]
+    pub fn get_var_references(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.var_references).unwrap()
+    }
 }
 
 #[wasm_bindgen]
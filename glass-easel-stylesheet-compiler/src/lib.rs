@@ -1,13 +1,21 @@
+use std::collections::{BTreeMap, HashSet};
 use std::ops::Range;
 
-use cssparser::{CowRcStr, ParseError, ParserInput, Token};
+use cssparser::{CowRcStr, Delimiter, ParseError, ParserInput, ParserState, Token};
 
 pub mod error;
 pub mod js_bindings;
+mod minify;
 pub mod output;
+pub mod prefix;
+mod selector;
 mod step;
 
 use output::StyleSheetOutput;
+use selector::{
+    Combinator, ComplexSelector, CompoundSelector, HostSelector, PseudoArgs, RawToken,
+    SelectorList, SimpleSelector,
+};
 use step::{StepParser, StepToken};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,9 +23,35 @@ pub struct StyleSheetOptions {
     pub class_prefix: Option<String>,
     pub class_prefix_sign: Option<String>,
     pub rpx_ratio: f32,
+    /// The target of `rpx` conversion.
+    pub rpx_strategy: RpxStrategy,
+    /// The maximum number of decimal places of converted `rpx` values (unlimited if `None` ).
+    ///
+    /// Non-zero values are never rounded to zero, e.g. `1rpx` is converted to `1vw` with precision `0` .
+    pub rpx_precision: Option<u32>,
+    /// Convert non-zero `rpx` values to at least `1px` (only for `RpxStrategy::Px` ).
+    ///
+    /// This keeps hairlines (e.g. `1rpx` borders) visible on narrow screens.
+    pub rpx_min_one_px: bool,
     pub import_sign: Option<String>,
     pub convert_host: bool,
     pub host_is: Option<String>,
+    /// Flatten nested style rules into plain rules instead of preserving the nesting syntax.
+    pub flatten_nesting: bool,
+    /// Replace class names with hashes of the path and the class names (`class_prefix` is ignored).
+    pub hash_class_names: bool,
+    /// Minify the output, e.g. remove empty rules and overridden declarations, and shorten colors.
+    pub minify: bool,
+    /// The custom properties provided by the environment (e.g. set by the host).
+    ///
+    /// If provided, `var()` references without fallbacks to the custom properties
+    /// neither declared in the stylesheet nor listed here are reported.
+    pub known_css_variables: Option<Vec<String>>,
+    /// Add the `-webkit-` prefixes needed by the browser targets (disabled if `None` ).
+    ///
+    /// The prefixes are decided with a built-in compatibility table,
+    /// and the prefixed properties and values are written before the original ones.
+    pub autoprefix: Option<Vec<prefix::BrowserTarget>>,
 }
 
 impl Default for StyleSheetOptions {
@@ -26,13 +60,101 @@ impl Default for StyleSheetOptions {
             class_prefix: None,
             class_prefix_sign: None,
             rpx_ratio: 750.,
+            rpx_strategy: RpxStrategy::Vw,
+            rpx_precision: None,
+            rpx_min_one_px: false,
             import_sign: None,
             convert_host: false,
             host_is: None,
+            flatten_nesting: false,
+            hash_class_names: false,
+            minify: false,
+            known_css_variables: None,
+            autoprefix: None,
         }
     }
 }
 
+/// How `rpx` values are converted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpxStrategy {
+    /// Convert to `vw` , i.e. `N rpx` to `N * 100 / rpx_ratio vw` .
+    Vw,
+    /// Convert to `px` for a fixed screen width, i.e. `N rpx` to `N * screen_width / rpx_ratio px` .
+    Px { screen_width: f32 },
+    /// Convert to `calc(var(--name) * N)` so that the scale can be adjusted at runtime.
+    ///
+    /// The variable should be set to the width of `1rpx` .
+    /// Since variables are not allowed in at-rule preludes (e.g. `@media` queries),
+    /// `rpx` values in them are converted to `vw` instead.
+    CssVar { name: String },
+}
+
+impl std::str::FromStr for RpxStrategy {
+    type Err = String;
+
+    /// Parse from `vw` , `px:SCREEN_WIDTH` , `var` or `var:NAME` .
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = match s.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (s, None),
+        };
+        match (kind, arg) {
+            ("vw", None) => Ok(Self::Vw),
+            ("px", Some(arg)) => match arg.parse::<f32>() {
+                Ok(screen_width) if screen_width > 0. => Ok(Self::Px { screen_width }),
+                _ => Err(format!("illegal screen width {:?}", arg)),
+            },
+            ("var", None) => Ok(Self::CssVar {
+                name: "--rpx".to_string(),
+            }),
+            ("var", Some(arg)) if arg.starts_with("--") => Ok(Self::CssVar {
+                name: arg.to_string(),
+            }),
+            _ => Err(format!(
+                "illegal rpx strategy {:?} (expected `vw`, `px:SCREEN_WIDTH`, `var` or `var:--NAME`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Loads the stylesheets imported by `@import` , so that they can be bundled.
+pub trait ImportResolver {
+    /// Resolve `rel_path` imported by the stylesheet `importer` .
+    ///
+    /// Returns the resolved path and the content of the imported stylesheet, or `None` if not found.
+    /// The resolved path is used in source maps and for circular import detection.
+    /// An `Err` message is reported along with the import warning.
+    fn resolve(
+        &mut self,
+        importer: &str,
+        rel_path: &str,
+    ) -> Result<Option<(String, String)>, String>;
+}
+
+impl<F: FnMut(&str, &str) -> Option<(String, String)>> ImportResolver for F {
+    fn resolve(
+        &mut self,
+        importer: &str,
+        rel_path: &str,
+    ) -> Result<Option<(String, String)>, String> {
+        Ok(self(importer, rel_path))
+    }
+}
+
+/// A custom property declaration or a `var()` reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CssVariableUsage {
+    /// The path of the stylesheet (may be an imported one).
+    pub path: String,
+    /// The custom property name, e.g. `--a` .
+    pub name: String,
+    pub location: Range<error::Position>,
+    /// Whether a fallback value is provided in the `var()` (always `false` for declarations).
+    pub has_fallback: bool,
+}
+
 pub struct StyleSheetTransformer {
     options: StyleSheetOptions,
     path: String,
@@ -41,10 +163,48 @@ pub struct StyleSheetTransformer {
     using_low_priority: bool,
     warnings: Vec<error::ParseError>,
     cur_at_rule_stacks: Vec<String>,
+    in_at_rule_prelude: bool,
+    nesting_level: usize,
+    nesting_parents: Vec<NestingParent>,
+    class_name_mapping: BTreeMap<String, String>,
+    class_names: BTreeMap<String, error::Position>,
+    import_resolver: Option<Box<dyn ImportResolver>>,
+    import_stack: Vec<String>,
+    custom_properties: Vec<CssVariableUsage>,
+    var_references: Vec<CssVariableUsage>,
+}
+
+/// The output selectors of the enclosing style rule, used when flattening nested rules.
+#[derive(Debug, Clone)]
+struct NestingParent {
+    selectors: Vec<(String, error::Position)>,
 }
 
 impl StyleSheetTransformer {
     pub fn from_css(path: &str, css: &str, options: StyleSheetOptions) -> Self {
+        Self::new(path, css, options, None)
+    }
+
+    /// Transform the stylesheet with the imported stylesheets inlined.
+    ///
+    /// The imported stylesheets are loaded with `resolver` and transformed recursively,
+    /// wrapped in the `layer()` , `supports()` and media conditions of the `@import` .
+    /// The source map contains all the stylesheets.
+    pub fn from_css_bundled(
+        path: &str,
+        css: &str,
+        options: StyleSheetOptions,
+        resolver: impl ImportResolver + 'static,
+    ) -> Self {
+        Self::new(path, css, options, Some(Box::new(resolver)))
+    }
+
+    fn new(
+        path: &str,
+        css: &str,
+        options: StyleSheetOptions,
+        import_resolver: Option<Box<dyn ImportResolver>>,
+    ) -> Self {
         let parser_input = &mut ParserInput::new(css);
         let parser = &mut cssparser::Parser::new(parser_input);
         let mut input = StepParser::wrap(parser);
@@ -58,22 +218,67 @@ impl StyleSheetTransformer {
             using_low_priority: false,
             warnings: vec![],
             cur_at_rule_stacks: vec![],
+            in_at_rule_prelude: false,
+            nesting_level: 0,
+            nesting_parents: vec![],
+            class_name_mapping: BTreeMap::new(),
+            class_names: BTreeMap::new(),
+            import_resolver,
+            import_stack: vec![],
+            custom_properties: vec![],
+            var_references: vec![],
         };
 
         {
+            check_unclosed_blocks(&mut this, css);
             parse_rules(&mut input, &mut this);
         }
+        this.import_resolver = None;
+        this.check_var_references();
+        if this.options.minify {
+            let placeholder = StyleSheetOutput::new(path, css);
+            let output = std::mem::replace(&mut this.normal_output, placeholder);
+            this.normal_output = minify::minify(output, path, css);
+            let placeholder = StyleSheetOutput::new(path, css);
+            let output = std::mem::replace(&mut this.low_priority_output, placeholder);
+            this.low_priority_output = minify::minify(output, path, css);
+        }
         this
     }
 
+    /// The path of the stylesheet being parsed (may be an imported one).
+    fn cur_path(&self) -> &str {
+        self.import_stack.last().unwrap_or(&self.path)
+    }
+
     fn add_warning(&mut self, kind: error::ParseErrorKind, location: Range<error::Position>) {
         self.warnings.push(error::ParseError {
-            path: self.path.clone(),
+            path: self.cur_path().to_string(),
             kind,
             location,
+            detail: None,
         });
     }
 
+    /// The lengths of the warnings and CSS variable lists, used before parsing something again.
+    fn diagnostics_len(&self) -> (usize, usize, usize) {
+        (
+            self.warnings.len(),
+            self.custom_properties.len(),
+            self.var_references.len(),
+        )
+    }
+
+    /// Drop the warnings and CSS variables collected when parsing something again.
+    fn truncate_diagnostics(
+        &mut self,
+        (warnings, custom_properties, var_references): (usize, usize, usize),
+    ) {
+        self.warnings.truncate(warnings);
+        self.custom_properties.truncate(custom_properties);
+        self.var_references.truncate(var_references);
+    }
+
     pub fn warnings(&self) -> impl Iterator<Item = &error::ParseError> {
         self.warnings.iter()
     }
@@ -82,6 +287,79 @@ impl StyleSheetTransformer {
         std::mem::replace(&mut self.warnings, vec![])
     }
 
+    /// The mapping from the original class names to the hashed ones.
+    ///
+    /// It is empty unless `hash_class_names` is enabled.
+    pub fn class_name_mapping(&self) -> &BTreeMap<String, String> {
+        &self.class_name_mapping
+    }
+
+    /// The class name mapping in JSON format, i.e. `{ "original": "hashed" }` .
+    pub fn class_name_mapping_json(&self) -> String {
+        serde_json::to_string(&self.class_name_mapping).unwrap_or_default()
+    }
+
+    /// The class names used in selectors, with the position where each one first appears.
+    ///
+    /// The names are the original ones, i.e. before prefixing or hashing.
+    pub fn class_names(&self) -> &BTreeMap<String, error::Position> {
+        &self.class_names
+    }
+
+    /// The custom properties declared in the stylesheet (and the imported ones).
+    pub fn custom_properties(&self) -> &[CssVariableUsage] {
+        &self.custom_properties
+    }
+
+    /// The `var()` references in the stylesheet (and the imported ones).
+    pub fn var_references(&self) -> &[CssVariableUsage] {
+        &self.var_references
+    }
+
+    fn add_css_variable_usage(
+        &mut self,
+        name: &str,
+        position: error::Position,
+        is_reference: bool,
+        has_fallback: bool,
+    ) {
+        let end = error::Position {
+            line: position.line,
+            utf16_col: position.utf16_col + name.encode_utf16().count() as u32,
+        };
+        let usage = CssVariableUsage {
+            path: self.cur_path().to_string(),
+            name: name.to_string(),
+            location: position..end,
+            has_fallback,
+        };
+        if is_reference {
+            self.var_references.push(usage);
+        } else {
+            self.custom_properties.push(usage);
+        }
+    }
+
+    fn check_var_references(&mut self) {
+        let Some(known) = self.options.known_css_variables.as_ref() else {
+            return;
+        };
+        for r in self.var_references.iter() {
+            if r.has_fallback
+                || known.contains(&r.name)
+                || self.custom_properties.iter().any(|x| x.name == r.name)
+            {
+                continue;
+            }
+            self.warnings.push(error::ParseError {
+                path: r.path.clone(),
+                kind: error::ParseErrorKind::UndeclaredCssVariable,
+                location: r.location.clone(),
+                detail: None,
+            });
+        }
+    }
+
     pub fn output(self) -> StyleSheetOutput {
         self.normal_output
     }
@@ -159,6 +437,24 @@ impl StyleSheetTransformer {
             .append_token_space_preserved(token, src)
     }
 
+    /// Run `f` with its output discarded if `write` is false,
+    /// e.g. for the selectors of a rule that is not written.
+    fn write_or_discard<R>(
+        &mut self,
+        write: bool,
+        input: &mut StepParser,
+        f: impl FnOnce(&mut Self, &mut StepParser) -> R,
+    ) -> R {
+        if write {
+            return f(self, input);
+        }
+        let placeholder = StyleSheetOutput::new(&self.path, "");
+        let output = std::mem::replace(self.current_output_mut(), placeholder);
+        let r = f(self, input);
+        *self.current_output_mut() = output;
+        r
+    }
+
     fn cur_output_utf8_len(&self) -> usize {
         self.current_output().cur_utf8_len()
     }
@@ -187,6 +483,16 @@ fn write_maybe_class_name(
     src: &CowRcStr,
     in_class: bool,
 ) {
+    if in_class && ss.options.hash_class_names {
+        let hashed = ss
+            .class_name_mapping
+            .entry(src.to_string())
+            .or_insert_with(|| hash_class_name(&ss.path, src))
+            .clone();
+        let st = StepToken::wrap(Token::Ident(hashed.as_str().into()), next.position);
+        ss.append_token_space_preserved(st, input, Some(Token::Ident(src.clone())));
+        return;
+    }
     if in_class {
         if let Some(content) = ss.options.class_prefix_sign.clone() {
             let st = StepToken::wrap(Token::Comment(&content), next.position);
@@ -202,59 +508,197 @@ fn write_maybe_class_name(
     }
 }
 
+/// Generate the hashed class name used when `hash_class_names` is enabled.
+///
+/// The result is a short CSS identifier determined by the path and the class name only.
+pub fn hash_class_name(path: &str, class_name: &str) -> String {
+    // 32-bit FNV-1a
+    let mut hash: u32 = 0x811c9dc5;
+    for b in path
+        .bytes()
+        .chain(std::iter::once(0))
+        .chain(class_name.bytes())
+    {
+        hash ^= b as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    let mut ret = String::from("_");
+    let mut digits = vec![];
+    loop {
+        digits.push(std::char::from_digit(hash % 36, 36).unwrap());
+        hash /= 36;
+        if hash == 0 {
+            break;
+        }
+    }
+    ret.extend(digits.into_iter().rev());
+    ret
+}
+
+fn round_rpx_value(value: f32, precision: Option<u32>) -> (f32, Option<i32>) {
+    let value = match precision {
+        Some(p) => {
+            let m = 10f64.powi(p.min(9) as i32);
+            let rounded = (value as f64 * m).round() / m;
+            if rounded == 0. && value != 0. {
+                // keep non-zero values visible, e.g. `1rpx` is not rounded to `0vw`
+                (value.signum() as f64 / m) as f32
+            } else {
+                rounded as f32
+            }
+        }
+        None => value,
+    };
+    let int_value = if (value.round() - value).abs() <= f32::EPSILON {
+        Some(value.round() as i32)
+    } else {
+        None
+    };
+    (value, int_value)
+}
+
 fn write_maybe_rpx_dimension(
     input: &mut StepParser,
     ss: &mut StyleSheetTransformer,
     next: &StepToken,
-    has_sign: bool,
-    value: f32,
-    int_value: Option<i32>,
-    unit: &CowRcStr,
+    in_calc: bool,
 ) {
-    let unit_str: &str = &unit;
-    if unit_str == "rpx" {
-        let new_value = value * 100. / ss.options.rpx_ratio;
-        let new_int_value = if (new_value.round() - new_value).abs() <= f32::EPSILON {
-            Some(new_value.round() as i32)
-        } else {
-            None
-        };
-        let t = Token::Dimension {
-            has_sign,
-            value: new_value,
-            int_value: new_int_value,
-            unit: "vw".into(),
-        };
-        let st = StepToken::wrap(t, next.position);
-        ss.append_token(
-            st,
-            input,
-            Some(Token::Dimension {
-                has_sign,
-                value,
-                unit: unit.clone(),
-                int_value,
-            }),
-        );
-    } else {
-        let token = Token::Dimension {
-            has_sign,
-            value,
-            unit: unit.clone(),
-            int_value,
-        };
-        let st = StepToken::wrap(token, next.position);
-        ss.append_token(st, input, None);
+    let Token::Dimension {
+        has_sign,
+        value,
+        int_value,
+        unit,
+    } = &**next
+    else {
+        unreachable!()
+    };
+    let (has_sign, value, int_value) = (*has_sign, *value, *int_value);
+    if &**unit != "rpx" {
+        ss.append_token(next.clone(), input, None);
+        return;
+    }
+    let src = next.token.clone();
+    let (new_value, new_unit) = match &ss.options.rpx_strategy {
+        RpxStrategy::CssVar { name } if !ss.in_at_rule_prelude => {
+            // `calc(var(--rpx)*N)` , or `(var(--rpx)*N)` if it is already inside `calc()`
+            let name = name.clone();
+            let open = if in_calc {
+                Token::ParenthesisBlock
+            } else {
+                Token::Function("calc".into())
+            };
+            let tokens = [
+                open,
+                Token::Function("var".into()),
+                Token::Ident(name.as_str().into()),
+                Token::CloseParenthesis,
+                Token::Delim('*'),
+                Token::Number {
+                    has_sign,
+                    value,
+                    int_value,
+                },
+                Token::CloseParenthesis,
+            ];
+            let mut src = Some(src);
+            for t in tokens {
+                let st = StepToken::wrap(t, next.position);
+                ss.append_token(st, input, src.take());
+            }
+            return;
+        }
+        RpxStrategy::Px { screen_width } => {
+            let px = value * screen_width / ss.options.rpx_ratio;
+            if ss.options.rpx_min_one_px && px != 0. && px.abs() < 1. {
+                (px.signum(), "px")
+            } else {
+                (px, "px")
+            }
+        }
+        RpxStrategy::Vw | RpxStrategy::CssVar { .. } => (value * 100. / ss.options.rpx_ratio, "vw"),
+    };
+    let (new_value, new_int_value) = round_rpx_value(new_value, ss.options.rpx_precision);
+    let t = Token::Dimension {
+        has_sign,
+        value: new_value,
+        int_value: new_int_value,
+        unit: new_unit.into(),
+    };
+    let st = StepToken::wrap(t, next.position);
+    ss.append_token(st, input, Some(src));
+}
+
+/// Report the blocks that are not closed until the end of the stylesheet.
+///
+/// The parser closes them implicitly, so they are checked in a separate pass.
+fn check_unclosed_blocks(ss: &mut StyleSheetTransformer, css: &str) {
+    fn rec(input: &mut StepParser, ss: &mut StyleSheetTransformer) -> bool {
+        let mut reported = false;
+        while let Ok(next) = input.next_including_whitespace() {
+            if !matches!(
+                &*next,
+                Token::Function(_)
+                    | Token::ParenthesisBlock
+                    | Token::SquareBracketBlock
+                    | Token::CurlyBracketBlock
+            ) {
+                continue;
+            }
+            let mut inner_reported = false;
+            let mut inner_end = None;
+            input
+                .parse_nested_block::<_, (), ()>(|nested_input| {
+                    let input = &mut StepParser::wrap(nested_input);
+                    inner_reported = rec(input, ss);
+                    inner_end = Some(input.source_position());
+                    Ok(())
+                })
+                .ok();
+            // only the innermost one is reported
+            if inner_reported {
+                reported = true;
+            } else if inner_end == Some(input.source_position()) {
+                ss.add_warning(
+                    error::ParseErrorKind::UnclosedBlock,
+                    next.position..next.end_position(),
+                );
+                reported = true;
+            }
+        }
+        reported
     }
+    let parser_input = &mut ParserInput::new(css);
+    let parser = &mut cssparser::Parser::new(parser_input);
+    rec(&mut StepParser::wrap(parser), ss);
 }
 
 fn parse_rules(input: &mut StepParser, ss: &mut StyleSheetTransformer) {
     let mut at_file_start = true;
     while !input.is_exhausted() {
+        // skip stray tokens so that they do not break the next rule
+        if let Ok(peek) = input.peek() {
+            let kind = match &*peek {
+                Token::Semicolon => Some(error::ParseErrorKind::StraySemicolon),
+                Token::CloseCurlyBracket | Token::CloseParenthesis | Token::CloseSquareBracket => {
+                    Some(error::ParseErrorKind::UnexpectedCharacter)
+                }
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                input.next().ok();
+                ss.add_warning(kind, peek.position..peek.end_position());
+                continue;
+            }
+        }
+        // `@import` can follow other `@import`
+        let is_import = match input.peek() {
+            Ok(peek) => matches!(&*peek, Token::AtKeyword(x) if &**x == "import"),
+            Err(_) => false,
+        };
         if !parse_at_rule(input, ss, at_file_start) {
             parse_qualified_rule(input, ss);
         }
-        at_file_start = false;
+        at_file_start = at_file_start && is_import;
     }
 }
 
@@ -267,9 +711,10 @@ fn parse_at_rule(
     if let Token::AtKeyword(x) = &*peek {
         input.next().ok();
         let at_keyword: &str = &x;
-        if at_keyword == "import" && ss.options.import_sign.is_some() {
+        if at_keyword == "import"
+            && (ss.options.import_sign.is_some() || ss.import_resolver.is_some())
+        {
             // process at-import if needed
-            let import_sign = ss.options.import_sign.clone().unwrap();
             let start_pos = input.position();
             if !at_file_start {
                 ss.add_warning(
@@ -277,12 +722,28 @@ fn parse_at_rule(
                     start_pos..start_pos,
                 );
             }
+            ss.in_at_rule_prelude = true;
             let r = input.try_parse::<_, _, ParseError<()>>(|input| {
-                let rel_path = input.expect_string_cloned()?;
+                let rel_path = input.expect_url_or_string()?;
                 let mut close_stack = vec![];
+                let mut at_rule_strs = vec![];
                 let mut has_media = false;
                 while let Ok(peek) = input.peek() {
                     match &*peek {
+                        Token::Ident(x) if &**x == "layer" && close_stack.is_empty() => {
+                            // anonymous layer
+                            input.next().ok();
+                            let output_index = ss.cur_output_utf8_len();
+                            let st = StepToken::wrap(Token::AtKeyword(x.clone()), peek.position);
+                            ss.append_token(st, input, None);
+                            at_rule_strs.push(
+                                ss.get_output_segment(output_index..ss.cur_output_utf8_len())
+                                    .to_string(),
+                            );
+                            let st = StepToken::wrap(Token::CurlyBracketBlock, peek.position);
+                            let close = ss.append_nested_block(st, input);
+                            close_stack.push(close);
+                        }
                         Token::Function(x) => {
                             let xs: &str = &x;
                             if !matches!(xs, "layer" | "supports") {
@@ -293,6 +754,7 @@ fn parse_at_rule(
                                 break;
                             }
                             input.next().ok();
+                            let output_index = ss.cur_output_utf8_len();
                             let st = StepToken::wrap(Token::AtKeyword(x.clone()), peek.position);
                             ss.append_token(st, input, Some(peek.token.clone()));
                             match xs {
@@ -308,6 +770,10 @@ fn parse_at_rule(
                                 }
                                 _ => unreachable!(),
                             }
+                            at_rule_strs.push(
+                                ss.get_output_segment(output_index..ss.cur_output_utf8_len())
+                                    .to_string(),
+                            );
                             let st = StepToken::wrap(Token::CurlyBracketBlock, peek.position);
                             let close = ss.append_nested_block(st, input);
                             close_stack.push(close);
@@ -331,6 +797,7 @@ fn parse_at_rule(
                 }
                 let pos = input.position();
                 if has_media {
+                    let output_index = ss.cur_output_utf8_len();
                     let st = StepToken::wrap(Token::AtKeyword("media".into()), start_pos);
                     ss.append_token(st, input, None);
                     while let Ok(next) = input.next() {
@@ -357,18 +824,31 @@ fn parse_at_rule(
                             }
                         }
                     }
+                    at_rule_strs.push(
+                        ss.get_output_segment(output_index..ss.cur_output_utf8_len())
+                            .to_string(),
+                    );
                     let st = StepToken::wrap(Token::CurlyBracketBlock, start_pos);
                     let close = ss.append_nested_block(st, input);
                     close_stack.push(close);
                 }
-                let comment = format!("{} {}", import_sign, urlencoding::encode(&rel_path));
-                let st = StepToken::wrap(Token::Comment(comment.as_str()), start_pos);
-                ss.append_token(st, input, None);
+                if ss.import_resolver.is_some() {
+                    let at_rule_stack_len = ss.cur_at_rule_stacks.len();
+                    ss.cur_at_rule_stacks.extend(at_rule_strs);
+                    ss.in_at_rule_prelude = false;
+                    inline_import(ss, &rel_path, start_pos);
+                    ss.cur_at_rule_stacks.truncate(at_rule_stack_len);
+                } else if let Some(import_sign) = ss.options.import_sign.as_ref() {
+                    let comment = format!("{} {}", import_sign, urlencoding::encode(&rel_path));
+                    let st = StepToken::wrap(Token::Comment(comment.as_str()), start_pos);
+                    ss.append_token(st, input, None);
+                }
                 while let Some(close) = close_stack.pop() {
                     ss.append_nested_block_close(close, input);
                 }
                 Ok(())
             });
+            ss.in_at_rule_prelude = false;
             if r.is_err() {
                 while let Ok(x) = input.next() {
                     match &*x {
@@ -379,62 +859,29 @@ fn parse_at_rule(
             }
         } else {
             // process other at-rules
-            let st = StepToken::wrap(Token::AtKeyword(x.clone()), peek.position);
-            let output_index = ss.cur_output_utf8_len();
-            ss.append_token(st, input, None);
-            let x: &str = &x;
-            let contain_rule_list = matches!(x, "media" | "supports" | "document");
-            loop {
-                let r = input.try_parse::<_, _, ParseError<()>>(|input| {
-                    let next = input.next()?;
-                    match next.token.clone() {
-                        Token::CurlyBracketBlock => {
-                            let at_rule_str = ss
-                                .get_output_segment(output_index..ss.cur_output_utf8_len())
-                                .to_string();
-                            ss.wrap_at_rule_output(input, at_rule_str, |ss, input| {
-                                let close = ss.append_nested_block(next, input);
-                                if contain_rule_list {
-                                    input
-                                        .parse_nested_block::<_, (), ()>(|nested_input| {
-                                            let input = &mut StepParser::wrap(nested_input);
-                                            parse_rules(input, ss);
-                                            Ok(())
-                                        })
-                                        .ok();
-                                } else {
-                                    convert_rpx_in_block(input, ss, None);
-                                }
-                                ss.append_nested_block_close(close, input);
-                            });
-                            return Ok(false);
-                        }
-                        Token::SquareBracketBlock
-                        | Token::ParenthesisBlock
-                        | Token::Function(_) => {
-                            let close = ss.append_nested_block(next, input);
-                            convert_class_names_and_rpx_in_block(input, ss);
-                            ss.append_nested_block_close(close, input);
-                        }
-                        Token::Semicolon => {
-                            ss.append_token(next, input, None);
-                            return Ok(false);
-                        }
-                        _ => {
-                            ss.append_token(next, input, None);
-                        }
-                    }
-                    Ok(true)
-                });
-                match r {
-                    Ok(cont) => {
-                        if !cont {
-                            break;
-                        }
-                    }
-                    Err(_) => break,
-                }
+            if !is_known_at_rule(&x) {
+                ss.add_warning(
+                    error::ParseErrorKind::UnknownAtRule,
+                    peek.position..peek.end_position(),
+                );
             }
+            let needs_prefix = ss
+                .options
+                .autoprefix
+                .as_ref()
+                .is_some_and(|targets| prefix::at_rule_needs_prefix(x.as_ref(), targets));
+            if needs_prefix {
+                // write the prefixed one first
+                let state = input.state();
+                let diagnostics_len = ss.diagnostics_len();
+                let name = format!("-webkit-{}", x);
+                let st = StepToken::wrap(Token::AtKeyword(name.into()), peek.position);
+                parse_other_at_rule(input, ss, st);
+                ss.truncate_diagnostics(diagnostics_len);
+                input.reset(&state);
+            }
+            let st = StepToken::wrap(Token::AtKeyword(x.clone()), peek.position);
+            parse_other_at_rule(input, ss, st);
         }
         true
     } else {
@@ -442,123 +889,84 @@ fn parse_at_rule(
     }
 }
 
-fn parse_qualified_rule(input: &mut StepParser, ss: &mut StyleSheetTransformer) {
-    let mut in_class = false;
-    let mut has_whitespace = false;
-    input.skip_whitespace();
-    if ss.options.convert_host {
-        let r = input.try_parse::<_, _, ParseError<()>>(|input| {
-            input.expect_colon()?;
-            let Ok(next) = input.next() else {
-                return Ok(());
-            };
-            let mut invalid = match &*next {
-                Token::Ident(x) if x.as_bytes() == b"host" => None,
-                Token::Function(x) if x.as_bytes() == b"host" => Some(input.position()),
-                _ => return Err(input.new_custom_error(())),
-            };
-            let next = loop {
-                let Ok(next) = input.next() else {
-                    return Ok(());
-                };
-                if *next != Token::CurlyBracketBlock {
-                    if invalid.is_none() {
-                        invalid = Some(input.position());
-                    }
-                } else {
-                    break next;
-                }
-            };
-            if let Some(pos) = invalid {
-                ss.add_warning(error::ParseErrorKind::HostSelectorCombination, pos..pos);
-            } else {
-                ss.write_in_low_priority(input, |ss, input| {
-                    let write_attr_selector =
-                        |ss: &mut StyleSheetTransformer,
-                         input: &mut StepParser,
-                         name: &str,
-                         value: &str| {
-                            ss.append_token(
-                                StepToken::wrap_at(Token::SquareBracketBlock, &next),
-                                input,
-                                None,
-                            );
-                            ss.append_token(
-                                StepToken::wrap_at(Token::Ident(name.into()), &next),
-                                input,
-                                None,
-                            );
-                            ss.append_token(
-                                StepToken::wrap_at(Token::Delim('='), &next),
-                                input,
-                                None,
-                            );
-                            let quoted_value = Token::QuotedString(value.into());
-                            ss.append_token(StepToken::wrap_at(quoted_value, &next), input, None);
-                            ss.append_token(
-                                StepToken::wrap_at(Token::CloseSquareBracket, &next),
-                                input,
-                                None,
-                            );
-                        };
-                    let p = ss.options.class_prefix.clone().unwrap_or_default();
-                    write_attr_selector(ss, input, "wx-host", &p);
-                    if let Some(host_is) = ss.options.host_is.clone() {
-                        ss.append_token(StepToken::wrap_at(Token::Comma, &next), input, None);
-                        write_attr_selector(ss, input, "is", &host_is);
-                    }
-                    let close = ss.append_nested_block(next, input);
-                    convert_rpx_in_block(input, ss, None);
-                    ss.append_nested_block_close(close, input);
-                });
-            }
-            Ok(())
-        });
-        if r.is_ok() {
-            return;
-        }
-    }
+/// Write an at-rule other than `@import` , with the at-keyword replaced by `at_keyword` .
+fn parse_other_at_rule(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    at_keyword: StepToken,
+) {
+    let Token::AtKeyword(name) = &*at_keyword else {
+        return;
+    };
+    let contain_rule_list = is_group_at_rule(name);
+    let contain_keyframes = is_keyframes_at_rule(name);
+    let output_index = ss.cur_output_utf8_len();
+    ss.append_token(at_keyword.clone(), input, None);
+    ss.in_at_rule_prelude = true;
     loop {
         let r = input.try_parse::<_, _, ParseError<()>>(|input| {
-            let next = input.next_including_whitespace()?;
-            match &*next {
-                Token::CurlyBracketBlock | Token::WhiteSpace(_) => {}
-                _ => {
-                    if has_whitespace {
-                        let st = StepToken::wrap(Token::WhiteSpace(" "), next.position);
-                        ss.append_token_space_preserved(st, input, None);
-                    }
-                }
-            }
-            has_whitespace = false;
-            match &*next {
+            let block_state = input.state();
+            let next = input.next()?;
+            match next.token.clone() {
                 Token::CurlyBracketBlock => {
-                    let close = ss.append_nested_block(next, input);
-                    convert_rpx_in_block(input, ss, None);
-                    ss.append_nested_block_close(close, input);
+                    ss.in_at_rule_prelude = false;
+                    let at_rule_str = ss
+                        .get_output_segment(output_index..ss.cur_output_utf8_len())
+                        .to_string();
+                    ss.wrap_at_rule_output(input, at_rule_str, |ss, input| {
+                        let close = ss.append_nested_block(next.clone(), input);
+                        if contain_rule_list && ss.nesting_level > 0 {
+                            // nested in a style rule
+                            if let Some(parent) = ss.nesting_parents.last().cloned() {
+                                let write_block = is_rule_block_written(input, ss, &block_state);
+                                if write_block {
+                                    write_nesting_parent(input, ss, &parent);
+                                }
+                                let st = StepToken::wrap_at(Token::CurlyBracketBlock, &next);
+                                parse_rule_block(input, ss, st, parent, write_block);
+                            } else {
+                                input
+                                    .parse_nested_block::<_, (), ()>(|nested_input| {
+                                        let input = &mut StepParser::wrap(nested_input);
+                                        parse_rule_body(input, ss, RuleBodyPass::All);
+                                        Ok(())
+                                    })
+                                    .ok();
+                            }
+                        } else if contain_rule_list {
+                            input
+                                .parse_nested_block::<_, (), ()>(|nested_input| {
+                                    let input = &mut StepParser::wrap(nested_input);
+                                    parse_rules(input, ss);
+                                    Ok(())
+                                })
+                                .ok();
+                        } else if contain_keyframes {
+                            input
+                                .parse_nested_block::<_, (), ()>(|nested_input| {
+                                    let input = &mut StepParser::wrap(nested_input);
+                                    parse_keyframe_blocks(input, ss);
+                                    Ok(())
+                                })
+                                .ok();
+                        } else {
+                            convert_rpx_in_block(input, ss, None);
+                        }
+                        ss.append_nested_block_close(close, input);
+                    });
                     return Ok(false);
                 }
                 Token::SquareBracketBlock | Token::ParenthesisBlock | Token::Function(_) => {
                     let close = ss.append_nested_block(next, input);
                     convert_class_names_and_rpx_in_block(input, ss);
                     ss.append_nested_block_close(close, input);
-                    in_class = false;
-                }
-                Token::Delim('.') => {
-                    ss.append_token_space_preserved(next, input, None);
-                    in_class = true;
-                }
-                Token::Ident(src) => {
-                    write_maybe_class_name(input, ss, &next, src, in_class);
-                    in_class = false;
                 }
-                Token::WhiteSpace(_) => {
-                    has_whitespace = true;
-                    in_class = false;
+                Token::Semicolon => {
+                    ss.append_token(next, input, None);
+                    return Ok(false);
                 }
                 _ => {
-                    ss.append_token_space_preserved(next.clone(), input, None);
-                    in_class = false;
+                    ss.append_token(next, input, None);
                 }
             }
             Ok(true)
@@ -572,32 +980,823 @@ fn parse_qualified_rule(input: &mut StepParser, ss: &mut StyleSheetTransformer)
             Err(_) => break,
         }
     }
+    ss.in_at_rule_prelude = false;
 }
 
-fn convert_class_names_and_rpx_in_block(input: &mut StepParser, ss: &mut StyleSheetTransformer) {
-    input
-        .parse_nested_block::<_, (), ()>(|nested_input| {
+/// Write the keyframe blocks of `@keyframes` , whose declarations are handled like the ones in style rules.
+fn parse_keyframe_blocks(input: &mut StepParser, ss: &mut StyleSheetTransformer) {
+    while let Ok(next) = input.next() {
+        match &*next {
+            Token::CurlyBracketBlock => {
+                let close = ss.append_nested_block(next, input);
+                input
+                    .parse_nested_block::<_, (), ()>(|nested_input| {
+                        let input = &mut StepParser::wrap(nested_input);
+                        parse_rule_body(input, ss, RuleBodyPass::All);
+                        Ok(())
+                    })
+                    .ok();
+                ss.append_nested_block_close(close, input);
+            }
+            _ => {
+                ss.append_token(next, input, None);
+            }
+        }
+    }
+}
+
+fn inline_import(ss: &mut StyleSheetTransformer, rel_path: &str, position: error::Position) {
+    let importer = ss.cur_path().to_string();
+    let Some(resolver) = ss.import_resolver.as_mut() else {
+        return;
+    };
+    let (path, css) = match resolver.resolve(&importer, rel_path) {
+        Ok(Some(x)) => x,
+        Ok(None) => {
+            ss.add_warning(error::ParseErrorKind::ImportNotFound, position..position);
+            return;
+        }
+        Err(detail) => {
+            ss.warnings.push(error::ParseError {
+                path: importer,
+                kind: error::ParseErrorKind::ImportFailed,
+                location: position..position,
+                detail: Some(detail),
+            });
+            return;
+        }
+    };
+    if path == ss.path || ss.import_stack.contains(&path) {
+        ss.add_warning(error::ParseErrorKind::CircularImport, position..position);
+        return;
+    }
+    let normal_source = ss.normal_output.switch_source(&path, &css);
+    let low_priority_source = ss.low_priority_output.switch_source(&path, &css);
+    ss.import_stack.push(path);
+    {
+        check_unclosed_blocks(ss, &css);
+        let parser_input = &mut ParserInput::new(&css);
+        let parser = &mut cssparser::Parser::new(parser_input);
+        let mut input = StepParser::wrap(parser);
+        parse_rules(&mut input, ss);
+    }
+    ss.import_stack.pop();
+    ss.normal_output.restore_source(normal_source);
+    ss.low_priority_output.restore_source(low_priority_source);
+}
+
+fn parse_qualified_rule(input: &mut StepParser, ss: &mut StyleSheetTransformer) {
+    input.skip_whitespace();
+
+    // skip to the next rule if there is no block, e.g. a declaration outside style rules
+    let state = input.state();
+    let start = input.position();
+    let mut has_block = false;
+    let mut end = start;
+    while let Ok(next) = input.next() {
+        if let Token::CurlyBracketBlock = &*next {
+            has_block = true;
+            break;
+        }
+        end = next.end_position();
+        if let Token::Semicolon = &*next {
+            break;
+        }
+    }
+    if !has_block {
+        ss.add_warning(error::ParseErrorKind::InvalidSelector, start..end);
+        return;
+    }
+    input.reset(&state);
+
+    let list = input
+        .parse_until_before::<_, _, ()>(Delimiter::CurlyBracketBlock, |nested_input| {
             let input = &mut StepParser::wrap(nested_input);
-            let mut in_class = false;
-            let mut has_whitespace = false;
-            input.skip_whitespace();
-            loop {
-                let next = input.next_including_whitespace()?;
-                match &*next {
-                    Token::CurlyBracketBlock | Token::WhiteSpace(_) => {}
-                    _ => {
-                        if has_whitespace {
-                            let st = StepToken::wrap(Token::WhiteSpace(" "), next.position);
-                            ss.append_token_space_preserved(st, input, None);
+            Ok(selector::parse_selector_list(input))
+        })
+        .unwrap_or_default();
+    let state = input.state();
+    let write_block = is_rule_block_written(input, ss, &state);
+    let Ok(next) = input.next() else {
+        return;
+    };
+    if list.selectors.is_empty() {
+        ss.add_warning(
+            error::ParseErrorKind::InvalidSelector,
+            next.position..next.end_position(),
+        );
+    } else if let Some(range) = list.invalid_range(ss.nesting_level > 0) {
+        ss.add_warning(error::ParseErrorKind::InvalidSelector, range);
+    }
+    for (name, range) in list.pseudo_elements() {
+        if !is_supported_pseudo_element(&name) {
+            ss.add_warning(error::ParseErrorKind::UnsupportedPseudoElement, range);
+        }
+    }
+
+    // split `:host` selectors out
+    let convert_host = ss.options.convert_host && ss.nesting_level == 0;
+    let mut normal = vec![];
+    let mut host = vec![];
+    let mut has_invalid = false;
+    for sel in list.selectors.iter() {
+        if !convert_host {
+            normal.push(sel);
+            continue;
+        }
+        match sel.host() {
+            HostSelector::None => normal.push(sel),
+            HostSelector::Host(arg) => host.push((sel, arg)),
+            HostSelector::Invalid(pos) => {
+                ss.add_warning(error::ParseErrorKind::HostSelectorCombination, pos..pos);
+                has_invalid = true;
+            }
+        }
+    }
+
+    // write `:host` rules in low priority
+    if !host.is_empty() {
+        ss.write_in_low_priority(input, |ss, input| {
+            let p = ss.options.class_prefix.clone().unwrap_or_default();
+            let mut attrs = vec![("wx-host", p)];
+            if let Some(host_is) = ss.options.host_is.clone() {
+                attrs.push(("is", host_is));
+            }
+            let selectors = ss.write_or_discard(write_block, input, |ss, input| {
+                let mut selectors = vec![];
+                for (sel, arg) in host.iter() {
+                    for (name, value) in attrs.iter() {
+                        if !selectors.is_empty() {
+                            let st = StepToken::wrap_at(Token::Comma, &next);
+                            ss.append_token(st, input, None);
                         }
+                        let start = ss.cur_output_utf8_len();
+                        write_host_selector(input, ss, sel, *arg, name, value);
+                        let s = ss.get_output_segment(start..ss.cur_output_utf8_len());
+                        selectors.push((s.to_string(), sel.position));
                     }
                 }
-                has_whitespace = false;
-                match &*next {
-                    Token::CurlyBracketBlock
-                    | Token::SquareBracketBlock
-                    | Token::ParenthesisBlock => {
-                        let close = ss.append_nested_block(next, input);
+                selectors
+            });
+            let parent = NestingParent { selectors };
+            parse_rule_block(input, ss, next.clone(), parent, write_block);
+        });
+        if normal.is_empty() {
+            return;
+        }
+    }
+    if normal.is_empty() && has_invalid {
+        return;
+    }
+
+    // write other rules
+    let next = if host.is_empty() {
+        next
+    } else {
+        input.reset(&state);
+        match input.next() {
+            Ok(x) => x,
+            Err(_) => return,
+        }
+    };
+    let diagnostics_len = ss.diagnostics_len();
+    let parent = ss.nesting_parents.last().cloned();
+    let parent_selectors = match parent.as_ref() {
+        Some(parent) if !parent.selectors.is_empty() => {
+            parent.selectors.iter().map(Some).collect::<Vec<_>>()
+        }
+        _ => vec![None],
+    };
+    let selectors = ss.write_or_discard(write_block, input, |ss, input| {
+        let mut selectors = vec![];
+        for parent_selector in parent_selectors {
+            for sel in normal.iter() {
+                if !selectors.is_empty() {
+                    ss.append_token(StepToken::wrap(Token::Comma, sel.position), input, None);
+                }
+                let start = ss.cur_output_utf8_len();
+                let mut position = sel.position;
+                if let Some((s, parent_position)) = parent_selector {
+                    // selectors without `&` are relative to the parent selector
+                    if !sel.contains_nesting() {
+                        ss.current_output_mut()
+                            .append_raw_with_position(s, *parent_position);
+                        if sel.compounds.first().is_some_and(|x| x.0.is_none()) {
+                            let st = StepToken::wrap(Token::WhiteSpace(" "), sel.position);
+                            ss.append_token_space_preserved(st, input, None);
+                        }
+                        position = *parent_position;
+                    }
+                }
+                write_complex_selector(input, ss, sel, parent_selector);
+                let s = ss.get_output_segment(start..ss.cur_output_utf8_len());
+                selectors.push((s.to_string(), position));
+            }
+        }
+        selectors
+    });
+    parse_rule_block(input, ss, next, NestingParent { selectors }, write_block);
+    if !host.is_empty() {
+        // the rule body has been parsed twice
+        ss.truncate_diagnostics(diagnostics_len);
+    }
+}
+
+fn write_host_selector(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    sel: &ComplexSelector,
+    arg: Option<&CompoundSelector>,
+    name: &str,
+    value: &str,
+) {
+    let position = sel.position;
+    let write = |ss: &mut StyleSheetTransformer, input: &mut StepParser, token: Token| {
+        ss.append_token(StepToken::wrap(token, position), input, None);
+    };
+    write(ss, input, Token::SquareBracketBlock);
+    write(ss, input, Token::Ident(name.into()));
+    write(ss, input, Token::Delim('='));
+    write(ss, input, Token::QuotedString(value.into()));
+    write(ss, input, Token::CloseSquareBracket);
+    if let Some(arg) = arg {
+        write_compound_selector(input, ss, arg, None);
+    }
+    for (combinator, compound) in sel.compounds[1..].iter() {
+        if let Some(combinator) = combinator {
+            write_combinator(input, ss, *combinator);
+        }
+        write_compound_selector(input, ss, compound, None);
+    }
+}
+
+fn write_selector_list(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    list: &SelectorList,
+    parent: Option<&(String, error::Position)>,
+) {
+    for (index, sel) in list.selectors.iter().enumerate() {
+        if index > 0 {
+            ss.append_token(StepToken::wrap(Token::Comma, sel.position), input, None);
+        }
+        write_complex_selector(input, ss, sel, parent);
+    }
+}
+
+fn write_complex_selector(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    sel: &ComplexSelector,
+    parent: Option<&(String, error::Position)>,
+) {
+    for (combinator, compound) in sel.compounds.iter() {
+        if let Some(combinator) = combinator {
+            write_combinator(input, ss, *combinator);
+        }
+        write_compound_selector(input, ss, compound, parent);
+    }
+}
+
+fn write_combinator(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    (combinator, position): (Combinator, error::Position),
+) {
+    let token = match combinator {
+        Combinator::Descendant => Token::WhiteSpace(" "),
+        Combinator::Child => Token::Delim('>'),
+        Combinator::NextSibling => Token::Delim('+'),
+        Combinator::SubsequentSibling => Token::Delim('~'),
+    };
+    ss.append_token_space_preserved(StepToken::wrap(token, position), input, None);
+}
+
+fn write_compound_selector(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    compound: &CompoundSelector,
+    parent: Option<&(String, error::Position)>,
+) {
+    for simple in compound.simple_selectors.iter() {
+        match simple {
+            SimpleSelector::Type(t) | SimpleSelector::Id(t) => {
+                ss.append_token(t.clone(), input, None);
+            }
+            SimpleSelector::Nesting(t) => match parent {
+                Some((s, position)) => {
+                    ss.current_output_mut()
+                        .append_raw_with_position(s, *position);
+                }
+                None => ss.append_token(t.clone(), input, None),
+            },
+            SimpleSelector::Class { dot, name } => {
+                ss.append_token(dot.clone(), input, None);
+                if let Token::Ident(src) = &**name {
+                    ss.class_names
+                        .entry(src.to_string())
+                        .or_insert(name.position);
+                    write_maybe_class_name(input, ss, name, src, true);
+                }
+            }
+            SimpleSelector::Attribute { open, contents } => {
+                let close = ss.append_nested_block(open.clone(), input);
+                write_raw_tokens(input, ss, contents);
+                ss.append_nested_block_close(close, input);
+            }
+            SimpleSelector::PseudoClass { colon, name, args } => {
+                ss.append_token(colon.clone(), input, None);
+                write_pseudo(input, ss, name, args.as_ref(), parent);
+            }
+            SimpleSelector::PseudoElement { colons, name, args } => {
+                ss.append_token(colons.0.clone(), input, None);
+                ss.append_token(colons.1.clone(), input, None);
+                write_pseudo(input, ss, name, args.as_ref(), parent);
+            }
+            SimpleSelector::Unknown(t) => {
+                write_raw_tokens(input, ss, std::slice::from_ref(t));
+            }
+        }
+    }
+}
+
+fn write_pseudo(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    name: &StepToken,
+    args: Option<&PseudoArgs>,
+    parent: Option<&(String, error::Position)>,
+) {
+    let Some(args) = args else {
+        ss.append_token(name.clone(), input, None);
+        return;
+    };
+    let close = ss.append_nested_block(name.clone(), input);
+    match args {
+        PseudoArgs::Selectors(list) => {
+            write_selector_list(input, ss, list, parent);
+        }
+        PseudoArgs::NthOf(raw, of, list) => {
+            write_raw_tokens(input, ss, raw);
+            let st = StepToken::wrap_at(Token::WhiteSpace(" "), of);
+            ss.append_token_space_preserved(st.clone(), input, None);
+            ss.append_token(of.clone(), input, None);
+            ss.append_token_space_preserved(st, input, None);
+            write_selector_list(input, ss, list, parent);
+        }
+        PseudoArgs::Raw(raw) => {
+            write_raw_tokens(input, ss, raw);
+        }
+    }
+    ss.append_nested_block_close(close, input);
+}
+
+fn write_raw_tokens(input: &mut StepParser, ss: &mut StyleSheetTransformer, tokens: &[RawToken]) {
+    let mut has_whitespace = false;
+    let mut is_start = true;
+    for token in tokens {
+        let (RawToken::Token(t) | RawToken::Block(t, _)) = token;
+        if let Token::WhiteSpace(_) = &**t {
+            has_whitespace = !is_start;
+            continue;
+        }
+        if has_whitespace {
+            let st = StepToken::wrap(Token::WhiteSpace(" "), t.position);
+            ss.append_token_space_preserved(st, input, None);
+            has_whitespace = false;
+        }
+        is_start = false;
+        match token {
+            RawToken::Block(open, children) => {
+                let close = ss.append_nested_block(open.clone(), input);
+                write_raw_tokens(input, ss, children);
+                ss.append_nested_block_close(close, input);
+            }
+            RawToken::Token(t) => match &**t {
+                Token::Dimension { .. } => {
+                    write_maybe_rpx_dimension(input, ss, t, false);
+                }
+                _ => ss.append_token(t.clone(), input, None),
+            },
+        }
+    }
+}
+
+fn write_nesting_parent(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    parent: &NestingParent,
+) {
+    for (index, (s, position)) in parent.selectors.iter().enumerate() {
+        if index > 0 {
+            ss.append_token(StepToken::wrap(Token::Comma, *position), input, None);
+        }
+        ss.current_output_mut()
+            .append_raw_with_position(s, *position);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleBodyPass {
+    All,
+    Declarations,
+    NestedRules,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleBodyItem {
+    Declaration,
+    NestedRule,
+    NestedGroupRule,
+}
+
+fn is_group_at_rule(name: &str) -> bool {
+    matches!(name, "media" | "supports" | "document")
+}
+
+fn is_keyframes_at_rule(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let unprefixed = match name.strip_prefix('-') {
+        Some(x) => x.split_once('-').map(|x| x.1).unwrap_or_default(),
+        None => &name,
+    };
+    unprefixed == "keyframes"
+}
+
+fn is_known_at_rule(name: &str) -> bool {
+    // vendor-prefixed ones are not checked
+    name.starts_with('-')
+        || matches!(
+            name.to_ascii_lowercase().as_str(),
+            "charset"
+                | "import"
+                | "namespace"
+                | "media"
+                | "supports"
+                | "document"
+                | "font-face"
+                | "keyframes"
+                | "page"
+                | "layer"
+                | "container"
+                | "counter-style"
+                | "font-feature-values"
+                | "font-palette-values"
+                | "property"
+                | "scope"
+                | "starting-style"
+                | "viewport"
+        )
+}
+
+fn is_supported_pseudo_element(name: &str) -> bool {
+    // vendor-prefixed ones are not checked
+    name.starts_with('-')
+        || matches!(
+            name,
+            "before"
+                | "after"
+                | "first-line"
+                | "first-letter"
+                | "placeholder"
+                | "selection"
+                | "marker"
+                | "backdrop"
+                | "file-selector-button"
+                | "cue"
+                | "part"
+                | "slotted"
+                | "highlight"
+                | "target-text"
+                | "spelling-error"
+                | "grammar-error"
+        )
+}
+
+/// Whether the rule block starting at `block_state` should be written.
+///
+/// When flattening nesting, a rule containing nested rules only would be left empty,
+/// so it is not written.
+fn is_rule_block_written(
+    input: &mut StepParser,
+    ss: &StyleSheetTransformer,
+    block_state: &ParserState,
+) -> bool {
+    if !ss.options.flatten_nesting {
+        return true;
+    }
+    let state = input.state();
+    input.reset(block_state);
+    let mut has_declarations = false;
+    let mut has_nested_rules = false;
+    if input.next().is_ok() {
+        input
+            .parse_nested_block::<_, (), ()>(|nested_input| {
+                let input = &mut StepParser::wrap(nested_input);
+                while let Some(item) = peek_rule_body_item(input) {
+                    match item {
+                        RuleBodyItem::Declaration => has_declarations = true,
+                        RuleBodyItem::NestedRule | RuleBodyItem::NestedGroupRule => {
+                            has_nested_rules = true
+                        }
+                    }
+                    skip_rule_body_item(input);
+                }
+                Ok(())
+            })
+            .ok();
+    }
+    input.reset(&state);
+    has_declarations || !has_nested_rules
+}
+
+fn parse_rule_block(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    next: StepToken,
+    parent: NestingParent,
+    write_block: bool,
+) {
+    let close = write_block.then(|| ss.append_nested_block(next, input));
+    input
+        .parse_nested_block::<_, (), ()>(|nested_input| {
+            let input = &mut StepParser::wrap(nested_input);
+            if ss.options.flatten_nesting {
+                // nested rules are written after the parent rule
+                if let Some(close) = close {
+                    let state = input.state();
+                    parse_rule_body(input, ss, RuleBodyPass::Declarations);
+                    ss.append_nested_block_close(close, input);
+                    input.reset(&state);
+                }
+                ss.nesting_parents.push(parent);
+                parse_rule_body(input, ss, RuleBodyPass::NestedRules);
+                ss.nesting_parents.pop();
+            } else {
+                parse_rule_body(input, ss, RuleBodyPass::All);
+                if let Some(close) = close {
+                    ss.append_nested_block_close(close, input);
+                }
+            }
+            Ok(())
+        })
+        .ok();
+}
+
+fn parse_rule_body(input: &mut StepParser, ss: &mut StyleSheetTransformer, pass: RuleBodyPass) {
+    ss.nesting_level += 1;
+    let declared = if ss.options.autoprefix.is_some() {
+        collect_declared(input)
+    } else {
+        HashSet::new()
+    };
+    while let Some(item) = peek_rule_body_item(input) {
+        let skipped = match item {
+            RuleBodyItem::Declaration => pass == RuleBodyPass::NestedRules,
+            RuleBodyItem::NestedRule | RuleBodyItem::NestedGroupRule => {
+                pass == RuleBodyPass::Declarations
+            }
+        };
+        if skipped {
+            skip_rule_body_item(input);
+            continue;
+        }
+        match item {
+            RuleBodyItem::Declaration => {
+                if !parse_at_rule(input, ss, false) {
+                    parse_declaration(input, ss, &declared);
+                }
+            }
+            RuleBodyItem::NestedGroupRule => {
+                parse_at_rule(input, ss, false);
+            }
+            RuleBodyItem::NestedRule => {
+                parse_qualified_rule(input, ss);
+            }
+        }
+    }
+    ss.nesting_level -= 1;
+}
+
+/// Collect the properties (and keyword values) declared in a rule body.
+fn collect_declared(input: &mut StepParser) -> HashSet<String> {
+    let state = input.state();
+    let mut declared = HashSet::new();
+    while let Ok(next) = input.next() {
+        let prop = match &*next {
+            Token::Semicolon | Token::CurlyBracketBlock => continue,
+            Token::Ident(x) => x.to_ascii_lowercase(),
+            _ => {
+                skip_rule_body_item(input);
+                continue;
+            }
+        };
+        if input.try_parse(|input| input.expect_colon()).is_ok() {
+            if let Ok(value) = input.try_parse(|input| input.expect_ident_cloned()) {
+                declared.insert(format!("{}:{}", prop, value.to_ascii_lowercase()));
+            }
+            declared.insert(prop);
+        }
+        skip_rule_body_item(input);
+    }
+    input.reset(&state);
+    declared
+}
+
+fn skip_rule_body_item(input: &mut StepParser) {
+    while let Ok(next) = input.next() {
+        if matches!(&*next, Token::Semicolon | Token::CurlyBracketBlock) {
+            break;
+        }
+    }
+}
+
+fn peek_rule_body_item(input: &mut StepParser) -> Option<RuleBodyItem> {
+    let state = input.state();
+    let ret = input.next().ok().map(|next| match &*next {
+        Token::AtKeyword(x) if is_group_at_rule(x) => RuleBodyItem::NestedGroupRule,
+        Token::AtKeyword(_) | Token::Semicolon => RuleBodyItem::Declaration,
+        Token::Ident(x) if x.starts_with("--") => RuleBodyItem::Declaration,
+        Token::CurlyBracketBlock => RuleBodyItem::NestedRule,
+        _ => loop {
+            let Ok(next) = input.next() else {
+                break RuleBodyItem::Declaration;
+            };
+            match &*next {
+                Token::Semicolon => break RuleBodyItem::Declaration,
+                Token::CurlyBracketBlock => break RuleBodyItem::NestedRule,
+                _ => {}
+            }
+        },
+    });
+    input.reset(&state);
+    ret
+}
+
+fn parse_declaration(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    declared: &HashSet<String>,
+) {
+    let state = input.state();
+    let start = input.position();
+    let valid = match input.next() {
+        Ok(next) => match &*next {
+            Token::Semicolon => {
+                ss.add_warning(
+                    error::ParseErrorKind::StraySemicolon,
+                    next.position..next.end_position(),
+                );
+                return;
+            }
+            Token::Ident(name) if input.expect_colon().is_ok() => {
+                if name.starts_with("--") {
+                    ss.add_css_variable_usage(name, next.position, false, false);
+                    true
+                } else {
+                    input.peek().is_ok_and(|x| !matches!(&*x, Token::Semicolon))
+                }
+            }
+            _ => false,
+        },
+        Err(_) => return,
+    };
+    input.reset(&state);
+    if !valid {
+        // the whole declaration is ignored
+        let mut end = start;
+        while let Ok(next) = input.next() {
+            if let Token::Semicolon = &*next {
+                break;
+            }
+            end = next.end_position();
+        }
+        ss.add_warning(error::ParseErrorKind::InvalidDeclaration, start..end);
+        return;
+    }
+    if ss.options.autoprefix.is_some() {
+        write_prefixed_declarations(input, ss, declared);
+        input.reset(&state);
+    }
+    input
+        .parse_until_before::<_, (), ()>(Delimiter::Semicolon, |nested_input| {
+            let input = &mut StepParser::wrap(nested_input);
+            convert_rpx_in_tokens(input, ss, false);
+            Ok(())
+        })
+        .ok();
+    if let Ok(next) = input.next() {
+        ss.append_token(next, input, None);
+    }
+}
+
+/// Write the `-webkit-` prefixed copies of the declaration needed by the autoprefix targets.
+///
+/// `declared` contains the properties (and keyword values) declared in the same rule,
+/// so that the prefixed declarations written by hand (before or after this one) are not duplicated.
+fn write_prefixed_declarations(
+    input: &mut StepParser,
+    ss: &mut StyleSheetTransformer,
+    declared: &HashSet<String>,
+) {
+    let state = input.state();
+    let Ok(name) = input.next() else {
+        return;
+    };
+    let Token::Ident(prop) = &*name else {
+        return;
+    };
+    if prop.starts_with("--") {
+        return;
+    }
+    let prop = prop.to_ascii_lowercase();
+    let value = input
+        .try_parse::<_, _, ()>(|input| {
+            input.expect_colon().map_err(|_| ())?;
+            let value = input.expect_ident_cloned().map_err(|_| ())?;
+            let important = input
+                .try_parse::<_, _, ParseError<()>>(|input| {
+                    input.expect_delim('!')?;
+                    input.expect_ident_matching("important")?;
+                    Ok(())
+                })
+                .is_ok();
+            match input.next() {
+                Ok(next) if !matches!(&*next, Token::Semicolon) => Err(()),
+                _ => Ok((value, important)),
+            }
+        })
+        .ok();
+    input.reset(&state);
+
+    // decide what to write
+    let targets = ss.options.autoprefix.as_deref().unwrap_or_default();
+    let prefixed_prop = format!("-webkit-{}", prop);
+    let prop_needed =
+        prefix::property_needs_prefix(&prop, targets) && !declared.contains(&prefixed_prop);
+    let value_needed = value.clone().filter(|(value, _)| {
+        let prefixed = format!("{}:-webkit-{}", prop, value.to_ascii_lowercase());
+        prefix::value_needs_prefix(&prop, value, targets) && !declared.contains(&prefixed)
+    });
+
+    // the prefixed property with the same value
+    if prop_needed {
+        let diagnostics_len = ss.diagnostics_len();
+        input.next().ok();
+        let st = StepToken::wrap_at(Token::Ident(prefixed_prop.into()), &name);
+        ss.append_token(st, input, Some(name.token.clone()));
+        input
+            .parse_until_before::<_, (), ()>(Delimiter::Semicolon, |nested_input| {
+                let input = &mut StepParser::wrap(nested_input);
+                convert_rpx_in_tokens(input, ss, false);
+                Ok(())
+            })
+            .ok();
+        ss.append_token(StepToken::wrap_at(Token::Semicolon, &name), input, None);
+        ss.truncate_diagnostics(diagnostics_len);
+        input.reset(&state);
+    }
+
+    // the same property with the prefixed value
+    if let Some((value, important)) = value_needed {
+        let prefixed_value = format!("-webkit-{}", &*value);
+        ss.append_token(name.clone(), input, None);
+        ss.append_token(StepToken::wrap_at(Token::Colon, &name), input, None);
+        let st = StepToken::wrap_at(Token::Ident(prefixed_value.into()), &name);
+        ss.append_token(st, input, Some(Token::Ident(value)));
+        if important {
+            ss.append_token(StepToken::wrap_at(Token::Delim('!'), &name), input, None);
+            let st = StepToken::wrap_at(Token::Ident("important".into()), &name);
+            ss.append_token(st, input, None);
+        }
+        ss.append_token(StepToken::wrap_at(Token::Semicolon, &name), input, None);
+    }
+}
+
+fn convert_class_names_and_rpx_in_block(input: &mut StepParser, ss: &mut StyleSheetTransformer) {
+    input
+        .parse_nested_block::<_, (), ()>(|nested_input| {
+            let input = &mut StepParser::wrap(nested_input);
+            let mut in_class = false;
+            let mut has_whitespace = false;
+            input.skip_whitespace();
+            loop {
+                let next = input.next_including_whitespace()?;
+                match &*next {
+                    Token::CurlyBracketBlock | Token::WhiteSpace(_) => {}
+                    _ => {
+                        if has_whitespace {
+                            let st = StepToken::wrap(Token::WhiteSpace(" "), next.position);
+                            ss.append_token_space_preserved(st, input, None);
+                        }
+                    }
+                }
+                has_whitespace = false;
+                match &*next {
+                    Token::CurlyBracketBlock
+                    | Token::SquareBracketBlock
+                    | Token::ParenthesisBlock => {
+                        let close = ss.append_nested_block(next, input);
                         convert_class_names_and_rpx_in_block(input, ss);
                         ss.append_nested_block_close(close, input);
                         in_class = false;
@@ -622,15 +1821,8 @@ fn convert_class_names_and_rpx_in_block(input: &mut StepParser, ss: &mut StyleSh
                         write_maybe_class_name(input, ss, &next, src, in_class);
                         in_class = false;
                     }
-                    Token::Dimension {
-                        has_sign,
-                        value,
-                        unit,
-                        int_value,
-                    } => {
-                        write_maybe_rpx_dimension(
-                            input, ss, &next, *has_sign, *value, *int_value, unit,
-                        );
+                    Token::Dimension { .. } => {
+                        write_maybe_rpx_dimension(input, ss, &next, false);
                         in_class = false;
                     }
                     Token::WhiteSpace(_) => {
@@ -647,6 +1839,27 @@ fn convert_class_names_and_rpx_in_block(input: &mut StepParser, ss: &mut StyleSh
         .ok();
 }
 
+/// Record the `var()` reference whose function token has just been consumed.
+fn record_var_reference(input: &mut StepParser, ss: &mut StyleSheetTransformer) {
+    let state = input.state();
+    let r = input.parse_nested_block::<_, _, ()>(|nested_input| {
+        let input = &mut StepParser::wrap(nested_input);
+        let next = input.next()?;
+        let Token::Ident(name) = &*next else {
+            return Err(input.new_custom_error(()));
+        };
+        let has_fallback = matches!(input.next().as_deref(), Ok(Token::Comma));
+        while input.next().is_ok() {}
+        Ok((name.to_string(), next.position, has_fallback))
+    });
+    input.reset(&state);
+    if let Ok((name, position, has_fallback)) = r {
+        if name.starts_with("--") {
+            ss.add_css_variable_usage(&name, position, true, has_fallback);
+        }
+    }
+}
+
 struct ConvertOptions {
     in_calc: bool,
 }
@@ -656,219 +1869,1107 @@ fn convert_rpx_in_block(
     ss: &mut StyleSheetTransformer,
     convert_options: Option<ConvertOptions>,
 ) {
-    let mut skip_whitespace = true;
-    let mut in_calc = false;
-    if let Some(options) = convert_options {
-        if options.in_calc {
-            skip_whitespace = false;
-            in_calc = true;
-        }
-    }
+    let in_calc = convert_options.map(|x| x.in_calc).unwrap_or(false);
     input
         .parse_nested_block::<_, (), ()>(|nested_input| {
             let input = &mut StepParser::wrap(nested_input);
-            let mut prev_token: Option<StepToken> = None;
-            loop {
-                let next = if skip_whitespace {
-                    input.next()?
+            convert_rpx_in_tokens(input, ss, in_calc);
+            Ok(())
+        })
+        .ok();
+}
+
+fn convert_rpx_in_tokens(input: &mut StepParser, ss: &mut StyleSheetTransformer, in_calc: bool) {
+    let skip_whitespace = !in_calc;
+    let mut prev_token: Option<StepToken> = None;
+    loop {
+        let next = if skip_whitespace {
+            input.next()
+        } else {
+            input.next_including_whitespace()
+        };
+        let Ok(next) = next else {
+            break;
+        };
+        match &*next {
+            Token::CurlyBracketBlock | Token::SquareBracketBlock | Token::ParenthesisBlock => {
+                let close = ss.append_nested_block(next.clone(), input);
+                convert_rpx_in_block(input, ss, None);
+                ss.append_nested_block_close(close, input);
+            }
+            Token::Function(func) => {
+                let func: &str = func;
+                let config = if func == "calc" {
+                    Some(ConvertOptions { in_calc: true })
                 } else {
-                    input.next_including_whitespace()?
+                    None
                 };
-                match &*next {
-                    Token::CurlyBracketBlock
-                    | Token::SquareBracketBlock
-                    | Token::ParenthesisBlock => {
-                        let close = ss.append_nested_block(next.clone(), input);
-                        convert_rpx_in_block(input, ss, None);
-                        ss.append_nested_block_close(close, input);
-                    }
-                    Token::Function(func) => {
-                        let func: &str = func;
-                        let config = if func == "calc" {
-                            Some(ConvertOptions { in_calc: true })
-                        } else {
-                            None
-                        };
-                        let close = ss.append_nested_block(next.clone(), input);
-                        convert_rpx_in_block(input, ss, config);
-                        ss.append_nested_block_close(close, input);
-                    }
-                    Token::Dimension {
-                        has_sign,
-                        value,
-                        unit,
-                        int_value,
-                    } => {
-                        write_maybe_rpx_dimension(
-                            input, ss, &next, *has_sign, *value, *int_value, unit,
-                        );
-                    }
-                    Token::WhiteSpace(_) => {
-                        let mut skip = true;
-                        if in_calc {
-                            // In calc(), the + and - operators must be surrounded by whitespace.
-                            // match next token
-                            let _ = input.try_parse::<_, (), ()>(|input| {
-                                let next_token =
-                                    input.next_including_whitespace().map_err(|_| ())?;
-                                match &*next_token {
-                                    Token::Delim(c) if *c == '+' || *c == '-' => {
-                                        skip = false;
-                                    }
-                                    _ => {}
-                                }
-                                Err(())
-                            });
-                            // match prev token
-                            if let Some(prev_token) = prev_token {
-                                match &*prev_token {
-                                    Token::Delim(c) if *c == '+' || *c == '-' => {
-                                        skip = false;
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                        if !skip {
-                            let st = StepToken::wrap(Token::WhiteSpace(" "), next.position);
-                            ss.append_token(st, input, None);
-                        }
-                    }
-                    _ => {
-                        ss.append_token(next.clone(), input, None);
+                if func.eq_ignore_ascii_case("var") {
+                    record_var_reference(input, ss);
+                }
+                let close = ss.append_nested_block(next.clone(), input);
+                convert_rpx_in_block(input, ss, config);
+                ss.append_nested_block_close(close, input);
+            }
+            Token::Dimension { .. } => {
+                write_maybe_rpx_dimension(input, ss, &next, in_calc);
+            }
+            Token::WhiteSpace(_) => {
+                let mut skip = true;
+                if in_calc {
+                    // In calc(), the + and - operators must be surrounded by whitespace.
+                    // match next token
+                    let _ = input.try_parse::<_, (), ()>(|input| {
+                        let next_token = input.next_including_whitespace().map_err(|_| ())?;
+                        match &*next_token {
+                            Token::Delim(c) if *c == '+' || *c == '-' => {
+                                skip = false;
+                            }
+                            _ => {}
+                        }
+                        Err(())
+                    });
+                    // match prev token
+                    if let Some(prev_token) = prev_token {
+                        match &*prev_token {
+                            Token::Delim(c) if *c == '+' || *c == '-' => {
+                                skip = false;
+                            }
+                            _ => {}
+                        }
                     }
                 }
-                prev_token = Some(next);
+                if !skip {
+                    let st = StepToken::wrap(Token::WhiteSpace(" "), next.position);
+                    ss.append_token(st, input, None);
+                }
             }
-        })
-        .ok();
+            _ => {
+                ss.append_token(next.clone(), input, None);
+            }
+        }
+        prev_token = Some(next);
+    }
 }
 
 #[cfg(test)]
 mod test {
     use sourcemap::SourceMap;
 
-    use super::*;
+    use super::*;
+
+    #[test]
+    fn remove_comments() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#" /* test */ .a { } "#,
+            StyleSheetOptions {
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        let mut sm = Vec::new();
+        output.write_source_map(&mut sm).unwrap();
+        assert_eq!(std::str::from_utf8(&s).unwrap(), ".a{}");
+    }
+
+    #[test]
+    fn add_class_prefix() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                #a.b   [g] .c.d g:e(.f) {
+                    key: .v f(.c) d.e;
+                }
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        let mut sm = Vec::new();
+        output.write_source_map(&mut sm).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            "#a.p--b [g] .p--c.p--d g:e(.p--f){key:.v f(.c)d.e;}"
+        );
+    }
+
+    #[test]
+    fn allow_class_prefix_sign() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                .a g:e(.f) {}
+            "#,
+            StyleSheetOptions {
+                class_prefix: None,
+                class_prefix_sign: Some("TEST".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        let mut sm = Vec::new();
+        output.write_source_map(&mut sm).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            "./*TEST*/a g:e(./*TEST*/f){}"
+        );
+    }
+
+    #[test]
+    fn allow_class_prefix_sign_before_prefix() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                .a {}
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                class_prefix_sign: Some("TEST".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        let mut sm = Vec::new();
+        output.write_source_map(&mut sm).unwrap();
+        assert_eq!(std::str::from_utf8(&s).unwrap(), "./*TEST*/p--a{}");
+    }
+
+    #[test]
+    fn transform_rpx() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                #a.b[1rpx]  (7.5rpx){
+                    key: 7.5rpx f(7.5rpx);
+                }
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            "#a.p--b[0.133333vw] (1vw){key:1vw f(1vw);}"
+        );
+    }
+
+    #[test]
+    fn transform_rpx_in_simple_at_rules() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                @a 75rpx;
+                .b {}
+                @c (75rpx .d) { 75rpx .e }
+                .f {}
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            "@a 75rpx;.p--b{}@c(10vw .p--d){10vw.e}.p--f{}"
+        );
+    }
+
+    #[test]
+    fn rules_in_at_media() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                @media (width: 1rpx) {
+                    .b [2rpx] {
+                        key: 3rpx .a;
+                    }
+                }
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                rpx_ratio: 10.,
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            "@media(width: 10vw){.p--b [20vw]{key:30vw.a;}}"
+        );
+    }
+
+    #[test]
+    fn rpx_strategy_px() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                @media (min-width: 750rpx) {
+                    .a { width: 300rpx; border: 1rpx solid; margin: -1rpx 0rpx calc(2rpx + 10%); }
+                }
+            "#,
+            StyleSheetOptions {
+                rpx_strategy: RpxStrategy::Px { screen_width: 375. },
+                rpx_min_one_px: true,
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            "@media(min-width: 375px){.a{width:150px;border:1px solid;margin:-1px 0px calc(1px + 10%);}}"
+        );
+    }
+
+    #[test]
+    fn rpx_strategy_css_var() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                @media (width: 75rpx) {
+                    .a { width: 3rpx; margin: -1rpx calc(10rpx * 2 + 30px); }
+                }
+            "#,
+            StyleSheetOptions {
+                rpx_strategy: "var".parse().unwrap(),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            "@media(width: 10vw){.a{width:calc(var(--rpx)*3);margin:calc(var(--rpx)*-1)calc((var(--rpx)*10)*2 + 30px);}}"
+        );
+    }
+
+    #[test]
+    fn rpx_precision() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#" .a { width: 1rpx; height: 100rpx; margin: 1rpx; } "#,
+            StyleSheetOptions {
+                rpx_strategy: RpxStrategy::Px { screen_width: 414. },
+                rpx_precision: Some(2),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            ".a{width:0.55px;height:55.2px;margin:0.55px;}"
+        );
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#" .a { width: 1rpx; margin: -1rpx; height: 0rpx } "#,
+            StyleSheetOptions {
+                rpx_precision: Some(0),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            ".a{width:1vw;margin:-1vw;height:0vw}"
+        );
+    }
+
+    #[test]
+    fn parse_rpx_strategy() {
+        assert_eq!("vw".parse(), Ok(RpxStrategy::Vw));
+        assert_eq!("px:375".parse(), Ok(RpxStrategy::Px { screen_width: 375. }));
+        assert_eq!(
+            "var:--a".parse(),
+            Ok(RpxStrategy::CssVar { name: "--a".into() })
+        );
+        assert!("px".parse::<RpxStrategy>().is_err());
+        assert!("px:0".parse::<RpxStrategy>().is_err());
+        assert!("var:a".parse::<RpxStrategy>().is_err());
+        assert!("rem".parse::<RpxStrategy>().is_err());
+    }
+
+    #[test]
+    fn host_select() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                :host {
+                    color: red;
+                }
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("ABC".into()),
+                convert_host: true,
+                ..Default::default()
+            },
+        );
+        let (output, lp) = trans.output_and_low_priority_output();
+        let mut s = Vec::new();
+        output.write(&mut s).unwrap();
+        assert!(std::str::from_utf8(&s).unwrap().is_empty());
+        let mut s = Vec::new();
+        lp.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"[wx-host="ABC"]{color:red;}"#
+        );
+    }
+
+    #[test]
+    fn host_combination() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                :host(.a) {
+                    color: red;
+                }
+                :host .a > .b, .c {
+                    color: green;
+                }
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                convert_host: true,
+                host_is: Some("IS".into()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(trans.warnings().count(), 0);
+        let (output, lp) = trans.output_and_low_priority_output();
+        let mut s = Vec::new();
+        output.write(&mut s).unwrap();
+        assert_eq!(std::str::from_utf8(&s).unwrap(), r#".p--c{color:green;}"#);
+        let mut s = Vec::new();
+        lp.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"[wx-host="p"].p--a,[is="IS"].p--a{color:red;}[wx-host="p"] .p--a>.p--b,[is="IS"] .p--a>.p--b{color:green;}"#
+        );
+    }
+
+    #[test]
+    fn illegal_host_combination() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                .a :host {
+                    color: red;
+                }
+                :host.a, :is(:host) {
+                    color: red;
+                }
+                :host(.a .b), .a { color: green }
+            "#,
+            StyleSheetOptions {
+                convert_host: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            trans.warnings().map(|x| x.kind.clone()).collect::<Vec<_>>(),
+            [
+                error::ParseErrorKind::HostSelectorCombination,
+                error::ParseErrorKind::HostSelectorCombination,
+                error::ParseErrorKind::HostSelectorCombination,
+                error::ParseErrorKind::HostSelectorCombination,
+            ],
+        );
+        let (output, lp) = trans.output_and_low_priority_output();
+        let mut s = Vec::new();
+        output.write(&mut s).unwrap();
+        assert_eq!(std::str::from_utf8(&s).unwrap(), r#".a{color:green}"#);
+        let mut s = Vec::new();
+        lp.write(&mut s).unwrap();
+        assert_eq!(std::str::from_utf8(&s).unwrap(), r#""#);
+    }
+
+    #[test]
+    fn class_prefix_in_pseudo_class_arguments() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                :is(.a, :not(.b)) :where(.c):has(> .d) li:nth-child(2n + 1 of .e) {}
+                :lang(a.b) :nth-of-type(2n) [class~=f] ::slotted(.g) {}
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#":is(.p--a,:not(.p--b)) :where(.p--c):has(>.p--d) li:nth-child(2n + 1 of .p--e){}:lang(a.b) :nth-of-type(2n) [class~=f] ::slotted(.p--g){}"#
+        );
+    }
+
+    #[test]
+    fn malformed_selector_lists() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#".c > {color:red} .d, {color:blue} ,.a,,.b > + .e {} :is(.f,) {}"#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                ..Default::default()
+            },
+        );
+        let warnings: Vec<_> = trans
+            .warnings()
+            .map(|x| (x.kind.clone(), x.location.start.line_col_utf16()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (error::ParseErrorKind::InvalidSelector, (0, 3)),
+                (error::ParseErrorKind::InvalidSelector, (0, 19)),
+                (error::ParseErrorKind::InvalidSelector, (0, 34)),
+            ],
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#".p--c>{color:red}.p--d,{color:blue},.p--a,,.p--b>+.p--e{}:is(.p--f,){}"#
+        );
+    }
+
+    #[test]
+    fn collect_class_names() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                .a .b, :not(.c) {}
+                :host(.d) { .e { .a {} } }
+                @media (width: 1px) { [class~=f] .g:is(.a) {} }
+            "#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                convert_host: true,
+                flatten_nesting: true,
+                ..Default::default()
+            },
+        );
+        let names: Vec<_> = trans
+            .class_names()
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.line, v.utf16_col))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("a", 1, 17),
+                ("b", 1, 20),
+                ("c", 1, 29),
+                ("d", 2, 23),
+                ("e", 2, 29),
+                ("g", 3, 50),
+            ],
+        );
+    }
+
+    #[test]
+    fn host_select_inside_at_rules() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                @media (width: 1px) {
+                    @supports (color: red) {
+                        .a {
+                            color: red;
+                        }
+                        :host {
+                            color: pink;
+                        }
+                        .b {
+                            color: green;
+                        }
+                    }
+                }
+            "#,
+            StyleSheetOptions {
+                class_prefix: None,
+                convert_host: true,
+                host_is: Some("IS".into()),
+                ..Default::default()
+            },
+        );
+        let (output, lp) = trans.output_and_low_priority_output();
+        let mut s = Vec::new();
+        output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"@media(width: 1px){@supports(color: red){.a{color:red;}.b{color:green;}}}"#
+        );
+        let mut s = Vec::new();
+        lp.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"@media(width: 1px){@supports(color: red){[wx-host=""],[is="IS"]{color:pink;}}}"#
+        );
+    }
+
+    #[test]
+    fn import_sign_urlencoded() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                @import './a\\b*?'
+            "#,
+            StyleSheetOptions {
+                import_sign: Some("TEST".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"/*TEST .%2Fa%5Cb%2A%3F*/"#
+        );
+    }
+
+    #[test]
+    fn import_sign_with_media_queries() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                @import './a' (min-width: 10px);
+                .a { }
+            "#,
+            StyleSheetOptions {
+                import_sign: Some("TEST".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"@media(min-width: 10px){/*TEST .%2Fa*/}.a{}"#
+        );
+    }
+
+    #[test]
+    fn import_sign_with_supports() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                @import './a' layer(a) supports(color: red) print and (min-width: 10px);
+            "#,
+            StyleSheetOptions {
+                import_sign: Some("TEST".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"@layer a{@supports(color: red){@media print and (min-width: 10px){/*TEST .%2Fa*/}}}"#
+        );
+    }
+
+    #[test]
+    fn import_sign_not_at_top() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                .a {}
+                @import './a';
+            "#,
+            StyleSheetOptions {
+                import_sign: Some("TEST".into()),
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        assert_eq!(
+            trans.warnings().map(|x| x.kind.clone()).collect::<Vec<_>>(),
+            [error::ParseErrorKind::IllegalImportPosition],
+        );
+        let output = trans.output();
+        output.write(&mut s).unwrap();
+        assert_eq!(std::str::from_utf8(&s).unwrap(), r#".a{}/*TEST .%2Fa*/"#);
+    }
+
+    #[test]
+    fn css_variables() {
+        let trans = StyleSheetTransformer::from_css(
+            "index",
+            r#"
+                :host { --a: 1px; color: var(--b) }
+                .c { margin: calc(var(--a) * 2) var(--d, 1px) var(--e); --f: var(--g) }
+            "#,
+            StyleSheetOptions {
+                convert_host: true,
+                known_css_variables: Some(vec!["--b".into()]),
+                ..Default::default()
+            },
+        );
+        let list = |x: &[CssVariableUsage]| {
+            x.iter()
+                .map(|x| {
+                    (
+                        x.name.clone(),
+                        x.location.start.line,
+                        x.location.start.utf16_col,
+                        x.has_fallback,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            list(trans.custom_properties()),
+            [
+                ("--a".to_string(), 1, 24, false),
+                ("--f".to_string(), 2, 72, false)
+            ],
+        );
+        assert_eq!(
+            list(trans.var_references()),
+            [
+                ("--b".to_string(), 1, 45, false),
+                ("--a".to_string(), 2, 38, false),
+                ("--d".to_string(), 2, 52, true),
+                ("--e".to_string(), 2, 66, false),
+                ("--g".to_string(), 2, 81, false),
+            ],
+        );
+        let warnings: Vec<_> = trans
+            .warnings()
+            .map(|x| {
+                (
+                    x.kind.clone(),
+                    x.location.start.utf16_col,
+                    x.location.end.utf16_col,
+                )
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (error::ParseErrorKind::UndeclaredCssVariable, 66, 69),
+                (error::ParseErrorKind::UndeclaredCssVariable, 81, 84),
+            ],
+        );
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#" .a { color: var(--b) } "#,
+            StyleSheetOptions::default(),
+        );
+        assert_eq!(trans.warnings().count(), 0);
+    }
+
+    fn import_resolver(
+        files: &'static [(&'static str, &'static str)],
+    ) -> impl FnMut(&str, &str) -> Option<(String, String)> {
+        |_importer, rel_path| {
+            files
+                .iter()
+                .find(|x| x.0 == rel_path)
+                .map(|x| (x.0.to_string(), x.1.to_string()))
+        }
+    }
 
     #[test]
-    fn remove_comments() {
-        let trans = StyleSheetTransformer::from_css(
-            "",
-            r#" /* test */ .a { } "#,
+    fn bundle_imports() {
+        let trans = StyleSheetTransformer::from_css_bundled(
+            "index",
+            r#"
+                @import "a" layer(x) supports(color: red) (min-width: 10px);
+                @import url(b) layer;
+                .c { width: 7.5rpx }
+            "#,
             StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                convert_host: true,
                 ..Default::default()
             },
+            import_resolver(&[
+                ("a", "@import 'b'; .a { color: red } :host { color: blue }"),
+                ("b", ".b {}"),
+            ]),
+        );
+        assert_eq!(trans.warnings().count(), 0);
+        let (output, low_priority_output) = trans.output_and_low_priority_output();
+        let mut s = Vec::new();
+        output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"@layer x{@supports(color: red){@media(min-width: 10px){.p--b{}.p--a{color:red}}}}@layer{.p--b{}}.p--c{width:1vw}"#
+        );
+        let mut s = Vec::new();
+        low_priority_output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"@layer x{@supports(color: red){@media(min-width: 10px){[wx-host="p"]{color:blue}}}}"#
+        );
+    }
+
+    #[test]
+    fn bundle_imports_errors() {
+        let trans = StyleSheetTransformer::from_css_bundled(
+            "index",
+            r#"@import "a"; @import "c"; .c {}"#,
+            StyleSheetOptions::default(),
+            import_resolver(&[("a", "@import 'b';"), ("b", "@import 'a';")]),
+        );
+        let warnings: Vec<_> = trans
+            .warnings()
+            .map(|x| (x.path.as_str(), x.kind.clone(), x.location.start.utf16_col))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                ("b", error::ParseErrorKind::CircularImport, 7),
+                ("index", error::ParseErrorKind::ImportNotFound, 20),
+            ],
         );
         let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(std::str::from_utf8(&s).unwrap(), ".c{}");
+    }
+
+    #[test]
+    fn bundle_imports_resolver_failed() {
+        struct FailingResolver;
+        impl ImportResolver for FailingResolver {
+            fn resolve(
+                &mut self,
+                _importer: &str,
+                rel_path: &str,
+            ) -> Result<Option<(String, String)>, String> {
+                Err(format!("cannot read {}", rel_path))
+            }
+        }
+        let trans = StyleSheetTransformer::from_css_bundled(
+            "index",
+            r#"@import "a"; .c {}"#,
+            StyleSheetOptions::default(),
+            FailingResolver,
+        );
+        let warnings: Vec<_> = trans.warnings().collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, error::ParseErrorKind::ImportFailed);
+        assert_eq!(warnings[0].detail.as_deref(), Some("cannot read a"));
+        assert_eq!(
+            warnings[0].to_string(),
+            "style sheet parsing error at index:1:8-1:8: failed to load the imported stylesheet (cannot read a)",
+        );
+    }
+
+    #[test]
+    fn bundle_imports_source_map() {
+        let trans = StyleSheetTransformer::from_css_bundled(
+            "index",
+            "@import 'a';\n.c { color: red }",
+            StyleSheetOptions {
+                minify: true,
+                ..Default::default()
+            },
+            import_resolver(&[("a", ".a {}\n.b { width: 75rpx }")]),
+        );
         let output = trans.output();
+        let mut s = Vec::new();
         output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            ".b{width:10vw}.c{color:red}"
+        );
         let mut sm = Vec::new();
         output.write_source_map(&mut sm).unwrap();
-        assert_eq!(std::str::from_utf8(&s).unwrap(), ".a{}");
+        let sm: &[u8] = &sm;
+        let source_map = SourceMap::from_reader(sm).unwrap();
+        let check = |col, source, src_line, src_col| {
+            let token = source_map.lookup_token(0, col).unwrap();
+            assert_eq!(token.get_dst_col(), col);
+            assert_eq!(token.get_source(), Some(source));
+            assert_eq!(token.get_src_line(), src_line);
+            assert_eq!(token.get_src_col(), src_col);
+        };
+        check(0, "a", 1, 0);
+        check(9, "a", 1, 12);
+        check(14, "index", 1, 0);
+        check(17, "index", 1, 5);
+        assert_eq!(
+            source_map.get_source_contents(1),
+            Some(".a {}\n.b { width: 75rpx }")
+        );
     }
 
     #[test]
-    fn add_class_prefix() {
+    fn minify_calc() {
         let trans = StyleSheetTransformer::from_css(
             "",
             r#"
-                #a.b   [g] .c.d g:e(.f) {
-                    key: .v f(.c) d.e;
+                .a {
+                    margin: 10px 20rpx 30px calc(10px + 20px / 2);
+                    padding: calc(10rpx  *  2  +  30px);
                 }
             "#,
             StyleSheetOptions {
                 class_prefix: Some("p".into()),
+                rpx_ratio: 10.,
                 ..Default::default()
             },
         );
         let mut s = Vec::new();
         let output = trans.output();
         output.write(&mut s).unwrap();
-        let mut sm = Vec::new();
-        output.write_source_map(&mut sm).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            "#a.p--b [g] .p--c.p--d g:e(.p--f){key:.v f(.c)d.e;}"
+            ".p--a{margin:10px 200vw 30px calc(10px + 20px/2);padding:calc(100vw*2 + 30px);}"
         );
     }
 
     #[test]
-    fn allow_class_prefix_sign() {
+    fn minify_calc_source_map() {
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#"
-                .a g:e(.f) {}
-            "#,
+            r#" .a { padding: calc(10rpx  *  2  +  30px); } "#,
             StyleSheetOptions {
-                class_prefix: None,
-                class_prefix_sign: Some("TEST".into()),
+                class_prefix: Some("p".into()),
+                rpx_ratio: 10.,
                 ..Default::default()
             },
         );
         let mut s = Vec::new();
         let output = trans.output();
         output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            ".p--a{padding:calc(100vw*2 + 30px);}"
+        );
         let mut sm = Vec::new();
         output.write_source_map(&mut sm).unwrap();
+        let sm: &[u8] = &sm;
+        let source_map = SourceMap::from_reader(sm).unwrap();
+        {
+            let token = source_map.lookup_token(0, 0).unwrap();
+            assert_eq!(token.get_name(), None);
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 1);
+        }
+        {
+            let token = source_map.lookup_token(0, 1).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 2);
+        }
+        {
+            let token = source_map.lookup_token(0, 5).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 4);
+        }
+        {
+            let token = source_map.lookup_token(0, 6).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 6);
+        }
+        {
+            let token = source_map.lookup_token(0, 14).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 15);
+        }
+        {
+            let token = source_map.lookup_token(0, 19).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 20);
+        }
+        {
+            let token = source_map.lookup_token(0, 24).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 27);
+        }
+        {
+            let token = source_map.lookup_token(0, 27).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 33);
+        }
+        {
+            let token = source_map.lookup_token(0, 29).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 36);
+        }
+    }
+
+    #[test]
+    fn minify() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#"
+                .a { color: #FFFFFF; color: #FFFFFF; margin: 0px 0.5em 10px; }
+                .a { padding: 0rpx; }
+                .b {}
+                @media (width: 1px) { .c {} }
+                .d { display: -webkit-box; display: flex; background: rgb(255, 0, 0); border-color: #ff000080; }
+                .e { color: red !important; color: blue; flex: 1 1 0px; --x: 0px; }
+                .f { width: calc(0px + 10%); opacity: 0.50; }
+                .a { color: #aabbccff }
+                .g { position: relative; position: sticky; display: block; display: grid; color: red; color: red; width: 1px; width: 2px !important; }
+                .h { width: 10.0px; height: 1.50px; opacity: 0.0; flex: 1.0; width: 90%; width: calc(100% - 10px); position: -webkit-sticky; position: sticky; }
+            "#,
+            StyleSheetOptions {
+                minify: true,
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            "./*TEST*/a g:e(./*TEST*/f){}"
+            ".a{color:#fff;margin:0 .5em 10px;padding:0}.d{display:-webkit-box;display:flex;background:red;border-color:#ff000080}.e{color:red!important;flex:1 1 0px;--x:0px}.f{width:calc(0px + 10%);opacity:.5}.a{color:#abc}.g{position:sticky;display:grid;color:red;width:2px!important}.h{width:10px;height:1.5px;opacity:0;flex:1;width:90%;width:calc(100% - 10px);position:-webkit-sticky;position:sticky}"
         );
     }
 
     #[test]
-    fn allow_class_prefix_sign_before_prefix() {
+    fn minify_nesting_and_host() {
         let trans = StyleSheetTransformer::from_css(
             "",
             r#"
-                .a {}
+                .a { .b { color: red } }
+                :host { color: #000000 }
+                :host { }
+                @media (width: 1px) { :host { color: red; color: blue } }
             "#,
             StyleSheetOptions {
-                class_prefix: Some("p".into()),
-                class_prefix_sign: Some("TEST".into()),
+                convert_host: true,
+                flatten_nesting: true,
+                minify: true,
                 ..Default::default()
             },
         );
+        let (output, low_priority_output) = trans.output_and_low_priority_output();
+        let mut s = Vec::new();
+        output.write(&mut s).unwrap();
+        assert_eq!(std::str::from_utf8(&s).unwrap(), ".a .b{color:red}");
         let mut s = Vec::new();
+        low_priority_output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            "[wx-host=\"\"]{color:#000}@media(width: 1px){[wx-host=\"\"]{color:blue}}"
+        );
+    }
+
+    #[test]
+    fn minify_source_map() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            ".a{}\n.b { color: #ffffff; color: #ffffff; width: 75rpx }",
+            StyleSheetOptions {
+                minify: true,
+                ..Default::default()
+            },
+        );
         let output = trans.output();
+        let mut s = Vec::new();
         output.write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            ".b{color:#fff;width:10vw}"
+        );
         let mut sm = Vec::new();
         output.write_source_map(&mut sm).unwrap();
-        assert_eq!(std::str::from_utf8(&s).unwrap(), "./*TEST*/p--a{}");
+        let sm: &[u8] = &sm;
+        let source_map = SourceMap::from_reader(sm).unwrap();
+        let check = |col, src_line, src_col, name| {
+            let token = source_map.lookup_token(0, col).unwrap();
+            assert_eq!(token.get_dst_col(), col);
+            assert_eq!(token.get_src_line(), src_line);
+            assert_eq!(token.get_src_col(), src_col);
+            assert_eq!(token.get_name(), name);
+        };
+        check(0, 1, 0, None);
+        check(1, 1, 1, None);
+        check(3, 1, 21, None);
+        check(9, 1, 28, None);
+        check(14, 1, 37, None);
+        check(20, 1, 44, Some("75rpx"));
     }
 
     #[test]
-    fn transform_rpx() {
+    fn source_map() {
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#"
-                #a.b[1rpx]  (7.5rpx){
-                    key: 7.5rpx f(7.5rpx);
-                }
-            "#,
+            r#" .a { width: 1rpx } "#,
             StyleSheetOptions {
                 class_prefix: Some("p".into()),
+                rpx_ratio: 10.,
                 ..Default::default()
             },
         );
         let mut s = Vec::new();
         let output = trans.output();
         output.write(&mut s).unwrap();
-        assert_eq!(
-            std::str::from_utf8(&s).unwrap(),
-            "#a.p--b[0.133333vw] (1vw){key:1vw f(1vw);}"
-        );
+        assert_eq!(std::str::from_utf8(&s).unwrap(), ".p--a{width:10vw}");
+        let mut sm = Vec::new();
+        output.write_source_map(&mut sm).unwrap();
+        let sm: &[u8] = &sm;
+        let source_map = SourceMap::from_reader(sm).unwrap();
+        {
+            let token = source_map.lookup_token(0, 0).unwrap();
+            assert_eq!(token.get_name(), None);
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 1);
+        }
+        {
+            let token = source_map.lookup_token(0, 1).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 2);
+        }
+        {
+            let token = source_map.lookup_token(0, 5).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 4);
+        }
+        {
+            let token = source_map.lookup_token(0, 6).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 6);
+        }
+        {
+            let token = source_map.lookup_token(0, 12).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 13);
+        }
     }
 
     #[test]
-    fn transform_rpx_in_simple_at_rules() {
+    fn nesting_preserved() {
         let trans = StyleSheetTransformer::from_css(
             "",
             r#"
-                @a 75rpx;
-                .b {}
-                @c (75rpx .d) { 75rpx .e }
-                .f {}
+                .a {
+                    color: red;
+                    &:hover { width: 2rpx }
+                    > .b, .c & { color: blue }
+                    @media (width: 10rpx) { .d { color: green } }
+                }
             "#,
             StyleSheetOptions {
                 class_prefix: Some("p".into()),
@@ -876,368 +2977,423 @@ mod test {
             },
         );
         let mut s = Vec::new();
-        let output = trans.output();
-        output.write(&mut s).unwrap();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            "@a 75rpx;.p--b{}@c(10vw .p--d){10vw.e}.p--f{}"
+            r#".p--a{color:red;&:hover{width:0.266667vw}>.p--b,.p--c &{color:blue}@media(width: 1.33333vw){.p--d{color:green}}}"#
         );
     }
 
     #[test]
-    fn rules_in_at_media() {
+    fn nesting_flattened() {
         let trans = StyleSheetTransformer::from_css(
             "",
             r#"
-                @media (width: 1rpx) {
-                    .b [2rpx] {
-                        key: 3rpx .a;
-                    }
+                .a, .b {
+                    color: red;
+                    &:hover { width: 2rpx }
+                    .c, &.d { .e { x: y } }
+                    > .f { z: 1 }
+                    :not(&) { w: 0 }
                 }
             "#,
             StyleSheetOptions {
                 class_prefix: Some("p".into()),
-                rpx_ratio: 10.,
+                flatten_nesting: true,
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#".p--a,.p--b{color:red;}.p--a:hover,.p--b:hover{width:0.266667vw}.p--a .p--c .p--e,.p--a.p--d .p--e,.p--b .p--c .p--e,.p--b.p--d .p--e{x:y}.p--a>.p--f,.p--b>.p--f{z:1}:not(.p--a),:not(.p--b){w:0}"#
+        );
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            r#".a, .b { .c { color: red } } .d {}"#,
+            StyleSheetOptions {
+                class_prefix: Some("p".into()),
+                flatten_nesting: true,
+                ..Default::default()
+            },
+        );
+        let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#".p--a .p--c,.p--b .p--c{color:red}.p--d{}"#
+        );
+    }
+
+    #[test]
+    fn nesting_invalid_selectors() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            ".a { &-b { color: red } }\n& .c { color: blue }",
+            StyleSheetOptions {
+                flatten_nesting: true,
                 ..Default::default()
             },
         );
-        let mut s = Vec::new();
-        let output = trans.output();
-        output.write(&mut s).unwrap();
+        let warnings: Vec<_> = trans
+            .warnings()
+            .map(|x| {
+                (
+                    x.kind.clone(),
+                    x.location.start.line_col_utf16(),
+                    x.location.end.line_col_utf16(),
+                )
+            })
+            .collect();
         assert_eq!(
-            std::str::from_utf8(&s).unwrap(),
-            "@media(width: 10vw){.p--b [20vw]{key:30vw.a;}}"
+            warnings,
+            [
+                (error::ParseErrorKind::InvalidSelector, (0, 6), (0, 8)),
+                (error::ParseErrorKind::InvalidSelector, (1, 0), (1, 1)),
+            ],
         );
     }
 
     #[test]
-    fn host_select() {
+    fn nesting_flattened_in_at_rules_and_host() {
         let trans = StyleSheetTransformer::from_css(
             "",
             r#"
+                .a {
+                    @media (width: 10rpx) {
+                        color: blue;
+                        .b { x: y }
+                    }
+                }
                 :host {
                     color: red;
+                    .c { x: y }
                 }
             "#,
             StyleSheetOptions {
-                class_prefix: Some("ABC".into()),
+                class_prefix: Some("p".into()),
                 convert_host: true,
+                flatten_nesting: true,
                 ..Default::default()
             },
         );
         let (output, lp) = trans.output_and_low_priority_output();
         let mut s = Vec::new();
         output.write(&mut s).unwrap();
-        assert!(std::str::from_utf8(&s).unwrap().is_empty());
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            r#"@media(width: 1.33333vw){.p--a{color:blue;}.p--a .p--b{x:y}}"#
+        );
         let mut s = Vec::new();
         lp.write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            r#"[wx-host="ABC"]{color:red;}"#
+            r#"[wx-host="p"]{color:red;}[wx-host="p"] .p--c{x:y}"#
         );
     }
 
     #[test]
-    fn illegal_host_combination() {
+    fn nesting_flattened_source_map() {
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#"
-                :host(.a) {
-                    color: red;
-                }
-                :host .a {
-                    color: red;
-                }
-                .a { color: green }
-            "#,
+            r#".a{&.b{}}"#,
             StyleSheetOptions {
-                convert_host: true,
+                class_prefix: Some("p".into()),
+                flatten_nesting: true,
                 ..Default::default()
             },
         );
-        assert_eq!(
-            trans.warnings().map(|x| x.kind.clone()).collect::<Vec<_>>(),
-            [
-                error::ParseErrorKind::HostSelectorCombination,
-                error::ParseErrorKind::HostSelectorCombination
-            ],
-        );
-        let (output, lp) = trans.output_and_low_priority_output();
         let mut s = Vec::new();
+        let output = trans.output();
         output.write(&mut s).unwrap();
-        assert_eq!(std::str::from_utf8(&s).unwrap(), r#".a{color:green}"#);
-        let mut s = Vec::new();
-        lp.write(&mut s).unwrap();
-        assert_eq!(std::str::from_utf8(&s).unwrap(), r#""#);
+        assert_eq!(std::str::from_utf8(&s).unwrap(), ".p--a.p--b{}");
+        let mut sm = Vec::new();
+        output.write_source_map(&mut sm).unwrap();
+        let sm: &[u8] = &sm;
+        let source_map = SourceMap::from_reader(sm).unwrap();
+        {
+            let token = source_map.lookup_token(0, 0).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 0);
+        }
+        {
+            let token = source_map.lookup_token(0, 5).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 4);
+        }
+        {
+            let token = source_map.lookup_token(0, 6).unwrap();
+            assert_eq!(token.get_src_line(), 0);
+            assert_eq!(token.get_src_col(), 5);
+            assert_eq!(token.get_name(), Some("b"));
+        }
     }
 
     #[test]
-    fn host_select_inside_at_rules() {
+    fn hash_class_names() {
         let trans = StyleSheetTransformer::from_css(
-            "",
+            "comp/a",
             r#"
-                @media (width: 1px) {
-                    @supports (color: red) {
-                        .a {
-                            color: red;
-                        }
-                        :host {
-                            color: pink;
-                        }
-                        .b {
-                            color: green;
-                        }
-                    }
-                }
+                .a .b, .a:not(.c) {}
+                @media (width: 1px) { .b {} }
             "#,
             StyleSheetOptions {
-                class_prefix: None,
-                convert_host: true,
-                host_is: Some("IS".into()),
+                class_prefix: Some("p".into()),
+                hash_class_names: true,
                 ..Default::default()
             },
         );
-        let (output, lp) = trans.output_and_low_priority_output();
+        let a = hash_class_name("comp/a", "a");
+        let b = hash_class_name("comp/a", "b");
+        let c = hash_class_name("comp/a", "c");
+        assert_ne!(a, b);
+        assert_ne!(a, hash_class_name("comp/b", "a"));
+        assert_eq!(
+            trans.class_name_mapping_json(),
+            format!(r#"{{"a":"{}","b":"{}","c":"{}"}}"#, a, b, c),
+        );
         let mut s = Vec::new();
-        output.write(&mut s).unwrap();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            r#"@media(width: 1px){@supports(color: red){.a{color:red;}.b{color:green;}}}"#
+            format!(
+                ".{a} .{b},.{a}:not(.{c}){{}}@media(width: 1px){{.{b}{{}}}}",
+                a = a,
+                b = b,
+                c = c
+            ),
+        );
+    }
+
+    #[test]
+    fn diagnostics() {
+        let trans = StyleSheetTransformer::from_css(
+            "",
+            ".a { color: red;; width }\n;\n.b::foo {}\n.c > {}\n@foo bar;\ncolor: red;\n.d { height: 1px }",
+            StyleSheetOptions::default(),
+        );
+        let warnings: Vec<_> = trans
+            .warnings()
+            .map(|x| {
+                (
+                    x.kind.clone(),
+                    x.location.start.line_col_utf16(),
+                    x.location.end.line_col_utf16(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (error::ParseErrorKind::StraySemicolon, (0, 16), (0, 17)),
+                (error::ParseErrorKind::InvalidDeclaration, (0, 18), (0, 23)),
+                (error::ParseErrorKind::StraySemicolon, (1, 0), (1, 1)),
+                (
+                    error::ParseErrorKind::UnsupportedPseudoElement,
+                    (2, 2),
+                    (2, 7)
+                ),
+                (error::ParseErrorKind::InvalidSelector, (3, 3), (3, 4)),
+                (error::ParseErrorKind::UnknownAtRule, (4, 0), (4, 4)),
+                (error::ParseErrorKind::InvalidSelector, (5, 0), (5, 11)),
+            ],
         );
         let mut s = Vec::new();
-        lp.write(&mut s).unwrap();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            r#"@media(width: 1px){@supports(color: red){[wx-host=""],[is="IS"]{color:pink;}}}"#
+            ".a{color:red;}.b::foo{}.c>{}@foo bar;.d{height:1px}"
         );
     }
 
     #[test]
-    fn import_sign_urlencoded() {
+    fn recover_from_malformed_rules() {
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#"
-                @import './a\\b*?'
-            "#,
-            StyleSheetOptions {
-                import_sign: Some("TEST".into()),
-                ..Default::default()
-            },
+            ".a { width: 1px }}\n.b { height: 1px }\n.c { color: f(red }",
+            StyleSheetOptions::default(),
+        );
+        let warnings: Vec<_> = trans
+            .warnings()
+            .map(|x| (x.kind.clone(), x.location.start.line_col_utf16(), x.level()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (
+                    error::ParseErrorKind::UnclosedBlock,
+                    (2, 12),
+                    error::ParseErrorLevel::Fatal
+                ),
+                (
+                    error::ParseErrorKind::UnexpectedCharacter,
+                    (0, 17),
+                    error::ParseErrorLevel::Fatal
+                ),
+            ],
         );
         let mut s = Vec::new();
-        let output = trans.output();
-        output.write(&mut s).unwrap();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            r#"/*TEST .%2Fa%5Cb%2A%3F*/"#
+            ".a{width:1px}.b{height:1px}.c{color:f(red})}"
         );
     }
 
+    fn autoprefix_targets(targets: &[&str]) -> Option<Vec<prefix::BrowserTarget>> {
+        Some(targets.iter().map(|x| x.parse().unwrap()).collect())
+    }
+
     #[test]
-    fn import_sign_with_media_queries() {
+    fn autoprefix() {
+        let css = r#"
+            .a { transform: translateX(75rpx); display: flex; user-select: none }
+            .b { -webkit-user-select: none; user-select: none; position: sticky !important }
+            @keyframes k { from { opacity: 0 } }
+        "#;
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#"
-                @import './a' (min-width: 10px);
-                .a { }
-            "#,
+            css,
             StyleSheetOptions {
-                import_sign: Some("TEST".into()),
+                class_prefix: Some("p".into()),
+                autoprefix: autoprefix_targets(&["ios >= 8", "android 4.4.4"]),
                 ..Default::default()
             },
         );
         let mut s = Vec::new();
-        let output = trans.output();
-        output.write(&mut s).unwrap();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            r#"@media(min-width: 10px){/*TEST .%2Fa*/}.a{}"#
+            ".p--a{-webkit-transform:translateX(10vw);transform:translateX(10vw);display:-webkit-flex;display:flex;-webkit-user-select:none;user-select:none}.p--b{-webkit-user-select:none;user-select:none;position:-webkit-sticky!important;position:sticky!important}@-webkit-keyframes k{from{opacity:0}}@keyframes k{from{opacity:0}}"
         );
-    }
 
-    #[test]
-    fn import_sign_with_supports() {
+        // modern targets need less prefixes
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#"
-                @import './a' layer(a) supports(color: red) print and (min-width: 10px);
-            "#,
+            css,
             StyleSheetOptions {
-                import_sign: Some("TEST".into()),
+                autoprefix: autoprefix_targets(&["chrome 80", "android 10"]),
                 ..Default::default()
             },
         );
         let mut s = Vec::new();
-        let output = trans.output();
-        output.write(&mut s).unwrap();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            r#"@layer a{@supports(color: red){@media print and (min-width: 10px){/*TEST .%2Fa*/}}}"#
+            ".a{transform:translateX(10vw);display:flex;user-select:none}.b{-webkit-user-select:none;user-select:none;position:sticky!important}@keyframes k{from{opacity:0}}"
         );
     }
 
     #[test]
-    fn import_sign_not_at_top() {
+    fn autoprefix_declared_later() {
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#"
-                .a {}
-                @import './a';
-            "#,
+            ".a { transform: none; -webkit-transform: none; display: flex; display: -webkit-flex }",
             StyleSheetOptions {
-                import_sign: Some("TEST".into()),
+                autoprefix: autoprefix_targets(&["ios 8"]),
                 ..Default::default()
             },
         );
         let mut s = Vec::new();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
-            trans.warnings().map(|x| x.kind.clone()).collect::<Vec<_>>(),
-            [error::ParseErrorKind::IllegalImportPosition],
+            std::str::from_utf8(&s).unwrap(),
+            ".a{transform:none;-webkit-transform:none;display:flex;display:-webkit-flex}"
         );
-        let output = trans.output();
-        output.write(&mut s).unwrap();
-        assert_eq!(std::str::from_utf8(&s).unwrap(), r#".a{}/*TEST .%2Fa*/"#);
     }
 
     #[test]
-    fn minify_calc() {
+    fn autoprefix_old_android_flex() {
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#"
-                .a {
-                    margin: 10px 20rpx 30px calc(10px + 20px / 2);
-                    padding: calc(10rpx  *  2  +  30px);
-                }
-            "#,
+            ".a { display: flex; flex: 1; transform: none }",
             StyleSheetOptions {
-                class_prefix: Some("p".into()),
-                rpx_ratio: 10.,
+                autoprefix: autoprefix_targets(&["android 4.3"]),
                 ..Default::default()
             },
         );
         let mut s = Vec::new();
-        let output = trans.output();
-        output.write(&mut s).unwrap();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            ".p--a{margin:10px 200vw 30px calc(10px + 20px/2);padding:calc(100vw*2 + 30px);}"
+            ".a{display:flex;flex:1;-webkit-transform:none;transform:none}"
         );
     }
 
     #[test]
-    fn minify_calc_source_map() {
+    fn autoprefix_keyframes() {
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#" .a { padding: calc(10rpx  *  2  +  30px); } "#,
+            "@keyframes k { from { transform: none } 50%, to { transform: translateX(75rpx) } }",
             StyleSheetOptions {
-                class_prefix: Some("p".into()),
-                rpx_ratio: 10.,
+                autoprefix: autoprefix_targets(&["ios 8"]),
                 ..Default::default()
             },
         );
+        assert_eq!(trans.warnings().count(), 0);
         let mut s = Vec::new();
-        let output = trans.output();
-        output.write(&mut s).unwrap();
+        trans.output().write(&mut s).unwrap();
         assert_eq!(
             std::str::from_utf8(&s).unwrap(),
-            ".p--a{padding:calc(100vw*2 + 30px);}"
+            "@-webkit-keyframes k{from{-webkit-transform:none;transform:none}50%,to{-webkit-transform:translateX(10vw);transform:translateX(10vw)}}@keyframes k{from{-webkit-transform:none;transform:none}50%,to{-webkit-transform:translateX(10vw);transform:translateX(10vw)}}"
         );
-        let mut sm = Vec::new();
-        output.write_source_map(&mut sm).unwrap();
-        let sm: &[u8] = &sm;
-        let source_map = SourceMap::from_reader(sm).unwrap();
-        {
-            let token = source_map.lookup_token(0, 0).unwrap();
-            assert_eq!(token.get_name(), None);
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 1);
-        }
-        {
-            let token = source_map.lookup_token(0, 1).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 2);
-        }
-        {
-            let token = source_map.lookup_token(0, 5).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 4);
-        }
-        {
-            let token = source_map.lookup_token(0, 6).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 6);
-        }
-        {
-            let token = source_map.lookup_token(0, 14).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 15);
-        }
-        {
-            let token = source_map.lookup_token(0, 19).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 20);
-        }
-        {
-            let token = source_map.lookup_token(0, 24).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 27);
-        }
-        {
-            let token = source_map.lookup_token(0, 27).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 33);
-        }
-        {
-            let token = source_map.lookup_token(0, 29).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 36);
-        }
     }
 
     #[test]
-    fn source_map() {
+    fn autoprefix_source_map() {
         let trans = StyleSheetTransformer::from_css(
             "",
-            r#" .a { width: 1rpx } "#,
+            ".a {\n  user-select: none;\n}",
             StyleSheetOptions {
-                class_prefix: Some("p".into()),
-                rpx_ratio: 10.,
+                autoprefix: autoprefix_targets(&["safari 15"]),
                 ..Default::default()
             },
         );
-        let mut s = Vec::new();
         let output = trans.output();
+        let mut s = Vec::new();
         output.write(&mut s).unwrap();
-        assert_eq!(std::str::from_utf8(&s).unwrap(), ".p--a{width:10vw}");
+        assert_eq!(
+            std::str::from_utf8(&s).unwrap(),
+            ".a{-webkit-user-select:none;user-select:none;}"
+        );
         let mut sm = Vec::new();
         output.write_source_map(&mut sm).unwrap();
         let sm: &[u8] = &sm;
         let source_map = SourceMap::from_reader(sm).unwrap();
-        {
-            let token = source_map.lookup_token(0, 0).unwrap();
-            assert_eq!(token.get_name(), None);
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 1);
-        }
-        {
-            let token = source_map.lookup_token(0, 1).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 2);
-        }
-        {
-            let token = source_map.lookup_token(0, 5).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 4);
-        }
-        {
-            let token = source_map.lookup_token(0, 6).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 6);
-        }
-        {
-            let token = source_map.lookup_token(0, 12).unwrap();
-            assert_eq!(token.get_src_line(), 0);
-            assert_eq!(token.get_src_col(), 13);
+        for col in [3, 28] {
+            let token = source_map.lookup_token(0, col).unwrap();
+            assert_eq!(token.get_dst_col(), col);
+            assert_eq!((token.get_src_line(), token.get_src_col()), (1, 2));
         }
     }
+
+    #[test]
+    fn parse_browser_target() {
+        use prefix::{Browser, BrowserTarget};
+        assert_eq!(
+            "ios >= 9.3".parse(),
+            Ok(BrowserTarget {
+                browser: Browser::Ios,
+                version: (9, 3)
+            })
+        );
+        assert_eq!(
+            "Android 4.4.4".parse(),
+            Ok(BrowserTarget {
+                browser: Browser::Android,
+                version: (4, 4)
+            })
+        );
+        assert_eq!(
+            "chrome>=49".parse(),
+            Ok(BrowserTarget {
+                browser: Browser::Chrome,
+                version: (49, 0)
+            })
+        );
+        assert!("ie 11".parse::<BrowserTarget>().is_err());
+        assert!("safari".parse::<BrowserTarget>().is_err());
+        assert!("safari x".parse::<BrowserTarget>().is_err());
+    }
 }
@@ -1,7 +1,7 @@
 use clap::Parser;
 use glass_easel_stylesheet_compiler::*;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -42,6 +42,18 @@ struct CmdArgs {
     #[arg(short, long, default_value = "750.")]
     rpx_ratio: f32,
 
+    /// The target of RPX conversion: `vw`, `px:SCREEN_WIDTH`, `var` or `var:--NAME` (`var` means `var:--rpx`)
+    #[arg(long, default_value = "vw")]
+    rpx_strategy: RpxStrategy,
+
+    /// The maximum number of decimal places of converted RPX values
+    #[arg(long)]
+    rpx_precision: Option<u32>,
+
+    /// Convert non-zero RPX values to at least 1px (only for `--rpx-strategy px:SCREEN_WIDTH`)
+    #[arg(long)]
+    rpx_min_one_px: bool,
+
     /// A comment message inserted in where the import content should be added
     #[arg(long)]
     import_sign: Option<String>,
@@ -53,6 +65,66 @@ struct CmdArgs {
     /// Convert `:host` into an `is` attribute selector with the specified value ( `--convert-host` must be specified)
     #[arg(long)]
     host_is: Option<String>,
+
+    /// Flatten nested style rules into plain rules (for runtimes without CSS nesting support)
+    #[arg(long)]
+    flatten_nesting: bool,
+
+    /// Replace class names with hashes of SOURCE_FILE and the class names (`--class-prefix` is ignored)
+    #[arg(long)]
+    hash_class_names: bool,
+
+    /// The output file for the JSON mapping from original class names to hashed ones
+    #[arg(long)]
+    class_name_mapping_output: Option<PathBuf>,
+
+    /// Inline the stylesheets imported by `@import` (paths are relative to the importing stylesheet)
+    #[arg(long)]
+    bundle_imports: bool,
+
+    /// Minify the output (remove empty rules and overridden declarations, merge adjacent rules, shorten colors and numbers)
+    #[arg(long)]
+    minify: bool,
+
+    /// Add the `-webkit-` prefixes needed by the comma-separated browser targets, e.g. `ios 9,android 4.4` (BROWSER is `chrome`, `safari`, `ios` or `android`)
+    #[arg(long, value_delimiter = ',')]
+    autoprefix: Option<Vec<prefix::BrowserTarget>>,
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut ret = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !ret.pop() {
+                    ret.push("..");
+                }
+            }
+            _ => ret.push(comp),
+        }
+    }
+    ret
+}
+
+fn resolve_import(importer: &str, rel_path: &str) -> Option<(String, String)> {
+    let path = Path::new(importer).parent()?.join(rel_path);
+    let path = normalize_path(&path);
+    let content = fs::read_to_string(&path).ok()?;
+    Some((path.to_str()?.to_string(), content))
+}
+
+fn transform(
+    path: &str,
+    css: &str,
+    options: StyleSheetOptions,
+    bundle: bool,
+) -> StyleSheetTransformer {
+    if bundle {
+        StyleSheetTransformer::from_css_bundled(path, css, options, resolve_import)
+    } else {
+        StyleSheetTransformer::from_css(path, css, options)
+    }
 }
 
 fn main() {
@@ -62,32 +134,46 @@ fn main() {
         class_prefix: args.class_prefix.clone(),
         class_prefix_sign: args.class_prefix_sign.clone(),
         rpx_ratio: args.rpx_ratio,
+        rpx_strategy: args.rpx_strategy.clone(),
+        rpx_precision: args.rpx_precision,
+        rpx_min_one_px: args.rpx_min_one_px,
         import_sign: args.import_sign.clone(),
         convert_host: args.convert_host,
         host_is: args.host_is.clone(),
+        flatten_nesting: args.flatten_nesting,
+        hash_class_names: args.hash_class_names,
+        minify: args.minify,
+        known_css_variables: None,
+        autoprefix: args.autoprefix.clone(),
     };
 
     let sst = if args.interactive {
         use std::io::Read;
         let mut s = String::new();
         std::io::stdin().read_to_string(&mut s).unwrap();
-        StyleSheetTransformer::from_css(
+        transform(
             args.input
                 .to_str()
                 .expect("SOURCE_FILE name should be valid unicode string"),
             &s,
             options,
+            args.bundle_imports,
         )
     } else {
         let s = fs::read_to_string(&args.input).expect("Failed to read source file");
-        StyleSheetTransformer::from_css(
+        transform(
             args.input
                 .to_str()
                 .expect("SOURCE_FILE name should be valid unicode string"),
             &s,
             options,
+            args.bundle_imports,
         )
     };
+    if let Some(mapping_output) = args.class_name_mapping_output {
+        fs::write(mapping_output, sst.class_name_mapping_json())
+            .expect("Failed to write class name mapping output file");
+    }
     let (output, low_priority_output) = sst.output_and_low_priority_output();
 
     if let Some(output_file) = args.low_priority_output {
@@ -0,0 +1,678 @@
+//! Minification of the transformed output
+//!
+//! The output is re-tokenized and re-written,
+//! and the source map of the output is looked up to keep the original positions.
+
+use std::collections::HashMap;
+
+use cssparser::{ParseError, Parser, ParserInput, ToCss, Token};
+use sourcemap::SourceMap;
+
+use crate::error::Position;
+use crate::output::{SourceMapping, StyleSheetOutput};
+
+struct Tok<'i> {
+    token: Token<'i>,
+    mapping: Option<SourceMapping>,
+    block: Option<Block<'i>>,
+}
+
+#[derive(Default)]
+struct Block<'i> {
+    tokens: Vec<Tok<'i>>,
+    close: Option<SourceMapping>,
+}
+
+struct BlockNode<'i> {
+    open: Option<SourceMapping>,
+    items: Vec<Node<'i>>,
+    close: Option<SourceMapping>,
+}
+
+enum Node<'i> {
+    Rule {
+        prelude: Vec<Tok<'i>>,
+        block: BlockNode<'i>,
+    },
+    AtRule {
+        prelude: Vec<Tok<'i>>,
+        block: Option<BlockNode<'i>>,
+    },
+    Declaration {
+        /// The property name (lower-cased unless it is a custom property).
+        name: String,
+        tokens: Vec<Tok<'i>>,
+        important: bool,
+    },
+    Other {
+        tokens: Vec<Tok<'i>>,
+        semicolon: bool,
+    },
+}
+
+struct MappingLookup<'a> {
+    source_map: &'a SourceMap,
+    utf16_offsets: Vec<u32>,
+}
+
+impl MappingLookup<'_> {
+    fn get(&self, byte_index: usize) -> Option<SourceMapping> {
+        let col = *self.utf16_offsets.get(byte_index)?;
+        let token = self.source_map.lookup_token(0, col)?;
+        if token.get_dst_line() != 0 || token.get_dst_col() != col {
+            return None;
+        }
+        Some(SourceMapping {
+            source: token.get_source().map(|x| x.to_string()),
+            position: Position {
+                line: token.get_src_line(),
+                utf16_col: token.get_src_col(),
+            },
+            name: token.get_name().map(|x| x.to_string()),
+        })
+    }
+}
+
+/// Minify the output.
+///
+/// Empty rules are removed, adjacent rules with identical selectors are merged,
+/// overridden declarations are dropped, and colors and numbers are shortened.
+pub(crate) fn minify(output: StyleSheetOutput, path: &str, source_css: &str) -> StyleSheetOutput {
+    let (s, source_map) = output.into_str_and_source_map();
+    let mut utf16_offsets = Vec::with_capacity(s.len() + 1);
+    let mut utf16_len = 0;
+    for c in s.chars() {
+        for _ in 0..c.len_utf8() {
+            utf16_offsets.push(utf16_len);
+        }
+        utf16_len += c.len_utf16() as u32;
+    }
+    utf16_offsets.push(utf16_len);
+    let lookup = MappingLookup {
+        source_map: &source_map,
+        utf16_offsets,
+    };
+    let parser_input = &mut ParserInput::new(&s);
+    let parser = &mut Parser::new(parser_input);
+    let tokens = read_tokens(parser, &lookup);
+    let mut items = parse_items(tokens);
+    minify_items(&mut items);
+    let mut ret = StyleSheetOutput::new(path, source_css);
+    for (index, source) in source_map.sources().enumerate() {
+        let contents = source_map
+            .get_source_contents(index as u32)
+            .unwrap_or_default();
+        let prev = ret.switch_source(source, contents);
+        ret.restore_source(prev);
+    }
+    write_items(&mut ret, &items, true);
+    ret
+}
+
+fn read_tokens<'i>(input: &mut Parser<'i, '_>, lookup: &MappingLookup) -> Vec<Tok<'i>> {
+    let mut ret = vec![];
+    loop {
+        let mapping = lookup.get(input.position().byte_index());
+        let Ok(token) = input.next_including_whitespace_and_comments() else {
+            break;
+        };
+        let token = token.clone();
+        let block = match &token {
+            Token::Function(_)
+            | Token::ParenthesisBlock
+            | Token::SquareBracketBlock
+            | Token::CurlyBracketBlock => input
+                .parse_nested_block(|input| {
+                    let tokens = read_tokens(input, lookup);
+                    let close = lookup.get(input.position().byte_index());
+                    Ok::<_, ParseError<()>>(Block { tokens, close })
+                })
+                .ok(),
+            _ => None,
+        };
+        ret.push(Tok {
+            token,
+            mapping,
+            block,
+        });
+    }
+    ret
+}
+
+fn trim_whitespace(mut tokens: Vec<Tok>) -> Vec<Tok> {
+    while let Some(Token::WhiteSpace(_)) = tokens.last().map(|x| &x.token) {
+        tokens.pop();
+    }
+    let start = tokens
+        .iter()
+        .position(|x| !matches!(x.token, Token::WhiteSpace(_)))
+        .unwrap_or(tokens.len());
+    tokens.drain(..start);
+    tokens
+}
+
+fn declaration_name(tokens: &[Tok]) -> Option<String> {
+    let mut iter = tokens
+        .iter()
+        .filter(|x| !matches!(x.token, Token::WhiteSpace(_)));
+    let Token::Ident(name) = &iter.next()?.token else {
+        return None;
+    };
+    if !matches!(iter.next()?.token, Token::Colon) {
+        return None;
+    }
+    if name.starts_with("--") {
+        Some(name.to_string())
+    } else {
+        Some(name.to_ascii_lowercase())
+    }
+}
+
+fn parse_statement(tokens: Vec<Tok>, semicolon: bool) -> Node {
+    let tokens = trim_whitespace(tokens);
+    if let Some(Token::AtKeyword(_)) = tokens.first().map(|x| &x.token) {
+        return Node::AtRule {
+            prelude: tokens,
+            block: None,
+        };
+    }
+    match declaration_name(&tokens) {
+        Some(name) => {
+            let mut iter = tokens
+                .iter()
+                .rev()
+                .filter(|x| !matches!(x.token, Token::WhiteSpace(_)));
+            let important = matches!(
+                (iter.next().map(|x| &x.token), iter.next().map(|x| &x.token)),
+                (Some(Token::Ident(s)), Some(Token::Delim('!'))) if s.eq_ignore_ascii_case("important")
+            );
+            Node::Declaration {
+                name,
+                tokens,
+                important,
+            }
+        }
+        None => Node::Other { tokens, semicolon },
+    }
+}
+
+fn parse_items(tokens: Vec<Tok>) -> Vec<Node> {
+    let mut ret = vec![];
+    let mut run: Vec<Tok> = vec![];
+    for tok in tokens {
+        match &tok.token {
+            Token::WhiteSpace(_) if run.is_empty() => {}
+            Token::Comment(_) if run.is_empty() => ret.push(Node::Other {
+                tokens: vec![tok],
+                semicolon: false,
+            }),
+            Token::Semicolon => {
+                if !run.is_empty() {
+                    ret.push(parse_statement(std::mem::take(&mut run), true));
+                }
+            }
+            Token::CurlyBracketBlock
+                if !declaration_name(&run).is_some_and(|x| x.starts_with("--")) =>
+            {
+                let Tok { mapping, block, .. } = tok;
+                let block = block.unwrap_or_default();
+                let block = BlockNode {
+                    open: mapping,
+                    items: parse_items(block.tokens),
+                    close: block.close,
+                };
+                let prelude = trim_whitespace(std::mem::take(&mut run));
+                if let Some(Token::AtKeyword(_)) = prelude.first().map(|x| &x.token) {
+                    ret.push(Node::AtRule {
+                        prelude,
+                        block: Some(block),
+                    });
+                } else {
+                    ret.push(Node::Rule { prelude, block });
+                }
+            }
+            _ => run.push(tok),
+        }
+    }
+    if !run.is_empty() {
+        ret.push(parse_statement(run, false));
+    }
+    ret
+}
+
+fn tokens_to_string(tokens: &[Tok], s: &mut String) {
+    for tok in tokens {
+        tok.token.to_css(s).unwrap();
+        if let Some(block) = &tok.block {
+            tokens_to_string(&block.tokens, s);
+            s.push_str(close_str(&tok.token));
+        }
+    }
+}
+
+fn close_str(token: &Token) -> &'static str {
+    match token {
+        Token::SquareBracketBlock => "]",
+        Token::CurlyBracketBlock => "}",
+        _ => ")",
+    }
+}
+
+fn minify_items(items: &mut Vec<Node>) {
+    for item in items.iter_mut() {
+        match item {
+            Node::Rule { block, .. }
+            | Node::AtRule {
+                block: Some(block), ..
+            } => minify_items(&mut block.items),
+            _ => {}
+        }
+    }
+    items.retain(|item| !is_empty_rule(item));
+    merge_adjacent_rules(items);
+    drop_overridden_declarations(items);
+}
+
+fn is_empty_rule(item: &Node) -> bool {
+    match item {
+        Node::Rule { block, .. } => block.items.is_empty(),
+        Node::AtRule {
+            prelude,
+            block: Some(block),
+        } => {
+            let Some(Token::AtKeyword(name)) = prelude.first().map(|x| &x.token) else {
+                return false;
+            };
+            block.items.is_empty() && crate::is_group_at_rule(name)
+        }
+        _ => false,
+    }
+}
+
+fn merge_adjacent_rules(items: &mut Vec<Node>) {
+    let only_declarations = |block: &BlockNode| {
+        block
+            .items
+            .iter()
+            .all(|x| matches!(x, Node::Declaration { .. }))
+    };
+    let mut ret: Vec<Node> = Vec::with_capacity(items.len());
+    let mut prev_prelude = None;
+    for item in items.drain(..) {
+        let Node::Rule { prelude, block } = item else {
+            prev_prelude = None;
+            ret.push(item);
+            continue;
+        };
+        let mut prelude_str = String::new();
+        tokens_to_string(&prelude, &mut prelude_str);
+        if prev_prelude.as_ref() == Some(&prelude_str) && only_declarations(&block) {
+            if let Some(Node::Rule {
+                block: prev_block, ..
+            }) = ret.last_mut()
+            {
+                prev_block.items.extend(block.items);
+                prev_block.close = block.close;
+                drop_overridden_declarations(&mut prev_block.items);
+                continue;
+            }
+        }
+        prev_prelude = only_declarations(&block).then_some(prelude_str);
+        ret.push(Node::Rule { prelude, block });
+    }
+    *items = ret;
+}
+
+fn value_tokens<'a, 'i>(tokens: &'a [Tok<'i>]) -> &'a [Tok<'i>] {
+    match tokens.iter().position(|x| matches!(x.token, Token::Colon)) {
+        Some(index) => &tokens[(index + 1)..],
+        None => &[],
+    }
+}
+
+fn is_common_unit(unit: &str) -> bool {
+    const UNITS: [&str; 19] = [
+        "px", "em", "rem", "vw", "vh", "vmin", "vmax", "pt", "pc", "cm", "mm", "in", "ex", "ch",
+        "deg", "rad", "turn", "s", "ms",
+    ];
+    UNITS.iter().any(|x| unit.eq_ignore_ascii_case(x))
+}
+
+fn is_length_unit(unit: &str) -> bool {
+    is_common_unit(unit)
+        && !["deg", "rad", "turn", "s", "ms"].contains(&&*unit.to_ascii_lowercase())
+}
+
+fn drop_overridden_declarations(items: &mut Vec<Node>) {
+    // find the winning declaration of each property
+    let mut winners: HashMap<&str, (usize, bool)> = HashMap::new();
+    for (index, item) in items.iter().enumerate() {
+        let Node::Declaration {
+            name, important, ..
+        } = item
+        else {
+            continue;
+        };
+        let winner = winners.entry(name).or_insert((index, *important));
+        if *important || !winner.1 {
+            *winner = (index, *important);
+        }
+    }
+    let removed: Vec<bool> = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let Node::Declaration {
+                name,
+                tokens,
+                important,
+            } = item
+            else {
+                return false;
+            };
+            let winner_index = winners[name.as_str()].0;
+            if index == winner_index {
+                return false;
+            }
+            if index > winner_index {
+                // overridden by an earlier `!important` declaration
+                return true;
+            }
+            let Node::Declaration {
+                tokens: winner_tokens,
+                important: winner_important,
+                ..
+            } = &items[winner_index]
+            else {
+                unreachable!()
+            };
+            if *winner_important && !important {
+                return true;
+            }
+            // an earlier declaration may be a fallback when either value is prefixed or a function,
+            // e.g. `display: -webkit-box; display: flex` or `width: 90%; width: calc(100% - 10px)`
+            if may_need_fallback(value_tokens(tokens))
+                || may_need_fallback(value_tokens(winner_tokens))
+            {
+                let mut value = String::new();
+                tokens_to_string(value_tokens(tokens), &mut value);
+                let mut winner_value = String::new();
+                tokens_to_string(value_tokens(winner_tokens), &mut winner_value);
+                return value == winner_value;
+            }
+            true
+        })
+        .collect();
+    let mut removed = removed.into_iter();
+    items.retain(|_| !removed.next().unwrap());
+}
+
+/// Check if a value contains a vendor prefix or a function, which may not be supported everywhere.
+fn may_need_fallback(tokens: &[Tok]) -> bool {
+    tokens.iter().any(|tok| {
+        let prefixed = |s: &str| s.starts_with('-') && !s.starts_with("--");
+        match &tok.token {
+            Token::Function(_) | Token::UnquotedUrl(_) => return true,
+            Token::Ident(x) if prefixed(x) => return true,
+            _ => {}
+        }
+        tok.block
+            .as_ref()
+            .map(|block| may_need_fallback(&block.tokens))
+            .unwrap_or(false)
+    })
+}
+
+fn write_items(out: &mut StyleSheetOutput, items: &[Node], top_level: bool) {
+    for (index, item) in items.iter().enumerate() {
+        let is_last = !top_level && index + 1 == items.len();
+        match item {
+            Node::Rule { prelude, block } => {
+                write_tokens(out, prelude, None, 0);
+                write_block(out, block);
+            }
+            Node::AtRule { prelude, block } => {
+                write_tokens(out, prelude, None, 0);
+                match block {
+                    Some(block) => write_block(out, block),
+                    None => {
+                        if !is_last {
+                            write_str(out, &Token::Semicolon, ";", None);
+                        }
+                    }
+                }
+            }
+            Node::Declaration { name, tokens, .. } => {
+                let index = tokens
+                    .iter()
+                    .position(|x| matches!(x.token, Token::Colon))
+                    .map(|x| x + 1)
+                    .unwrap_or(0);
+                write_tokens(out, &tokens[..index], None, 0);
+                let value = if name.starts_with("--") {
+                    None
+                } else {
+                    Some(name.as_str())
+                };
+                write_tokens(out, &tokens[index..], value, 0);
+                if !is_last {
+                    write_str(out, &Token::Semicolon, ";", None);
+                }
+            }
+            Node::Other { tokens, semicolon } => {
+                write_tokens(out, tokens, None, 0);
+                if *semicolon && !is_last {
+                    write_str(out, &Token::Semicolon, ";", None);
+                }
+            }
+        }
+    }
+}
+
+fn write_block(out: &mut StyleSheetOutput, block: &BlockNode) {
+    write_str(out, &Token::CurlyBracketBlock, "{", block.open.as_ref());
+    write_items(out, &block.items, false);
+    write_str(out, &Token::CloseCurlyBracket, "}", block.close.as_ref());
+}
+
+fn write_str(out: &mut StyleSheetOutput, token: &Token, s: &str, mapping: Option<&SourceMapping>) {
+    out.append_str_mapped(s, token.serialization_type(), mapping);
+}
+
+/// Write tokens, minifying them if they are in the value of the property `value` .
+fn write_tokens(out: &mut StyleSheetOutput, tokens: &[Tok], value: Option<&str>, depth: usize) {
+    for tok in tokens {
+        if let Some(block) = &tok.block {
+            if value.is_some() {
+                if let Some(color) = rgb_function_to_hex(&tok.token, &block.tokens) {
+                    let token = Token::Hash(color.as_str().into());
+                    write_str(out, &token, &color_str(&color), tok.mapping.as_ref());
+                    continue;
+                }
+            }
+            write_str(
+                out,
+                &tok.token,
+                &tok.token.to_css_string(),
+                tok.mapping.as_ref(),
+            );
+            write_tokens(out, &block.tokens, value, depth + 1);
+            let close = match &tok.token {
+                Token::SquareBracketBlock => Token::CloseSquareBracket,
+                Token::CurlyBracketBlock => Token::CloseCurlyBracket,
+                _ => Token::CloseParenthesis,
+            };
+            write_str(out, &close, close_str(&tok.token), block.close.as_ref());
+            continue;
+        }
+        let Some(name) = value else {
+            write_str(
+                out,
+                &tok.token,
+                &tok.token.to_css_string(),
+                tok.mapping.as_ref(),
+            );
+            continue;
+        };
+        match &tok.token {
+            Token::WhiteSpace(_) => write_str(out, &tok.token, " ", None),
+            Token::Number { .. } | Token::Percentage { .. } => {
+                let s = short_number(tok.token.to_css_string());
+                write_str(out, &tok.token, &s, tok.mapping.as_ref());
+            }
+            Token::Dimension { value, unit, .. } => {
+                if *value == 0. && depth == 0 && name != "flex" && is_length_unit(unit) {
+                    let token = Token::Number {
+                        has_sign: false,
+                        value: 0.,
+                        int_value: Some(0),
+                    };
+                    write_str(out, &token, "0", tok.mapping.as_ref());
+                } else {
+                    let s = short_number(tok.token.to_css_string());
+                    write_str(out, &tok.token, &s, tok.mapping.as_ref());
+                }
+            }
+            Token::Hash(hex) | Token::IDHash(hex) => match shorten_hex_color(hex) {
+                Some(color) => {
+                    let token = Token::Hash(color.as_str().into());
+                    write_str(out, &token, &color_str(&color), tok.mapping.as_ref());
+                }
+                None => write_str(
+                    out,
+                    &tok.token,
+                    &tok.token.to_css_string(),
+                    tok.mapping.as_ref(),
+                ),
+            },
+            _ => write_str(
+                out,
+                &tok.token,
+                &tok.token.to_css_string(),
+                tok.mapping.as_ref(),
+            ),
+        }
+    }
+}
+
+/// Shorten a serialized number, e.g. `0.50` to `.5` and `10.0px` to `10px` .
+fn short_number(s: String) -> String {
+    let sign_len = if s.starts_with(['+', '-']) { 1 } else { 0 };
+    let (sign, rest) = s.split_at(sign_len);
+    let num_len = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    let (mut num, unit) = rest.split_at(num_len);
+    if num.contains('.') {
+        num = num.trim_end_matches('0').trim_end_matches('.');
+    }
+    let num = match num.strip_prefix('0') {
+        Some(x) if !x.is_empty() => x,
+        _ => num,
+    };
+    format!("{}{}{}", sign, num, unit)
+}
+
+/// The named colors which are shorter than the hex forms.
+const SHORT_COLOR_NAMES: [(&str, &str); 31] = [
+    ("000080", "navy"),
+    ("008000", "green"),
+    ("008080", "teal"),
+    ("4b0082", "indigo"),
+    ("800000", "maroon"),
+    ("800080", "purple"),
+    ("808000", "olive"),
+    ("808080", "gray"),
+    ("a0522d", "sienna"),
+    ("a52a2a", "brown"),
+    ("c0c0c0", "silver"),
+    ("cd853f", "peru"),
+    ("d2b48c", "tan"),
+    ("da70d6", "orchid"),
+    ("dda0dd", "plum"),
+    ("ee82ee", "violet"),
+    ("f0e68c", "khaki"),
+    ("f0ffff", "azure"),
+    ("f5deb3", "wheat"),
+    ("f5f5dc", "beige"),
+    ("fa8072", "salmon"),
+    ("faf0e6", "linen"),
+    ("ff0000", "red"),
+    ("ff6347", "tomato"),
+    ("ff7f50", "coral"),
+    ("ffa500", "orange"),
+    ("ffc0cb", "pink"),
+    ("ffd700", "gold"),
+    ("ffe4c4", "bisque"),
+    ("fffafa", "snow"),
+    ("fffff0", "ivory"),
+];
+
+/// The output form of a shortened color: a color name, or a hex color with `#` .
+fn color_str(color: &str) -> String {
+    if color.starts_with(|c: char| c.is_ascii_hexdigit()) {
+        format!("#{}", color)
+    } else {
+        color.to_string()
+    }
+}
+
+/// Shorten a hex color (without `#` ), returning the hex digits or a color name.
+fn shorten_hex_color(hex: &str) -> Option<String> {
+    if !matches!(hex.len(), 3 | 4 | 6 | 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = hex.to_ascii_lowercase();
+    let mut full: String = match hex.len() {
+        3 | 4 => hex.chars().flat_map(|c| [c, c]).collect(),
+        _ => hex.clone(),
+    };
+    if full.len() == 8 && full.ends_with("ff") {
+        full.truncate(6);
+    }
+    if let Some((_, name)) = SHORT_COLOR_NAMES.iter().find(|x| x.0 == full) {
+        return Some(name.to_string());
+    }
+    let bytes = full.as_bytes();
+    let short = if bytes.chunks(2).all(|x| x[0] == x[1]) {
+        bytes.chunks(2).map(|x| x[0] as char).collect()
+    } else {
+        full
+    };
+    if short != hex {
+        Some(short)
+    } else {
+        None
+    }
+}
+
+/// Convert `rgb(R, G, B)` (or `rgba(R, G, B, 1)` ) with integer arguments to a shortened hex color.
+fn rgb_function_to_hex(token: &Token, args: &[Tok]) -> Option<String> {
+    let Token::Function(name) = token else {
+        return None;
+    };
+    if !name.eq_ignore_ascii_case("rgb") && !name.eq_ignore_ascii_case("rgba") {
+        return None;
+    }
+    let mut components = vec![];
+    for arg in args {
+        match &arg.token {
+            Token::WhiteSpace(_) | Token::Comma => {}
+            Token::Number {
+                int_value: Some(x), ..
+            } if components.len() < 3 && (0..=255).contains(x) => components.push(*x as u8),
+            Token::Number { value, .. } if components.len() == 3 && *value == 1. => {
+                components.push(255)
+            }
+            _ => return None,
+        }
+    }
+    if components.len() < 3 {
+        return None;
+    }
+    let hex: String = components[..3]
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect();
+    Some(shorten_hex_color(&hex).unwrap_or(hex))
+}
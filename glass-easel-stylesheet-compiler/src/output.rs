@@ -3,8 +3,18 @@ use std::fmt::Write;
 use cssparser::{ToCss, Token, TokenSerializationType};
 use sourcemap::{SourceMap, SourceMapBuilder};
 
+use crate::error::Position;
 use crate::step::StepToken;
 
+/// The source position (and the original text) of an output segment.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SourceMapping {
+    /// The source file path ( `None` for the current source file).
+    pub(crate) source: Option<String>,
+    pub(crate) position: Position,
+    pub(crate) name: Option<String>,
+}
+
 pub struct StyleSheetOutput {
     s: String,
     prev_ser_type: TokenSerializationType,
@@ -43,6 +53,22 @@ impl StyleSheetOutput {
         self.source_map.into_sourcemap()
     }
 
+    /// Switch the source file of the following mappings, returning the previous source id.
+    pub(crate) fn switch_source(&mut self, path: &str, source_css: &str) -> u32 {
+        let source_id = self.source_map.add_source(path);
+        self.source_map
+            .set_source_contents(source_id, Some(source_css));
+        std::mem::replace(&mut self.source_id, source_id)
+    }
+
+    pub(crate) fn restore_source(&mut self, source_id: u32) {
+        self.source_id = source_id;
+    }
+
+    pub(crate) fn into_str_and_source_map(self) -> (String, SourceMap) {
+        (self.s, self.source_map.into_sourcemap())
+    }
+
     pub(crate) fn cur_utf8_len(&self) -> usize {
         self.s.len()
     }
@@ -58,6 +84,18 @@ impl StyleSheetOutput {
         self.utf16_len += str::encode_utf16(&self.s[output_start_pos..]).count() as u32;
     }
 
+    pub(crate) fn append_raw_with_position(&mut self, s: &str, position: Position) {
+        self.source_map.add_raw(
+            0,
+            self.utf16_len,
+            position.line,
+            position.utf16_col,
+            Some(self.source_id),
+            None,
+        );
+        self.append_raw(s);
+    }
+
     pub(crate) fn append_token(&mut self, token: StepToken, src: Option<Token>) {
         let next_ser_type = token.serialization_type();
         if self
@@ -85,6 +123,37 @@ impl StyleSheetOutput {
         self.utf16_len += str::encode_utf16(&self.s[output_start_pos..]).count() as u32;
     }
 
+    /// Append a serialized token, which may be different from its original serialization.
+    pub(crate) fn append_str_mapped(
+        &mut self,
+        s: &str,
+        ser_type: TokenSerializationType,
+        mapping: Option<&SourceMapping>,
+    ) {
+        if self.prev_ser_type.needs_separator_when_before(ser_type) {
+            self.s.push(' ');
+            self.utf16_len += 1;
+        }
+        self.prev_ser_type = ser_type;
+        if let Some(mapping) = mapping {
+            let source_id = match &mapping.source {
+                Some(path) => self.source_map.add_source(path),
+                None => self.source_id,
+            };
+            let name = mapping.name.as_ref().map(|x| self.source_map.add_name(x));
+            self.source_map.add_raw(
+                0,
+                self.utf16_len,
+                mapping.position.line,
+                mapping.position.utf16_col,
+                Some(source_id),
+                name,
+            );
+        }
+        self.s += s;
+        self.utf16_len += str::encode_utf16(s).count() as u32;
+    }
+
     pub(crate) fn append_token_space_preserved(&mut self, token: StepToken, src: Option<Token>) {
         if let Token::WhiteSpace(_) = &*token {
             self.prev_ser_type = token.serialization_type();
@@ -0,0 +1,195 @@
+//! Vendor prefixing with a built-in compatibility table
+//!
+//! Only `-webkit-` prefixes are handled, since the targets are all WebKit or Blink based.
+
+/// A browser (or WebView) family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    Chrome,
+    Safari,
+    /// Safari and WebViews on iOS.
+    Ios,
+    /// The Android browser and WebView.
+    ///
+    /// Versions since 5.0 are treated as the latest Chrome, since the WebView updates with Chrome.
+    Android,
+}
+
+/// The oldest version of a browser that should be supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrowserTarget {
+    pub browser: Browser,
+    pub version: (u32, u32),
+}
+
+impl std::str::FromStr for BrowserTarget {
+    type Err = String;
+
+    /// Parse from `BROWSER VERSION` or `BROWSER >= VERSION` , e.g. `ios >= 9.3` .
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || {
+            format!(
+                "illegal browser target {:?} (expected `BROWSER VERSION` with BROWSER in `chrome`, `safari`, `ios` or `android`)",
+                s
+            )
+        };
+        let s = s.trim();
+        let split = s
+            .find(|c: char| c.is_ascii_whitespace() || c == '>')
+            .ok_or_else(err)?;
+        let (name, version) = s.split_at(split);
+        let version = version.trim_start();
+        let version = version.strip_prefix(">=").unwrap_or(version).trim();
+        let browser = match name.to_ascii_lowercase().as_str() {
+            "chrome" | "and_chr" => Browser::Chrome,
+            "safari" => Browser::Safari,
+            "ios" | "ios_saf" => Browser::Ios,
+            "android" => Browser::Android,
+            _ => return Err(err()),
+        };
+        let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+        // patch versions (e.g. `4.4.4` ) are ignored
+        let minor = minor.split('.').next().unwrap_or_default();
+        match (major.parse(), minor.parse()) {
+            (Ok(major), Ok(minor)) => Ok(Self {
+                browser,
+                version: (major, minor),
+            }),
+            _ => Err(err()),
+        }
+    }
+}
+
+/// The first versions supporting a feature without prefixes,
+/// for Chrome, Safari, iOS and Android respectively.
+///
+/// `None` means prefixes are always needed.
+type Since = [Option<(u32, u32)>; 4];
+
+const fn since(
+    chrome: u32,
+    safari: (u32, u32),
+    ios: (u32, u32),
+    android: Option<(u32, u32)>,
+) -> Since {
+    [Some((chrome, 0)), Some(safari), Some(ios), android]
+}
+
+const ANIMATION: Since = since(43, (9, 0), (9, 0), None);
+const TRANSITION: Since = since(26, (6, 1), (7, 0), Some((4, 4)));
+const TRANSFORM: Since = since(36, (9, 0), (9, 0), None);
+// Android before 4.4 only supports the old `display: -webkit-box` syntax rather than `-webkit-flex` ,
+// so prefixing does not help there.
+const FLEX: Since = since(29, (9, 0), (9, 0), Some((0, 0)));
+const MASK: Since = since(120, (15, 4), (15, 4), None);
+const INTRINSIC_SIZE: Since = since(46, (11, 0), (11, 0), None);
+const SAFARI_ONLY: Since = [Some((0, 0)), None, None, None];
+
+static PROPERTIES: &[(&str, Since)] = &[
+    ("animation", ANIMATION),
+    ("animation-name", ANIMATION),
+    ("animation-duration", ANIMATION),
+    ("animation-timing-function", ANIMATION),
+    ("animation-delay", ANIMATION),
+    ("animation-iteration-count", ANIMATION),
+    ("animation-direction", ANIMATION),
+    ("animation-fill-mode", ANIMATION),
+    ("animation-play-state", ANIMATION),
+    ("transition", TRANSITION),
+    ("transition-property", TRANSITION),
+    ("transition-duration", TRANSITION),
+    ("transition-timing-function", TRANSITION),
+    ("transition-delay", TRANSITION),
+    ("transform", TRANSFORM),
+    ("transform-origin", TRANSFORM),
+    ("transform-style", TRANSFORM),
+    ("perspective", TRANSFORM),
+    ("perspective-origin", TRANSFORM),
+    ("backface-visibility", since(36, (15, 4), (15, 4), None)),
+    ("flex", FLEX),
+    ("flex-grow", FLEX),
+    ("flex-shrink", FLEX),
+    ("flex-basis", FLEX),
+    ("flex-direction", FLEX),
+    ("flex-wrap", FLEX),
+    ("flex-flow", FLEX),
+    ("order", FLEX),
+    ("justify-content", FLEX),
+    ("align-items", FLEX),
+    ("align-self", FLEX),
+    ("align-content", FLEX),
+    ("filter", since(53, (9, 1), (9, 3), None)),
+    ("backdrop-filter", since(76, (18, 0), (18, 0), None)),
+    ("clip-path", since(55, (13, 1), (13, 0), None)),
+    ("mask", MASK),
+    ("mask-image", MASK),
+    ("mask-size", MASK),
+    ("mask-position", MASK),
+    ("mask-repeat", MASK),
+    ("mask-origin", MASK),
+    ("mask-clip", MASK),
+    ("appearance", since(84, (15, 4), (15, 4), None)),
+    ("hyphens", since(88, (17, 0), (17, 0), None)),
+    ("user-select", [Some((54, 0)), None, None, None]),
+    ("text-size-adjust", SAFARI_ONLY),
+    ("box-decoration-break", [None, None, None, None]),
+];
+
+const SIZE_PROPERTIES: &[&str] = &[
+    "width",
+    "min-width",
+    "max-width",
+    "height",
+    "min-height",
+    "max-height",
+];
+
+static VALUES: &[(&[&str], &str, Since)] = &[
+    (&["position"], "sticky", since(56, (13, 0), (13, 0), None)),
+    (&["display"], "flex", FLEX),
+    (&["display"], "inline-flex", FLEX),
+    (SIZE_PROPERTIES, "fit-content", INTRINSIC_SIZE),
+    (SIZE_PROPERTIES, "min-content", INTRINSIC_SIZE),
+    (SIZE_PROPERTIES, "max-content", INTRINSIC_SIZE),
+];
+
+static AT_RULES: &[(&str, Since)] = &[("keyframes", ANIMATION)];
+
+fn is_needed(since: &Since, targets: &[BrowserTarget]) -> bool {
+    targets.iter().any(|target| {
+        let (index, version) = match target.browser {
+            Browser::Chrome => (0, target.version),
+            Browser::Safari => (1, target.version),
+            Browser::Ios => (2, target.version),
+            Browser::Android if target.version >= (5, 0) => (0, (u32::MAX, 0)),
+            Browser::Android => (3, target.version),
+        };
+        match since[index] {
+            None => true,
+            Some(since) => version < since,
+        }
+    })
+}
+
+/// Whether the property needs a `-webkit-` prefix for the targets.
+pub(crate) fn property_needs_prefix(name: &str, targets: &[BrowserTarget]) -> bool {
+    PROPERTIES
+        .iter()
+        .any(|(x, since)| name.eq_ignore_ascii_case(x) && is_needed(since, targets))
+}
+
+/// Whether the keyword value of the property needs a `-webkit-` prefix for the targets.
+pub(crate) fn value_needs_prefix(property: &str, value: &str, targets: &[BrowserTarget]) -> bool {
+    VALUES.iter().any(|(properties, x, since)| {
+        value.eq_ignore_ascii_case(x)
+            && properties.iter().any(|p| property.eq_ignore_ascii_case(p))
+            && is_needed(since, targets)
+    })
+}
+
+/// Whether the at-rule needs a `-webkit-` prefix for the targets.
+pub(crate) fn at_rule_needs_prefix(name: &str, targets: &[BrowserTarget]) -> bool {
+    AT_RULES
+        .iter()
+        .any(|(x, since)| name.eq_ignore_ascii_case(x) && is_needed(since, targets))
+}
@@ -0,0 +1,534 @@
+//! The selector AST
+//!
+//! Selectors are parsed tolerantly: tokens that cannot be recognized are kept as `Unknown` items,
+//! so that they can be written back as-is.
+
+use std::ops::Range;
+
+use cssparser::Token;
+
+use crate::error::Position;
+use crate::step::{StepParser, StepToken};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SelectorList<'i> {
+    pub(crate) selectors: Vec<ComplexSelector<'i>>,
+    /// The position of the first comma or combinator not followed by a compound selector.
+    pub(crate) missing_compound: Option<Position>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ComplexSelector<'i> {
+    pub(crate) position: Position,
+    /// The compound selectors, each with the combinator before it.
+    ///
+    /// The first compound selector has a combinator only if it is a relative selector,
+    /// e.g. `> .a` in nested rules or `:has()`.
+    pub(crate) compounds: Vec<(Option<(Combinator, Position)>, CompoundSelector<'i>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Combinator {
+    Descendant,
+    Child,
+    NextSibling,
+    SubsequentSibling,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CompoundSelector<'i> {
+    pub(crate) simple_selectors: Vec<SimpleSelector<'i>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum SimpleSelector<'i> {
+    /// A type selector or the universal selector `*`.
+    Type(StepToken<'i>),
+    /// The nesting selector `&`.
+    Nesting(StepToken<'i>),
+    Id(StepToken<'i>),
+    Class {
+        dot: StepToken<'i>,
+        name: StepToken<'i>,
+    },
+    Attribute {
+        open: StepToken<'i>,
+        contents: Vec<RawToken<'i>>,
+    },
+    PseudoClass {
+        colon: StepToken<'i>,
+        name: StepToken<'i>,
+        args: Option<PseudoArgs<'i>>,
+    },
+    PseudoElement {
+        colons: (StepToken<'i>, StepToken<'i>),
+        name: StepToken<'i>,
+        args: Option<PseudoArgs<'i>>,
+    },
+    Unknown(RawToken<'i>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum PseudoArgs<'i> {
+    Selectors(SelectorList<'i>),
+    /// The `An+B of S` form of `:nth-child()` and `:nth-last-child()`.
+    NthOf(Vec<RawToken<'i>>, StepToken<'i>, SelectorList<'i>),
+    Raw(Vec<RawToken<'i>>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum RawToken<'i> {
+    Token(StepToken<'i>),
+    Block(StepToken<'i>, Vec<RawToken<'i>>),
+}
+
+/// The `:host` usage of a complex selector.
+pub(crate) enum HostSelector<'a, 'i> {
+    /// Not related to `:host` .
+    None,
+    /// Starts with `:host` or `:host(<compound-selector>)` .
+    Host(Option<&'a CompoundSelector<'i>>),
+    /// Contains `:host` in an unsupported place.
+    Invalid(Position),
+}
+
+fn is_block(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Function(_)
+            | Token::ParenthesisBlock
+            | Token::SquareBracketBlock
+            | Token::CurlyBracketBlock
+    )
+}
+
+fn parse_raw_block<'i>(input: &mut StepParser<'i, '_, '_>) -> Vec<RawToken<'i>> {
+    input
+        .parse_nested_block::<_, _, ()>(|nested_input| {
+            let input = &mut StepParser::wrap(nested_input);
+            Ok(parse_raw_tokens(input))
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn parse_raw_tokens<'i>(input: &mut StepParser<'i, '_, '_>) -> Vec<RawToken<'i>> {
+    let mut ret = vec![];
+    while let Ok(next) = input.next_including_whitespace() {
+        ret.push(parse_raw_token(input, next));
+    }
+    ret
+}
+
+fn parse_raw_token<'i>(input: &mut StepParser<'i, '_, '_>, next: StepToken<'i>) -> RawToken<'i> {
+    if is_block(&next) {
+        let children = parse_raw_block(input);
+        RawToken::Block(next, children)
+    } else {
+        RawToken::Token(next)
+    }
+}
+
+fn pseudo_name(name: &StepToken) -> String {
+    match &**name {
+        Token::Ident(x) | Token::Function(x) => x.to_ascii_lowercase(),
+        _ => String::new(),
+    }
+}
+
+fn parse_pseudo_args<'i>(input: &mut StepParser<'i, '_, '_>, name: &str) -> PseudoArgs<'i> {
+    input
+        .parse_nested_block::<_, _, ()>(|nested_input| {
+            let input = &mut StepParser::wrap(nested_input);
+            let ret = match name {
+                "is" | "where" | "not" | "has" | "matches" | "any" | "-webkit-any" | "-moz-any"
+                | "host" | "host-context" | "slotted" => {
+                    PseudoArgs::Selectors(parse_selector_list(input))
+                }
+                "nth-child" | "nth-last-child" => {
+                    let mut raw = vec![];
+                    let mut of = None;
+                    while let Ok(next) = input.next_including_whitespace() {
+                        if let Token::Ident(x) = &*next {
+                            if x.eq_ignore_ascii_case("of") {
+                                of = Some(next);
+                                break;
+                            }
+                        }
+                        raw.push(parse_raw_token(input, next));
+                    }
+                    match of {
+                        Some(of) => PseudoArgs::NthOf(raw, of, parse_selector_list(input)),
+                        None => PseudoArgs::Raw(raw),
+                    }
+                }
+                "lang" | "dir" | "nth-of-type" | "nth-last-of-type" | "nth-col"
+                | "nth-last-col" | "state" | "part" | "highlight" => {
+                    PseudoArgs::Raw(parse_raw_tokens(input))
+                }
+                _ => {
+                    // treat the arguments as selectors only if they look like selectors
+                    let state = input.state();
+                    let list = parse_selector_list(input);
+                    if list.is_well_formed() {
+                        PseudoArgs::Selectors(list)
+                    } else {
+                        input.reset(&state);
+                        PseudoArgs::Raw(parse_raw_tokens(input))
+                    }
+                }
+            };
+            Ok(ret)
+        })
+        .unwrap_or(PseudoArgs::Raw(vec![]))
+}
+
+/// Parse a selector list until the input is exhausted.
+pub(crate) fn parse_selector_list<'i>(input: &mut StepParser<'i, '_, '_>) -> SelectorList<'i> {
+    let mut selectors = vec![];
+    let mut cur: Option<ComplexSelector> = None;
+    let mut compound = CompoundSelector::default();
+    let mut combinator: Option<(Combinator, Position)> = None;
+    let mut missing_compound = None;
+    let mut last_comma = None;
+    let mut has_whitespace = false;
+    input.skip_whitespace();
+    loop {
+        let Ok(next) = input.next_including_whitespace() else {
+            break;
+        };
+        let explicit_combinator = match &*next {
+            Token::WhiteSpace(_) => {
+                has_whitespace = true;
+                continue;
+            }
+            Token::Comma => {
+                if compound.simple_selectors.is_empty() {
+                    let pos = combinator.map(|x| x.1).unwrap_or(next.position);
+                    missing_compound = missing_compound.or(Some(pos));
+                }
+                end_compound(&mut cur, &mut combinator, &mut compound);
+                last_comma = Some(next.position);
+                selectors.push(cur.take().unwrap_or_else(|| empty_selector(next.position)));
+                has_whitespace = false;
+                input.skip_whitespace();
+                continue;
+            }
+            Token::Delim('>') => Some(Combinator::Child),
+            Token::Delim('+') => Some(Combinator::NextSibling),
+            Token::Delim('~') => Some(Combinator::SubsequentSibling),
+            _ => None,
+        };
+        last_comma = None;
+        if let Some(c) = explicit_combinator {
+            if compound.simple_selectors.is_empty() {
+                missing_compound = missing_compound.or(combinator.map(|x| x.1));
+            }
+            end_compound(&mut cur, &mut combinator, &mut compound);
+            combinator = Some((c, next.position));
+            has_whitespace = false;
+            continue;
+        }
+        if has_whitespace && !compound.simple_selectors.is_empty() {
+            end_compound(&mut cur, &mut combinator, &mut compound);
+            combinator = Some((Combinator::Descendant, next.position));
+        }
+        has_whitespace = false;
+        if cur.is_none() {
+            cur = Some(ComplexSelector {
+                position: combinator.map(|x| x.1).unwrap_or(next.position),
+                compounds: vec![],
+            });
+        }
+        let simple = match &*next {
+            // a type selector must be the first one in a compound selector, e.g. not `&-b`
+            Token::Ident(_) | Token::Delim('*') if !compound.simple_selectors.is_empty() => {
+                SimpleSelector::Unknown(RawToken::Token(next))
+            }
+            Token::Ident(_) | Token::Delim('*') => SimpleSelector::Type(next),
+            Token::Delim('&') => SimpleSelector::Nesting(next),
+            Token::IDHash(_) => SimpleSelector::Id(next),
+            Token::Delim('.') => {
+                let name = input.try_parse::<_, _, ()>(|input| {
+                    let name = input.next_including_whitespace().map_err(|_| ())?;
+                    match &*name {
+                        Token::Ident(_) => Ok(name),
+                        _ => Err(()),
+                    }
+                });
+                match name {
+                    Ok(name) => SimpleSelector::Class { dot: next, name },
+                    Err(_) => SimpleSelector::Unknown(RawToken::Token(next)),
+                }
+            }
+            Token::SquareBracketBlock => {
+                let contents = parse_raw_block(input);
+                SimpleSelector::Attribute {
+                    open: next,
+                    contents,
+                }
+            }
+            Token::Colon => parse_pseudo(input, next),
+            _ => SimpleSelector::Unknown(parse_raw_token(input, next)),
+        };
+        compound.simple_selectors.push(simple);
+    }
+    if compound.simple_selectors.is_empty() {
+        let pos = combinator.map(|x| x.1).or(last_comma);
+        missing_compound = missing_compound.or(pos);
+    }
+    end_compound(&mut cur, &mut combinator, &mut compound);
+    match cur {
+        Some(cur) => selectors.push(cur),
+        None => selectors.extend(last_comma.map(empty_selector)),
+    }
+    SelectorList {
+        selectors,
+        missing_compound,
+    }
+}
+
+/// An empty selector, kept so that a malformed list can be written back as-is, e.g. `.a,,.b` .
+fn empty_selector<'i>(position: Position) -> ComplexSelector<'i> {
+    ComplexSelector {
+        position,
+        compounds: vec![],
+    }
+}
+
+fn end_compound<'i>(
+    cur: &mut Option<ComplexSelector<'i>>,
+    combinator: &mut Option<(Combinator, Position)>,
+    compound: &mut CompoundSelector<'i>,
+) {
+    // a combinator without a following compound selector is kept with an empty one,
+    // so that it can be written back as-is
+    if compound.simple_selectors.is_empty() && combinator.is_none() {
+        return;
+    }
+    let compound = std::mem::take(compound);
+    if let (None, Some((_, pos))) = (&*cur, &*combinator) {
+        *cur = Some(empty_selector(*pos));
+    }
+    if let Some(cur) = cur.as_mut() {
+        cur.compounds.push((combinator.take(), compound));
+    }
+}
+
+fn parse_pseudo<'i>(
+    input: &mut StepParser<'i, '_, '_>,
+    colon: StepToken<'i>,
+) -> SimpleSelector<'i> {
+    let r = input.try_parse::<_, _, ()>(|input| {
+        let next = input.next_including_whitespace().map_err(|_| ())?;
+        match &*next {
+            Token::Colon => {
+                let name = input.next_including_whitespace().map_err(|_| ())?;
+                match &*name {
+                    Token::Ident(_) => Ok(SimpleSelector::PseudoElement {
+                        colons: (colon.clone(), next),
+                        name,
+                        args: None,
+                    }),
+                    Token::Function(_) => {
+                        let args = parse_pseudo_args(input, &pseudo_name(&name));
+                        Ok(SimpleSelector::PseudoElement {
+                            colons: (colon.clone(), next),
+                            name,
+                            args: Some(args),
+                        })
+                    }
+                    _ => Err(()),
+                }
+            }
+            Token::Ident(_) => Ok(SimpleSelector::PseudoClass {
+                colon: colon.clone(),
+                name: next,
+                args: None,
+            }),
+            Token::Function(_) => {
+                let args = parse_pseudo_args(input, &pseudo_name(&next));
+                Ok(SimpleSelector::PseudoClass {
+                    colon: colon.clone(),
+                    name: next,
+                    args: Some(args),
+                })
+            }
+            _ => Err(()),
+        }
+    });
+    r.unwrap_or(SimpleSelector::Unknown(RawToken::Token(colon)))
+}
+
+impl<'i> SelectorList<'i> {
+    /// Whether the list contains no `Unknown` items and no empty selectors.
+    pub(crate) fn is_well_formed(&self) -> bool {
+        !self.selectors.is_empty()
+            && self.missing_compound.is_none()
+            && self.selectors.iter().all(|x| {
+                !x.compounds.is_empty()
+                    && x.compounds.iter().all(|(_, c)| {
+                        c.simple_selectors.iter().all(|s| match s {
+                            SimpleSelector::Unknown(_) => false,
+                            SimpleSelector::PseudoClass { args, .. }
+                            | SimpleSelector::PseudoElement { args, .. } => match args {
+                                Some(PseudoArgs::Selectors(list))
+                                | Some(PseudoArgs::NthOf(_, _, list)) => list.is_well_formed(),
+                                _ => true,
+                            },
+                            _ => true,
+                        })
+                    })
+            })
+    }
+
+    /// The range of the first invalid part, e.g. an unrecognized token or a missing compound selector.
+    ///
+    /// Relative selectors (e.g. `> .a` ) and the nesting selector `&` are accepted only if `nested` is set.
+    pub(crate) fn invalid_range(&self, nested: bool) -> Option<Range<Position>> {
+        if let Some(pos) = self.missing_compound {
+            return Some(pos..next_col(pos));
+        }
+        if !nested {
+            let relative = self
+                .selectors
+                .iter()
+                .find_map(|x| x.compounds.first().and_then(|(c, _)| *c));
+            if let Some((_, pos)) = relative {
+                return Some(pos..next_col(pos));
+            }
+        }
+        let mut ret = None;
+        self.contains(&mut |s| match s {
+            SimpleSelector::Unknown(RawToken::Token(t) | RawToken::Block(t, _)) => {
+                ret = Some(t.position..t.end_position());
+                true
+            }
+            SimpleSelector::Nesting(t) if !nested => {
+                ret = Some(t.position..t.end_position());
+                true
+            }
+            _ => false,
+        });
+        ret
+    }
+
+    /// The names and ranges of the pseudo-elements, including the ones in pseudo-class arguments.
+    pub(crate) fn pseudo_elements(&self) -> Vec<(String, Range<Position>)> {
+        let mut ret = vec![];
+        self.contains(&mut |s| {
+            if let SimpleSelector::PseudoElement { colons, name, .. } = s {
+                ret.push((pseudo_name(name), colons.0.position..name.end_position()));
+            }
+            false
+        });
+        ret
+    }
+
+    fn contains(&self, f: &mut impl FnMut(&SimpleSelector<'i>) -> bool) -> bool {
+        self.selectors.iter().any(|x| x.contains(f))
+    }
+}
+
+fn next_col(pos: Position) -> Position {
+    Position {
+        line: pos.line,
+        utf16_col: pos.utf16_col + 1,
+    }
+}
+
+impl<'i> ComplexSelector<'i> {
+    fn contains(&self, f: &mut impl FnMut(&SimpleSelector<'i>) -> bool) -> bool {
+        self.compounds.iter().any(|(_, c)| {
+            c.simple_selectors.iter().any(|s| {
+                if f(s) {
+                    return true;
+                }
+                match s {
+                    SimpleSelector::PseudoClass { args, .. }
+                    | SimpleSelector::PseudoElement { args, .. } => match args {
+                        Some(PseudoArgs::Selectors(list)) | Some(PseudoArgs::NthOf(_, _, list)) => {
+                            list.contains(f)
+                        }
+                        _ => false,
+                    },
+                    _ => false,
+                }
+            })
+        })
+    }
+
+    /// Whether the nesting selector `&` is used (including in pseudo-class arguments).
+    pub(crate) fn contains_nesting(&self) -> bool {
+        self.contains(&mut |s| matches!(s, SimpleSelector::Nesting(_)))
+    }
+
+    /// Find out how `:host` is used in this selector.
+    pub(crate) fn host(&self) -> HostSelector<'_, 'i> {
+        let mut host_position = None;
+        self.contains(&mut |s| {
+            if let Some(pos) = s.host_position() {
+                host_position = Some(pos);
+                return true;
+            }
+            false
+        });
+        let Some(pos) = host_position else {
+            return HostSelector::None;
+        };
+        let Some((None, first)) = self.compounds.first() else {
+            return HostSelector::Invalid(pos);
+        };
+        if first.simple_selectors.len() != 1 {
+            return HostSelector::Invalid(pos);
+        }
+        let arg = match &first.simple_selectors[0] {
+            SimpleSelector::PseudoClass { args: None, .. } => None,
+            SimpleSelector::PseudoClass {
+                args: Some(PseudoArgs::Selectors(list)),
+                ..
+            } => match list.selectors.as_slice() {
+                [sel] => match sel.compounds.as_slice() {
+                    [(None, compound)] => Some(compound),
+                    _ => return HostSelector::Invalid(pos),
+                },
+                _ => return HostSelector::Invalid(pos),
+            },
+            _ => return HostSelector::Invalid(pos),
+        };
+        let rest_has_host = self.compounds[1..]
+            .iter()
+            .any(|(_, c)| c.simple_selectors.iter().any(|s| s.contains_host()))
+            || arg.is_some_and(|c| c.simple_selectors.iter().any(|s| s.contains_host()));
+        if rest_has_host {
+            return HostSelector::Invalid(pos);
+        }
+        HostSelector::Host(arg)
+    }
+}
+
+impl<'i> SimpleSelector<'i> {
+    fn host_position(&self) -> Option<Position> {
+        match self {
+            SimpleSelector::PseudoClass { colon, name, .. } if pseudo_name(name) == "host" => {
+                Some(colon.position)
+            }
+            _ => None,
+        }
+    }
+
+    fn contains_host(&self) -> bool {
+        if self.host_position().is_some() {
+            return true;
+        }
+        match self {
+            SimpleSelector::PseudoClass { args, .. }
+            | SimpleSelector::PseudoElement { args, .. } => match args {
+                Some(PseudoArgs::Selectors(list)) | Some(PseudoArgs::NthOf(_, _, list)) => {
+                    list.contains(&mut |s| s.host_position().is_some())
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
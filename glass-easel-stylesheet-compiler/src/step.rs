@@ -1,6 +1,6 @@
 use std::ops::{Deref, DerefMut};
 
-use cssparser::{BasicParseError, Token};
+use cssparser::{BasicParseError, ToCss, Token};
 
 use super::error;
 
@@ -35,6 +35,10 @@ impl<'i, 't, 'a> StepParser<'i, 't, 'a> {
         }
     }
 
+    pub(crate) fn source_position(&self) -> cssparser::SourcePosition {
+        self.parser.position()
+    }
+
     pub(crate) fn peek(&mut self) -> Result<StepToken<'i>, BasicParseError<'i>> {
         self.parser.skip_whitespace();
         self.peek_including_whitespace()
@@ -95,4 +99,13 @@ impl<'i> StepToken<'i> {
         let position = other.position.clone();
         Self { token, position }
     }
+
+    /// The position after the token, assuming the token is in a single line.
+    pub(crate) fn end_position(&self) -> error::Position {
+        let len = self.token.to_css_string().encode_utf16().count() as u32;
+        error::Position {
+            line: self.position.line,
+            utf16_col: self.position.utf16_col + len,
+        }
+    }
 }
@@ -0,0 +1,154 @@
+//! Cross-template checks in a `TmplGroup`
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::group::{TmplError, TmplGroup};
+use crate::parse::{
+    tag::{Element, ElementKind, TagLocation, Template, Value},
+    visit::{walk, Visit},
+    ParseError, ParseErrorKind, Position,
+};
+
+fn start_tag_location(tag_location: &TagLocation) -> Range<Position> {
+    tag_location.start.0.start..tag_location.start.1.end
+}
+
+struct TemplateRefCollector {
+    refs: Vec<(String, Range<Position>)>,
+}
+
+impl Visit for TemplateRefCollector {
+    fn visit_element(&mut self, element: &Element) {
+        if let ElementKind::TemplateRef {
+            target: (_, Value::Static { value, .. }),
+            ..
+        } = &element.kind
+        {
+            self.refs
+                .push((value.to_string(), start_tag_location(&element.tag_location)));
+        }
+        walk::walk_element(self, element);
+    }
+}
+
+impl TmplGroup {
+    /// Check the references between templates in the group.
+    ///
+    /// It reports missing `<import>` and `<include>` targets, unknown `<template is>` names,
+    /// circular `<include>` chains and shadowed template names.
+    /// The imported and included templates must be added to the group before checking.
+    pub fn check_references(&self, path: &str) -> Result<Vec<ParseError>, TmplError> {
+        let tree = self.get_tree(path)?;
+        let mut ret = vec![];
+        let mut add_err = |kind, location| {
+            ret.push(ParseError {
+                path: tree.path.clone(),
+                kind,
+                location,
+            })
+        };
+
+        // imports
+        let mut import_resolved = true;
+        let mut imported_names: HashSet<&str> = HashSet::new();
+        for import in tree.globals.imports.iter() {
+            let p = crate::path::resolve(&tree.path, &import.src.name);
+            let Ok(imported) = self.get_tree(&p) else {
+                import_resolved = false;
+                add_err(
+                    ParseErrorKind::DependencyNotFound,
+                    start_tag_location(&import.tag_location),
+                );
+                continue;
+            };
+            let mut shadowed = false;
+            for sub in imported.globals.sub_templates.iter() {
+                if !imported_names.insert(sub.name.name.as_str()) {
+                    shadowed = true;
+                }
+            }
+            if shadowed {
+                add_err(
+                    ParseErrorKind::ShadowedTemplateName,
+                    start_tag_location(&import.tag_location),
+                );
+            }
+        }
+
+        // local templates
+        for sub in tree.globals.sub_templates.iter() {
+            if imported_names.contains(sub.name.name.as_str()) {
+                add_err(
+                    ParseErrorKind::ShadowedTemplateName,
+                    start_tag_location(&sub.tag_location),
+                );
+            }
+        }
+
+        // template refs
+        if import_resolved {
+            let mut collector = TemplateRefCollector { refs: vec![] };
+            collector.visit_template(tree);
+            for (name, location) in collector.refs {
+                if self.find_sub_template(tree, &name).is_none() {
+                    add_err(ParseErrorKind::TemplateNotFound, location);
+                }
+            }
+        }
+
+        // includes
+        for include in tree.globals.includes.iter() {
+            let p = crate::path::resolve(&tree.path, &include.src.name);
+            match self.get_tree(&p) {
+                Err(_) => {
+                    add_err(
+                        ParseErrorKind::DependencyNotFound,
+                        start_tag_location(&include.tag_location),
+                    );
+                }
+                Ok(included) => {
+                    if self.include_reachable(included, &tree.path, &mut HashSet::new()) {
+                        add_err(
+                            ParseErrorKind::CircularInclude,
+                            start_tag_location(&include.tag_location),
+                        );
+                    }
+                }
+            }
+        }
+
+        ret.sort_by_key(|x| x.location.start);
+        Ok(ret)
+    }
+
+    /// Check the references of all templates, ordered by the template paths.
+    pub fn check_all_references(&self) -> Vec<ParseError> {
+        self.list_template_trees()
+            .flat_map(|(p, _)| self.check_references(p).unwrap_or_default())
+            .collect()
+    }
+
+    fn include_reachable<'a>(
+        &'a self,
+        from: &'a Template,
+        target: &str,
+        visited: &mut HashSet<&'a str>,
+    ) -> bool {
+        if from.path == target {
+            return true;
+        }
+        if !visited.insert(from.path.as_str()) {
+            return false;
+        }
+        for include in from.globals.includes.iter() {
+            let p = crate::path::resolve(&from.path, &include.src.name);
+            if let Ok(next) = self.get_tree(&p) {
+                if self.include_reachable(next, target, visited) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
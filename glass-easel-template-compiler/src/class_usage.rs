@@ -0,0 +1,162 @@
+//! Class name usage between templates and stylesheets
+
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::group::{TmplError, TmplGroup};
+use crate::parse::{
+    tag::{ClassNames, Element, ElementKind, Template},
+    visit::{walk, Visit},
+    Position,
+};
+
+/// A class name referenced in a template.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassNameRef {
+    pub path: String,
+    pub name: String,
+    pub location: Range<Position>,
+}
+
+/// The class names referenced by a template and the templates imported or included by it.
+#[derive(Debug, Clone, Default)]
+pub struct ClassUsage {
+    /// Class names in `class` and `class:` attributes.
+    pub class_refs: Vec<ClassNameRef>,
+    /// Class names passed in other attributes whose names end with `class` , e.g. `hover-class` .
+    pub other_names: BTreeSet<String>,
+    /// Whether some class names cannot be determined statically, e.g. `class="{{ a }}"` .
+    pub incomplete: bool,
+}
+
+impl ClassUsage {
+    /// Whether the class name may be used.
+    pub fn contains(&self, name: &str) -> bool {
+        self.other_names.contains(name) || self.class_refs.iter().any(|x| x.name == name)
+    }
+}
+
+/// The comparison between the class names in templates and the ones defined in stylesheets.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClassUsageReport {
+    /// Class names defined in stylesheets but never used in templates (sorted).
+    ///
+    /// It is always empty if some class names in templates cannot be determined statically.
+    pub unused_classes: Vec<String>,
+    /// Class names used in `class` and `class:` attributes but not defined in stylesheets.
+    pub undefined_classes: Vec<ClassNameRef>,
+    /// Whether some class names in templates cannot be determined statically.
+    pub incomplete: bool,
+}
+
+struct ClassNameCollector<'a> {
+    path: &'a str,
+    usage: &'a mut ClassUsage,
+}
+
+impl ClassNameCollector<'_> {
+    fn add_class_names(&mut self, names: ClassNames) {
+        for (name, location) in names.names {
+            self.usage.class_refs.push(ClassNameRef {
+                path: self.path.to_string(),
+                name: name.to_string(),
+                location,
+            });
+        }
+        self.usage.incomplete |= names.incomplete;
+    }
+}
+
+impl Visit for ClassNameCollector<'_> {
+    fn visit_element(&mut self, element: &Element) {
+        if let ElementKind::Normal {
+            class, attributes, ..
+        } = &element.kind
+        {
+            self.add_class_names(class.class_names());
+            for attr in attributes.iter() {
+                if !attr.name.name.ends_with("class") {
+                    continue;
+                }
+                let Some(value) = attr.value.as_ref() else {
+                    continue;
+                };
+                let mut names = ClassNames::default();
+                names.add_value(value);
+                self.usage.incomplete |= names.incomplete;
+                self.usage
+                    .other_names
+                    .extend(names.names.into_iter().map(|x| x.0.to_string()));
+            }
+        }
+        walk::walk_element(self, element);
+    }
+}
+
+impl TmplGroup {
+    /// Collect the class names referenced by a template.
+    ///
+    /// The templates imported or included by it (recursively) are also collected,
+    /// since they are styled by the same stylesheet.
+    pub fn class_usage(&self, path: &str) -> Result<ClassUsage, TmplError> {
+        let mut usage = ClassUsage::default();
+        let mut visited = HashSet::new();
+        self.collect_class_usage(self.get_tree(path)?, &mut usage, &mut visited);
+        Ok(usage)
+    }
+
+    fn collect_class_usage<'a>(
+        &'a self,
+        tree: &'a Template,
+        usage: &mut ClassUsage,
+        visited: &mut HashSet<&'a str>,
+    ) {
+        if !visited.insert(tree.path.as_str()) {
+            return;
+        }
+        ClassNameCollector {
+            path: &tree.path,
+            usage,
+        }
+        .visit_template(tree);
+        for dep in tree.direct_dependencies() {
+            if let Ok(dep) = self.get_tree(&dep) {
+                self.collect_class_usage(dep, usage, visited);
+            }
+        }
+    }
+
+    /// Compare the class names referenced by a template with the ones defined in its stylesheets.
+    ///
+    /// `defined` is the class names in the stylesheets of the component,
+    /// e.g. collected by the stylesheet compiler.
+    pub fn class_usage_report<'a>(
+        &self,
+        path: &str,
+        defined: impl IntoIterator<Item = &'a str>,
+    ) -> Result<ClassUsageReport, TmplError> {
+        let usage = self.class_usage(path)?;
+        let defined: BTreeSet<&str> = defined.into_iter().collect();
+        let unused_classes = if usage.incomplete {
+            vec![]
+        } else {
+            defined
+                .iter()
+                .filter(|x| !usage.contains(x))
+                .map(|x| x.to_string())
+                .collect()
+        };
+        let undefined_classes = usage
+            .class_refs
+            .into_iter()
+            .filter(|x| !defined.contains(x.name.as_str()))
+            .collect();
+        Ok(ClassUsageReport {
+            unused_classes,
+            undefined_classes,
+            incomplete: usage.incomplete,
+        })
+    }
+}
@@ -31,6 +31,14 @@ pub(crate) fn gen_lit_str(s: &str) -> String {
     format!("{:?}", s)
 }
 
+pub(crate) fn gen_lit_template_str_part(s: &str) -> String {
+    let quoted = gen_lit_str(s);
+    quoted[1..quoted.len() - 1]
+        .replace("\\\"", "\"")
+        .replace('`', "\\`")
+        .replace("${", "\\${")
+}
+
 pub(crate) fn dash_to_camel(s: &str) -> CompactString {
     let mut camel_name = CompactString::new("");
     let mut next_upper = false;
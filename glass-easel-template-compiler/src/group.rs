@@ -1,14 +1,22 @@
 //! The template group for cross references
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::fmt::Write;
 
+use serde::Serialize;
 use sourcemap::SourceMap;
 
 use crate::escape::gen_lit_str;
-use crate::parse::{ParseError, Template};
-use crate::proc_gen::{JsFunctionScopeWriter, JsTopScopeWriter};
+use crate::parse::{
+    tag::{Element, ElementKind, TemplateDefinition},
+    visit::{walk_mut, VisitMut},
+    ParseError, ParseErrorKind, Template, AST_JSON_VERSION,
+};
+use crate::proc_gen::{
+    escape_source_markers, extract_source_map, JsFunctionScopeWriter, JsTopScopeWriter,
+};
 use crate::stringify::Stringify;
 
 // PRESERVED one-letter vars
@@ -173,27 +181,54 @@ impl From<fmt::Error> for TmplError {
     }
 }
 
+impl From<serde_json::Error> for TmplError {
+    fn from(e: serde_json::Error) -> Self {
+        Self {
+            message: e.to_string(),
+        }
+    }
+}
+
 impl std::error::Error for TmplError {}
 
+#[derive(Serialize)]
+struct AstJson<'a> {
+    version: u32,
+    tree: &'a Template,
+}
+
+#[derive(Serialize)]
+struct AstJsonGroups<'a> {
+    version: u32,
+    trees: &'a BTreeMap<String, Template>,
+}
+
 /// A template group in which the templates can ref each other.
 #[derive(Debug)]
 pub struct TmplGroup {
-    trees: HashMap<String, Template>,
-    scripts: HashMap<String, String>,
+    // sorted maps keep the generated code in a stable order
+    trees: BTreeMap<String, Template>,
+    scripts: BTreeMap<String, String>,
     has_scripts: bool,
     extra_runtime_string: String,
     dev_mode: bool,
+    // the dependency path to the templates which depend on it
+    reverse_dependencies: HashMap<String, HashSet<String>>,
+    // the generated code of each template
+    gen_object_cache: RefCell<HashMap<String, String>>,
 }
 
 impl TmplGroup {
     /// Create a new template group.
     pub fn new() -> Self {
         Self {
-            trees: HashMap::new(),
-            scripts: HashMap::new(),
+            trees: BTreeMap::new(),
+            scripts: BTreeMap::new(),
             has_scripts: false,
             extra_runtime_string: String::new(),
             dev_mode: false,
+            reverse_dependencies: HashMap::new(),
+            gen_object_cache: RefCell::new(HashMap::new()),
         }
     }
 
@@ -211,7 +246,13 @@ impl TmplGroup {
 
     /// import another group.
     pub fn import_group(&mut self, group: &TmplGroup) {
-        self.trees.extend(group.trees.clone());
+        for (path, tree) in group.trees.iter() {
+            self.insert_tree(tree.clone());
+            self.invalidate(path);
+        }
+        for path in group.scripts.keys() {
+            self.invalidate(path);
+        }
         self.scripts.extend(group.scripts.clone());
         self.has_scripts = self.has_scripts || group.has_scripts;
         self.extra_runtime_string
@@ -229,7 +270,11 @@ impl TmplGroup {
     }
 
     /// Get a mutable ref of a parsed tree in the group.
+    ///
+    /// The cached generated code of the template and its dependents are dropped.
+    /// However, the dependencies of the template should not be changed through this ref.
     pub fn get_tree_mut(&mut self, path: &str) -> Result<&mut Template, TmplError> {
+        self.invalidate(path);
         match self.trees.get_mut(path) {
             Some(x) => Ok(x),
             None => Err(TmplError {
@@ -238,17 +283,168 @@ impl TmplGroup {
         }
     }
 
+    /// Find the sub-template that `<template is>` refers to in `tree` .
+    ///
+    /// Like the generated code, the ones defined in `tree` take precedence over the imported ones,
+    /// and the later imports take precedence over the earlier ones.
+    /// Imports that are not in the group are skipped.
+    /// Returns the template defining it, the definition, and the index of the import it comes from.
+    pub(crate) fn find_sub_template<'a>(
+        &'a self,
+        tree: &'a Template,
+        name: &str,
+    ) -> Option<(&'a Template, &'a TemplateDefinition, Option<usize>)> {
+        let find = |tree: &'a Template| {
+            tree.globals
+                .sub_templates
+                .iter()
+                .find(|x| x.name.name == name)
+        };
+        if let Some(x) = find(tree) {
+            return Some((tree, x, None));
+        }
+        tree.globals
+            .imports
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, import)| {
+                let path = crate::path::resolve(&tree.path, &import.src.name);
+                let imported = self.get_tree(&path).ok()?;
+                find(imported).map(|x| (imported, x, Some(index)))
+            })
+    }
+
+    /// Rename the class names in a template with a class name mapping.
+    ///
+    /// The mapping is usually generated by the stylesheet compiler with `hash_class_names` enabled,
+    /// i.e. `{ "original": "hashed" }` .
+    /// Class names not in the mapping are not changed.
+    /// In dynamic values, only the class names in string literals can be renamed,
+    /// and a warning is returned for each dynamic value that cannot be renamed.
+    /// The mapping should be applied again if the template is added again.
+    pub fn apply_class_name_mapping(
+        &mut self,
+        path: &str,
+        mapping: &HashMap<String, String>,
+    ) -> Result<Vec<ParseError>, TmplError> {
+        struct ClassRenamer<'a> {
+            mapping: &'a HashMap<String, String>,
+            path: &'a str,
+            warnings: Vec<ParseError>,
+        }
+
+        impl VisitMut for ClassRenamer<'_> {
+            fn visit_element(&mut self, element: &mut Element) {
+                if let ElementKind::Normal { class, .. } = &mut element.kind {
+                    let mapping = self.mapping;
+                    let failed =
+                        class.rename_classes(|x| mapping.get(x).map(|x| x.as_str().into()));
+                    if let Some(location) = failed {
+                        self.warnings.push(ParseError {
+                            path: self.path.to_string(),
+                            kind: ParseErrorKind::ClassNamesNotRenamed,
+                            location,
+                        });
+                    }
+                }
+                walk_mut::walk_element(self, element);
+            }
+        }
+
+        let tree = self.get_tree_mut(path)?;
+        let mut renamer = ClassRenamer {
+            mapping,
+            path,
+            warnings: vec![],
+        };
+        renamer.visit_template(tree);
+        Ok(renamer.warnings)
+    }
+
     /// Add a template into the group.
+    ///
+    /// The returned errors also contain the syntax errors in the inline scripts (see `check_inline_scripts` ).
     pub fn add_tmpl(&mut self, path: &str, tmpl_str: &str) -> Vec<ParseError> {
         let (template, mut parse_state) = crate::parse::parse(path, tmpl_str);
         if template.inline_script_module_names().next().is_some() {
             self.has_scripts = true;
         }
-        let ret = parse_state.take_warnings();
-        self.trees.insert(template.path.clone(), template);
+        let mut ret = parse_state.take_warnings();
+        let path = template.path.clone();
+        self.insert_tree(template);
+        self.invalidate(&path);
+        if let Ok(script_errors) = self.check_inline_scripts(&path) {
+            ret.extend(script_errors);
+        }
+        ret
+    }
+
+    fn insert_tree(&mut self, template: Template) {
+        let path = template.path.clone();
+        if let Some(old) = self.trees.remove(&path) {
+            self.remove_reverse_dependencies(&old);
+        }
+        for dep in template
+            .direct_dependencies()
+            .chain(template.script_dependencies())
+        {
+            self.reverse_dependencies
+                .entry(dep)
+                .or_default()
+                .insert(path.clone());
+        }
+        self.trees.insert(path, template);
+    }
+
+    fn remove_reverse_dependencies(&mut self, template: &Template) {
+        for dep in template
+            .direct_dependencies()
+            .chain(template.script_dependencies())
+        {
+            if let Some(set) = self.reverse_dependencies.get_mut(&dep) {
+                set.remove(&template.path);
+                if set.is_empty() {
+                    self.reverse_dependencies.remove(&dep);
+                }
+            }
+        }
+    }
+
+    /// Get the templates which should be regenerated when the specified file changes.
+    ///
+    /// The `path` can be a template or a script.
+    /// The result contains the `path` itself (if it is a template in the group)
+    /// and all templates which directly or indirectly depend on it, in sorted order.
+    pub fn invalidated_templates(&self, path: &str) -> Vec<String> {
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut stack = vec![path];
+        while let Some(p) = stack.pop() {
+            if let Some(dependents) = self.reverse_dependencies.get(p) {
+                for d in dependents.iter() {
+                    if visited.insert(d.as_str()) {
+                        stack.push(d.as_str());
+                    }
+                }
+            }
+        }
+        if self.trees.contains_key(path) {
+            visited.insert(path);
+        }
+        let mut ret: Vec<_> = visited.into_iter().map(|x| x.to_string()).collect();
+        ret.sort();
         ret
     }
 
+    fn invalidate(&mut self, path: &str) {
+        let list = self.invalidated_templates(path);
+        let cache = self.gen_object_cache.get_mut();
+        cache.remove(path);
+        for p in list {
+            cache.remove(&p);
+        }
+    }
+
     /// Remove a template from the group.
     ///
     /// This simply removes a template path.
@@ -256,7 +452,14 @@ impl TmplGroup {
     /// but not suitable for final builds since it does not do cleanups.
     /// Returns true when a template is actually removed.
     pub fn remove_tmpl(&mut self, path: &str) -> bool {
-        self.trees.remove(path).is_some()
+        self.invalidate(path);
+        match self.trees.remove(path) {
+            Some(old) => {
+                self.remove_reverse_dependencies(&old);
+                true
+            }
+            None => false,
+        }
     }
 
     /// Regenerate a template content string of the specified template.
@@ -273,8 +476,32 @@ impl TmplGroup {
         Some(stringify_result)
     }
 
+    /// Export the parsed tree of the specified template as a versioned JSON string.
+    ///
+    /// The output is an object with `version` (see `AST_JSON_VERSION` ) and `tree` fields.
+    pub fn get_tree_ast_json(&self, path: &str) -> Result<String, TmplError> {
+        let tree = self.get_tree(path)?;
+        let ret = serde_json::to_string(&AstJson {
+            version: AST_JSON_VERSION,
+            tree,
+        })?;
+        Ok(ret)
+    }
+
+    /// Export the parsed trees of all templates as a versioned JSON string.
+    ///
+    /// The output is an object with `version` (see `AST_JSON_VERSION` ) and `trees` fields,
+    /// and `trees` is a map from the template paths to the trees.
+    pub fn get_ast_json_groups(&self) -> Result<String, TmplError> {
+        let ret = serde_json::to_string(&AstJsonGroups {
+            version: AST_JSON_VERSION,
+            trees: &self.trees,
+        })?;
+        Ok(ret)
+    }
+
     /// Get a script segment in the group.
-    pub fn get_script(&mut self, path: &str) -> Result<&str, TmplError> {
+    pub fn get_script(&self, path: &str) -> Result<&str, TmplError> {
         match self.scripts.get(path) {
             Some(x) => Ok(x.as_str()),
             None => Err(TmplError {
@@ -287,9 +514,12 @@ impl TmplGroup {
     ///
     /// The `content` must be valid JavaScript file content.
     /// `require` and `exports` can be visited in this JavaScript segment, similar to Node.js.
-    pub fn add_script(&mut self, path: &str, content: &str) {
+    /// Returns the syntax errors in the script (see `check_script` ).
+    pub fn add_script(&mut self, path: &str, content: &str) -> Vec<ParseError> {
         self.scripts.insert(path.to_string(), content.to_string());
         self.has_scripts = true;
+        self.invalidate(path);
+        self.check_script(path).unwrap_or_default()
     }
 
     /// Remove a script segment from the group.
@@ -299,6 +529,7 @@ impl TmplGroup {
     /// but not suitable for final builds since it does not do cleanups.
     /// Returns true when a script is actually removed.
     pub fn remove_script(&mut self, path: &str) -> bool {
+        self.invalidate(path);
         self.scripts.remove(path).is_some()
     }
 
@@ -386,14 +617,47 @@ impl TmplGroup {
     }
 
     /// Convert to WXML GenObject js string.
+    ///
+    /// The result is cached until the template or its dependencies change.
     pub fn get_tmpl_gen_object(&self, path: &str) -> Result<String, TmplError> {
+        self.tmpl_gen_object(path, false)
+    }
+
+    /// Convert to WXML GenObject js string, with a source map to the template.
+    ///
+    /// The source map maps each generated expression to the expression in the template.
+    pub fn get_tmpl_gen_object_with_source_map(
+        &self,
+        path: &str,
+    ) -> Result<(String, SourceMap), TmplError> {
+        let code = self.tmpl_gen_object(path, true)?;
+        Ok(extract_source_map(&code))
+    }
+
+    // the result contains source map markers if `source_map` is set
+    fn tmpl_gen_object(&self, path: &str, source_map: bool) -> Result<String, TmplError> {
+        if !source_map {
+            if let Some(x) = self.gen_object_cache.borrow().get(path) {
+                return Ok(x.clone());
+            }
+        }
         let tree = self.get_tree(path)?;
-        let mut w = JsTopScopeWriter::new(String::new());
+        let mut w = if source_map {
+            JsTopScopeWriter::new_with_source_map(String::new())
+        } else {
+            JsTopScopeWriter::new(String::new())
+        };
         w.expr_scope(|w| {
             tree.to_proc_gen(w, self)?;
             Ok(())
         })?;
-        Ok(w.finish())
+        let ret = w.finish();
+        if !source_map {
+            self.gen_object_cache
+                .borrow_mut()
+                .insert(path.to_string(), ret.clone());
+        }
+        Ok(ret)
     }
 
     fn write_group_global_content(
@@ -402,7 +666,7 @@ impl TmplGroup {
     ) -> Result<(), TmplError> {
         runtime_fns(w, self.has_scripts)?;
         if self.extra_runtime_string.len() > 0 {
-            w.custom_stmt_str(&self.extra_runtime_string)?;
+            w.custom_stmt_str(&escape_source_markers(&self.extra_runtime_string))?;
         }
         self.write_all_scripts(w)?;
         Ok(())
@@ -415,7 +679,7 @@ impl TmplGroup {
                     write!(
                         w,
                         r#"R[{path}]=D({path},(require,exports,module)=>{{{}}})"#,
-                        script,
+                        escape_source_markers(script),
                         path = gen_lit_str(p)
                     )?;
                     Ok(())
@@ -427,6 +691,18 @@ impl TmplGroup {
 
     /// Convert all to WXML GenObject js string.
     pub fn get_tmpl_gen_object_groups(&self) -> Result<String, TmplError> {
+        self.tmpl_gen_object_groups(false)
+    }
+
+    /// Convert all to WXML GenObject js string, with a source map to the templates.
+    pub fn get_tmpl_gen_object_groups_with_source_map(
+        &self,
+    ) -> Result<(String, SourceMap), TmplError> {
+        let code = self.tmpl_gen_object_groups(true)?;
+        Ok(extract_source_map(&code))
+    }
+
+    fn tmpl_gen_object_groups(&self, source_map: bool) -> Result<String, TmplError> {
         let mut w = JsTopScopeWriter::new(String::new());
         w.expr_scope(|w| {
             w.paren(|w| {
@@ -440,10 +716,10 @@ impl TmplGroup {
                         Ok(())
                     })?;
                     self.write_group_global_content(w)?;
-                    for (path, tree) in self.trees.iter() {
+                    for path in self.trees.keys() {
                         w.expr_stmt(|w| {
                             write!(w, r#"G[{}]="#, gen_lit_str(path))?;
-                            tree.to_proc_gen(w, self)?;
+                            write!(w, "{}", self.tmpl_gen_object(path, source_map)?)?;
                             Ok(())
                         })?;
                     }
@@ -462,6 +738,19 @@ impl TmplGroup {
 
     /// Convert all to WXML GenObject js string, with wx environment support.
     pub fn get_wx_gen_object_groups(&self) -> Result<String, TmplError> {
+        self.wx_gen_object_groups(false)
+    }
+
+    /// Convert all to WXML GenObject js string with wx environment support,
+    /// with a source map to the templates.
+    pub fn get_wx_gen_object_groups_with_source_map(
+        &self,
+    ) -> Result<(String, SourceMap), TmplError> {
+        let code = self.wx_gen_object_groups(true)?;
+        Ok(extract_source_map(&code))
+    }
+
+    fn wx_gen_object_groups(&self, source_map: bool) -> Result<String, TmplError> {
         let mut w = JsTopScopeWriter::new(String::new());
         w.expr_scope(|w| {
             w.paren(|w| {
@@ -475,10 +764,10 @@ impl TmplGroup {
                         Ok(())
                     })?;
                     self.write_group_global_content(w)?;
-                    for (path, tree) in self.trees.iter() {
+                    for path in self.trees.keys() {
                         w.expr_stmt(|w| {
                             write!(w, r#"__wxCodeSpace__.addCompiledTemplate({path},{{groupList:G,content:G[{path}]="#, path = gen_lit_str(path))?;
-                            tree.to_proc_gen(w, self)?;
+                            write!(w, "{}", self.tmpl_gen_object(path, source_map)?)?;
                             write!(w, "}})")?;
                             Ok(())
                         })?;
@@ -514,15 +803,28 @@ impl TmplGroup {
     }
 
     /// Get a string that used to check TypeScript problems.
+    ///
+    /// `tag_events` gives the event types to check the event handlers against,
+    /// i.e. `{ "tag-name": { "event-name": "EventType" } }` .
     pub(crate) fn get_tmpl_converted_expr(
         &self,
         path: &str,
         ts_env: &str,
+        component_types: &crate::stringify::typescript::ComponentTypes,
+        tag_events: &HashMap<String, HashMap<String, String>>,
     ) -> Result<(String, SourceMap), TmplError> {
         let tree = self.get_tree(path)?;
-        let env = crate::stringify::typescript::tmpl_converted_expr_runtime_string();
+        let env = format!(
+            "{}{}",
+            crate::stringify::typescript::tmpl_converted_expr_runtime_string(),
+            crate::stringify::typescript::tmpl_converted_expr_tag_events(tag_events),
+        );
         Ok(crate::stringify::typescript::generate_tmpl_converted_expr(
-            tree, ts_env, env,
+            self,
+            tree,
+            ts_env,
+            &env,
+            component_types,
         ))
     }
 
@@ -536,7 +838,7 @@ impl TmplGroup {
         self.trees.contains_key(path)
     }
 
-    /// List all available templates.
+    /// List all available templates, ordered by the template paths.
     pub fn list_template_trees(&self) -> impl Iterator<Item = (&str, &Template)> {
         self.trees.iter().map(|(name, tmpl)| (name.as_str(), tmpl))
     }
@@ -41,6 +41,46 @@ impl From<ParseError> for TemplateParseError {
     }
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClassNameRef {
+    path: String,
+    name: String,
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClassUsageReport {
+    unused_classes: Vec<String>,
+    undefined_classes: Vec<ClassNameRef>,
+    incomplete: bool,
+}
+
+impl From<class_usage::ClassUsageReport> for ClassUsageReport {
+    fn from(value: class_usage::ClassUsageReport) -> Self {
+        Self {
+            unused_classes: value.unused_classes,
+            undefined_classes: value
+                .undefined_classes
+                .into_iter()
+                .map(|x| ClassNameRef {
+                    path: x.path,
+                    name: x.name,
+                    start_line: x.location.start.line,
+                    start_column: x.location.start.utf16_col,
+                    end_line: x.location.end.line,
+                    end_column: x.location.end.utf16_col,
+                })
+                .collect(),
+            incomplete: value.incomplete,
+        }
+    }
+}
+
 #[wasm_bindgen]
 pub struct TmplGroup {
     group: crate::TmplGroup,
@@ -92,6 +132,43 @@ impl TmplGroup {
         self.group.remove_tmpl(&path)
     }
 
+    /// Rename class names in a template with a JSON class name mapping.
+    ///
+    /// The mapping is usually generated by the stylesheet compiler, i.e. `{ "original": "hashed" }` .
+    /// Returns the warnings for the dynamic class values that cannot be renamed.
+    #[wasm_bindgen(js_name = applyClassNameMapping)]
+    pub fn apply_class_name_mapping(
+        &mut self,
+        path: &str,
+        mapping: &str,
+    ) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let mapping: std::collections::HashMap<String, String> = serde_json::from_str(mapping)?;
+        let ret: Vec<_> = self
+            .group
+            .apply_class_name_mapping(&path, &mapping)?
+            .into_iter()
+            .map(TemplateParseError::from)
+            .collect();
+        Ok(serde_wasm_bindgen::to_value(&ret)?)
+    }
+
+    /// Compare the class names used in a template with the class names defined in its stylesheets.
+    #[wasm_bindgen(js_name = getClassUsageReport)]
+    pub fn get_class_usage_report(
+        &self,
+        path: &str,
+        class_names: Vec<String>,
+    ) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let report = self
+            .group
+            .class_usage_report(&path, class_names.iter().map(|x| x.as_str()))?;
+        Ok(serde_wasm_bindgen::to_value(&ClassUsageReport::from(
+            report,
+        ))?)
+    }
+
     /// Regenerate a template content string for the specified template.
     #[wasm_bindgen(js_name = stringifyTmpl)]
     pub fn stringify_tmpl(&mut self, path: &str) -> Option<String> {
@@ -99,10 +176,88 @@ impl TmplGroup {
         self.group.stringify_tmpl(&path)
     }
 
+    /// Export the parsed tree of the specified template as a versioned JSON string.
+    #[wasm_bindgen(js_name = getTreeAstJson)]
+    pub fn get_tree_ast_json(&self, path: &str) -> Result<String, JsError> {
+        let path = crate::path::normalize(path);
+        Ok(self.group.get_tree_ast_json(&path)?)
+    }
+
+    /// Export the parsed trees of all templates as a versioned JSON string.
+    #[wasm_bindgen(js_name = getAstJsonGroups)]
+    pub fn get_ast_json_groups(&self) -> Result<String, JsError> {
+        Ok(self.group.get_ast_json_groups()?)
+    }
+
+    /// Check the references between templates, i.e. imports, includes and `<template is>` names.
+    ///
+    /// Returns an JavaScript array of `TemplateParseError` .
+    ///
+    #[wasm_bindgen(js_name = checkReferences)]
+    pub fn check_references(&self, path: &str) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let ret: Vec<_> = self
+            .group
+            .check_references(&path)?
+            .into_iter()
+            .map(TemplateParseError::from)
+            .collect();
+        Ok(serde_wasm_bindgen::to_value(&ret)?)
+    }
+
+    /// Check the references of all templates in the group.
+    #[wasm_bindgen(js_name = checkAllReferences)]
+    pub fn check_all_references(&self) -> JsValue {
+        let ret: Vec<_> = self
+            .group
+            .check_all_references()
+            .into_iter()
+            .map(TemplateParseError::from)
+            .collect();
+        serde_wasm_bindgen::to_value(&ret).unwrap()
+    }
+
+    /// Check the syntax of the inline scripts (`<wxs>` with content) in a template.
+    ///
+    /// Returns an JavaScript array of `TemplateParseError` .
+    #[wasm_bindgen(js_name = checkInlineScripts)]
+    pub fn check_inline_scripts(&self, path: &str) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let ret: Vec<_> = self
+            .group
+            .check_inline_scripts(&path)?
+            .into_iter()
+            .map(TemplateParseError::from)
+            .collect();
+        Ok(serde_wasm_bindgen::to_value(&ret)?)
+    }
+
+    /// Check the syntax of an external script.
+    ///
+    /// Returns an JavaScript array of `TemplateParseError` .
+    #[wasm_bindgen(js_name = checkScript)]
+    pub fn check_script(&self, path: &str) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let ret: Vec<_> = self
+            .group
+            .check_script(&path)?
+            .into_iter()
+            .map(TemplateParseError::from)
+            .collect();
+        Ok(serde_wasm_bindgen::to_value(&ret)?)
+    }
+
+    /// Add a script and check its syntax.
+    ///
+    /// Returns an JavaScript array containing all syntax errors in the script.
+    /// Each array item is an `TemplateParseError` .
+    ///
     #[wasm_bindgen(js_name = addScript)]
-    pub fn add_script(&mut self, path: &str, tmpl_str: &str) {
+    pub fn add_script(&mut self, path: &str, tmpl_str: &str) -> JsValue {
         let path = crate::path::normalize(path);
-        self.group.add_script(&path, tmpl_str);
+        let errors = self.group.add_script(&path, tmpl_str);
+        let ret: Vec<_> = errors.into_iter().map(TemplateParseError::from).collect();
+        serde_wasm_bindgen::to_value(&ret).unwrap()
     }
 
     #[wasm_bindgen(js_name = removeScript)]
@@ -123,6 +278,13 @@ impl TmplGroup {
         Ok(convert_str_arr(dependencies))
     }
 
+    /// Get the templates which should be regenerated when the specified template or script changes.
+    #[wasm_bindgen(js_name = "getInvalidatedTemplates")]
+    pub fn get_invalidated_templates(&self, path: &str) -> js_sys::Array {
+        let path = crate::path::normalize(path);
+        convert_str_arr(self.group.invalidated_templates(&path).into_iter())
+    }
+
     #[wasm_bindgen(js_name = "getInlineScriptModuleNames")]
     pub fn get_inline_script_module_names(&self, path: &str) -> Result<js_sys::Array, JsError> {
         let names = self.group.inline_script_module_names(path)?;
@@ -187,6 +349,27 @@ impl TmplGroup {
         Ok(self.group.get_wx_gen_object_groups()?)
     }
 
+    #[wasm_bindgen(js_name = "getTmplGenObjectWithSourceMap")]
+    pub fn get_tmpl_gen_object_with_source_map(
+        &self,
+        path: &str,
+    ) -> Result<CodeWithSourceMap, JsError> {
+        let (code, source_map) = self.group.get_tmpl_gen_object_with_source_map(path)?;
+        CodeWithSourceMap::new(code, source_map)
+    }
+
+    #[wasm_bindgen(js_name = "getTmplGenObjectGroupsWithSourceMap")]
+    pub fn get_tmpl_gen_object_groups_with_source_map(&self) -> Result<CodeWithSourceMap, JsError> {
+        let (code, source_map) = self.group.get_tmpl_gen_object_groups_with_source_map()?;
+        CodeWithSourceMap::new(code, source_map)
+    }
+
+    #[wasm_bindgen(js_name = "getWxGenObjectGroupsWithSourceMap")]
+    pub fn get_wx_gen_object_groups_with_source_map(&self) -> Result<CodeWithSourceMap, JsError> {
+        let (code, source_map) = self.group.get_wx_gen_object_groups_with_source_map()?;
+        CodeWithSourceMap::new(code, source_map)
+    }
+
     #[wasm_bindgen(js_name = "exportGlobals")]
     pub fn export_globals(&self) -> Result<String, JsError> {
         Ok(self.group.export_globals()?)
@@ -202,15 +385,38 @@ impl TmplGroup {
         self.group.import_group(&group.group)
     }
 
+    /// The `component_types` is a JSON object containing the TypeScript property types of the custom components,
+    /// i.e. `{ "properties": { "tag-name": "PropertyType" }, "generics": { "tag-name": { "generic-name": "PropertyType" } } }` ,
+    /// in which `generics` gives the property values that a component passes to its generics.
+    /// The `tag_events` is a JSON mapping from tag names and event names to TypeScript event types,
+    /// i.e. `{ "tag-name": { "event-name": "EventType" } }` .
     #[wasm_bindgen(js_name = "getTmplConvertedExpr")]
     #[doc(hidden)]
     pub fn get_tmpl_converted_expr(
         &mut self,
         path: &str,
         ts_env: &str,
+        component_types: Option<String>,
+        tag_events: Option<String>,
     ) -> Result<TmplConvertedExpr, JsError> {
-        let (code, source_map) = self.group.get_tmpl_converted_expr(path, ts_env)?;
-        Ok(TmplConvertedExpr { code, source_map })
+        let component_types: crate::stringify::typescript::ComponentTypes = match component_types {
+            Some(x) => serde_json::from_str(&x)?,
+            None => Default::default(),
+        };
+        let tag_events: std::collections::HashMap<_, std::collections::HashMap<String, String>> =
+            match tag_events {
+                Some(x) => serde_json::from_str(&x)?,
+                None => Default::default(),
+            };
+        let (code, source_map) =
+            self.group
+                .get_tmpl_converted_expr(path, ts_env, &component_types, &tag_events)?;
+        let path = self.group.get_tree(path)?.path.clone();
+        Ok(TmplConvertedExpr {
+            code,
+            source_map,
+            path,
+        })
     }
 }
 
@@ -218,6 +424,7 @@ impl TmplGroup {
 pub struct TmplConvertedExpr {
     code: String,
     source_map: sourcemap::SourceMap,
+    path: String,
 }
 
 #[wasm_bindgen]
@@ -259,6 +466,10 @@ impl TmplConvertedExpr {
             let Some(name) = token.get_name() else {
                 continue;
             };
+            // tokens may also come from imported or included templates
+            if token.get_source() != Some(self.path.as_str()) {
+                continue;
+            }
             let (src_start_line, src_start_col) = token.get_src();
             if src_start_line != line {
                 continue;
@@ -282,6 +493,38 @@ impl TmplConvertedExpr {
     }
 }
 
+/// Generated code with its source map.
+#[wasm_bindgen]
+pub struct CodeWithSourceMap {
+    code: String,
+    source_map: String,
+}
+
+impl CodeWithSourceMap {
+    fn new(code: String, source_map: sourcemap::SourceMap) -> Result<Self, JsError> {
+        let mut buf = vec![];
+        source_map.to_writer(&mut buf)?;
+        Ok(Self {
+            code,
+            source_map: String::from_utf8(buf)?,
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl CodeWithSourceMap {
+    #[wasm_bindgen(js_name = "code")]
+    pub fn code(&self) -> String {
+        self.code.clone()
+    }
+
+    /// The source map in JSON format.
+    #[wasm_bindgen(js_name = "sourceMap")]
+    pub fn source_map(&self) -> String {
+        self.source_map.clone()
+    }
+}
+
 #[wasm_bindgen]
 pub fn enable_console_log() {
     console_log::init_with_level(log::Level::Debug).unwrap();
@@ -7,7 +7,10 @@ extern crate log;
 extern crate lazy_static;
 
 mod binding_map;
+mod check;
+pub mod class_usage;
 mod group;
+pub mod lint;
 pub mod parse;
 pub mod stringify;
 pub use group::*;
@@ -18,3 +21,4 @@ mod escape;
 mod js_bindings;
 mod path;
 mod proc_gen;
+mod wxs;
@@ -0,0 +1,669 @@
+//! Semantic checks on templates
+//!
+//! The parser only reports syntax-level problems.
+//! The lint rules here check the parsed trees in a `TmplGroup` for likely mistakes.
+//!
+//! Rules can be disabled inside templates with comments:
+//!
+//! - `<!-- lint-disable-next-line rule-a rule-b -->` disables the rules on the next line;
+//! - `<!-- lint-disable rule-a rule-b -->` disables the rules until the end of the file
+//!   or until a `<!-- lint-enable rule-a rule-b -->` .
+//!
+//! If no rule ID is given, the comment applies to all rules.
+//! The rules are tracked separately, so `<!-- lint-enable rule-a -->` re-enables `rule-a`
+//! even if it is disabled together with other rules.
+//! Unknown rule IDs in these comments are reported by the `unknown-lint-rule` rule.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::group::{TmplError, TmplGroup};
+use crate::parse::{
+    expr::Expression,
+    tag::{Element, ElementKind, Node, Template, Value},
+    visit::{walk, Visit},
+    ParseErrorLevel, Position,
+};
+
+/// A lint rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LintRule {
+    /// A `wx:for` without `wx:key` .
+    WxForKey,
+    /// A `<wxs>` module which is never used.
+    UnusedScript,
+    /// An `<import>` which templates are never referenced by `<template is>` .
+    UnusedImport,
+    /// A `<template is>` which target cannot be found in this file or the imported files.
+    UnknownTemplate,
+    /// A `data:` attribute which name contains uppercase letters.
+    ///
+    /// The `data-` attributes are lowercased by the parser with an `AvoidUppercaseLetters` warning,
+    /// so this rule only checks the `data:` form.
+    DataUppercase,
+    /// A lint disable or enable comment which contains an unknown rule ID.
+    UnknownLintRule,
+}
+
+impl LintRule {
+    /// All rules in the order of their IDs.
+    pub const ALL: [Self; 6] = [
+        Self::WxForKey,
+        Self::UnusedScript,
+        Self::UnusedImport,
+        Self::UnknownTemplate,
+        Self::DataUppercase,
+        Self::UnknownLintRule,
+    ];
+
+    /// The ID of the rule, used in configurations and disable comments.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::WxForKey => "wx-for-key",
+            Self::UnusedScript => "unused-wxs",
+            Self::UnusedImport => "unused-import",
+            Self::UnknownTemplate => "unknown-template",
+            Self::DataUppercase => "data-uppercase",
+            Self::UnknownLintRule => "unknown-lint-rule",
+        }
+    }
+
+    /// Find a rule by its ID.
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.iter().find(|x| x.id() == id).cloned()
+    }
+
+    /// The level used when the rule is not configured.
+    pub fn default_level(&self) -> ParseErrorLevel {
+        match self {
+            Self::WxForKey => ParseErrorLevel::Warn,
+            Self::UnusedScript => ParseErrorLevel::Warn,
+            Self::UnusedImport => ParseErrorLevel::Warn,
+            Self::UnknownTemplate => ParseErrorLevel::Error,
+            Self::DataUppercase => ParseErrorLevel::Note,
+            Self::UnknownLintRule => ParseErrorLevel::Warn,
+        }
+    }
+}
+
+impl std::fmt::Display for LintRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// The levels of the lint rules.
+#[derive(Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<LintRule, Option<ParseErrorLevel>>,
+}
+
+impl LintConfig {
+    /// Create a config with all rules in their default levels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the level of a rule, or disable it with `None` .
+    pub fn set_level(&mut self, rule: LintRule, level: Option<ParseErrorLevel>) {
+        self.levels.insert(rule, level);
+    }
+
+    /// Get the level of a rule, or `None` if it is disabled.
+    pub fn level(&self, rule: LintRule) -> Option<ParseErrorLevel> {
+        match self.levels.get(&rule) {
+            Some(x) => x.clone(),
+            None => Some(rule.default_level()),
+        }
+    }
+
+    /// Parse a `rule-id=level` string and apply it.
+    ///
+    /// The level can be `off` , `note` , `warn` , `error` or `fatal` .
+    pub fn apply_str(&mut self, s: &str) -> Result<(), TmplError> {
+        let (id, level) = s.split_once('=').ok_or_else(|| TmplError {
+            message: format!(r#"illegal lint rule config "{}""#, s),
+        })?;
+        let rule = LintRule::from_id(id.trim()).ok_or_else(|| TmplError {
+            message: format!(r#"no lint rule "{}" found"#, id.trim()),
+        })?;
+        let level = match level.trim() {
+            "off" => None,
+            "note" => Some(ParseErrorLevel::Note),
+            "warn" => Some(ParseErrorLevel::Warn),
+            "error" => Some(ParseErrorLevel::Error),
+            "fatal" => Some(ParseErrorLevel::Fatal),
+            x => {
+                return Err(TmplError {
+                    message: format!(r#"illegal lint level "{}""#, x),
+                })
+            }
+        };
+        self.set_level(rule, level);
+        Ok(())
+    }
+}
+
+/// A problem found by a lint rule.
+#[derive(Clone)]
+pub struct LintMessage {
+    pub path: String,
+    pub rule: LintRule,
+    pub level: ParseErrorLevel,
+    pub location: Range<Position>,
+    pub message: String,
+}
+
+impl LintMessage {
+    /// Whether the message prevent a success compilation.
+    pub fn prevent_success(&self) -> bool {
+        self.level >= ParseErrorLevel::Error
+    }
+}
+
+impl std::fmt::Display for LintMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "template lint [{}] at {}:{}:{}-{}:{}: {}",
+            self.rule,
+            self.path,
+            self.location.start.line + 1,
+            self.location.start.utf16_col + 1,
+            self.location.end.line + 1,
+            self.location.end.utf16_col + 1,
+            self.message,
+        )
+    }
+}
+
+impl std::fmt::Debug for LintMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl TmplGroup {
+    /// Run lint rules on the specified template.
+    pub fn lint(&self, path: &str, config: &LintConfig) -> Result<Vec<LintMessage>, TmplError> {
+        let tree = self.get_tree(path)?;
+        let mut linter = Linter {
+            group: self,
+            tree,
+            config,
+            disabled: DisabledRules::collect(tree),
+            messages: vec![],
+        };
+        linter.run();
+        Ok(linter.messages)
+    }
+
+    /// Run lint rules on all templates, ordered by the template paths.
+    pub fn lint_all(&self, config: &LintConfig) -> Vec<LintMessage> {
+        self.list_template_trees()
+            .flat_map(|(p, _)| self.lint(p, config).unwrap_or_default())
+            .collect()
+    }
+}
+
+/// The ranges in which some rules are disabled.
+struct DisabledRules {
+    ranges: Vec<(LintRule, Range<Position>)>,
+    unknown_ids: Vec<(String, Range<Position>)>,
+}
+
+impl DisabledRules {
+    fn collect(tree: &Template) -> Self {
+        struct CommentCollector<'a> {
+            comments: Vec<(&'a str, Range<Position>)>,
+        }
+        impl<'a> CommentCollector<'a> {
+            fn collect_nodes(&mut self, nodes: &'a [Node]) {
+                for node in nodes {
+                    match node {
+                        Node::Comment(x) => {
+                            self.comments.push((&x.content, x.location.clone()));
+                        }
+                        Node::Element(elem) => {
+                            for child in elem.iter_children() {
+                                self.collect_nodes(std::slice::from_ref(child));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        let mut cc = CommentCollector { comments: vec![] };
+        cc.collect_nodes(&tree.content);
+        for sub in tree.globals.sub_templates.iter() {
+            cc.collect_nodes(&sub.content);
+        }
+        cc.comments.sort_by_key(|(_, loc)| loc.start);
+
+        let end_of_file = Position {
+            line: u32::MAX,
+            utf16_col: u32::MAX,
+        };
+        let mut ranges = vec![];
+        let mut unknown_ids = vec![];
+        let mut opened: HashMap<LintRule, Position> = HashMap::new();
+        for (content, location) in cc.comments {
+            let mut words = content.split_whitespace();
+            let directive = words.next().unwrap_or("");
+            if !matches!(
+                directive,
+                "lint-disable-next-line" | "lint-disable" | "lint-enable"
+            ) {
+                continue;
+            }
+            let ids: Vec<&str> = words.collect();
+            let rules: Vec<LintRule> = if ids.is_empty() {
+                LintRule::ALL.to_vec()
+            } else {
+                ids.iter().filter_map(|id| LintRule::from_id(id)).collect()
+            };
+            for id in ids.iter().filter(|id| LintRule::from_id(id).is_none()) {
+                unknown_ids.push((id.to_string(), location.clone()));
+            }
+            match directive {
+                "lint-disable-next-line" => {
+                    let line = location.end.line + 1;
+                    let range = Position { line, utf16_col: 0 }..Position {
+                        line,
+                        utf16_col: u32::MAX,
+                    };
+                    for rule in rules {
+                        ranges.push((rule, range.clone()));
+                    }
+                }
+                "lint-disable" => {
+                    for rule in rules {
+                        opened.entry(rule).or_insert(location.start);
+                    }
+                }
+                _ => {
+                    for rule in rules {
+                        if let Some(start) = opened.remove(&rule) {
+                            ranges.push((rule, start..location.end));
+                        }
+                    }
+                }
+            }
+        }
+        for (rule, start) in opened {
+            ranges.push((rule, start..end_of_file));
+        }
+        Self {
+            ranges,
+            unknown_ids,
+        }
+    }
+
+    fn is_disabled(&self, rule: LintRule, location: &Range<Position>) -> bool {
+        self.ranges.iter().any(|(r, range)| {
+            *r == rule && range.start <= location.start && location.start <= range.end
+        })
+    }
+}
+
+struct Linter<'a> {
+    group: &'a TmplGroup,
+    tree: &'a Template,
+    config: &'a LintConfig,
+    disabled: DisabledRules,
+    messages: Vec<LintMessage>,
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, rule: LintRule, location: Range<Position>, message: String) {
+        let Some(level) = self.config.level(rule) else {
+            return;
+        };
+        if self.disabled.is_disabled(rule, &location) {
+            return;
+        }
+        self.messages.push(LintMessage {
+            path: self.tree.path.clone(),
+            rule,
+            level,
+            location,
+            message,
+        });
+    }
+
+    fn run(&mut self) {
+        // unknown rule IDs in disable comments
+        for (id, location) in std::mem::take(&mut self.disabled.unknown_ids) {
+            self.report(
+                LintRule::UnknownLintRule,
+                location,
+                format!(r#"no lint rule "{}" found"#, id),
+            );
+        }
+
+        // collect usages in the tree
+        let mut collector = UsageCollector {
+            script_count: self.tree.globals.scripts.len(),
+            used_scripts: HashSet::new(),
+            template_refs: vec![],
+            has_dynamic_template_ref: false,
+            findings: vec![],
+        };
+        collector.visit_template(self.tree);
+        for (rule, location, message) in std::mem::take(&mut collector.findings) {
+            self.report(rule, location, message);
+        }
+
+        // unused scripts
+        for (index, script) in self.tree.globals.scripts.iter().enumerate() {
+            if !collector.used_scripts.contains(&index) {
+                self.report(
+                    LintRule::UnusedScript,
+                    script.module_location(),
+                    format!(
+                        r#"wxs module "{}" is never used"#,
+                        script.module_name().name
+                    ),
+                );
+            }
+        }
+
+        // template refs and imports
+        let imports = &self.tree.globals.imports;
+        let import_unresolved = imports.iter().any(|import| {
+            let p = crate::path::resolve(&self.tree.path, &import.src.name);
+            self.group.get_tree(&p).is_err()
+        });
+        let mut used_imports = vec![false; imports.len()];
+        for (name, location) in std::mem::take(&mut collector.template_refs) {
+            match self.group.find_sub_template(self.tree, &name) {
+                Some((_, _, Some(index))) => used_imports[index] = true,
+                Some((_, _, None)) => {}
+                None => {
+                    if !import_unresolved {
+                        self.report(
+                            LintRule::UnknownTemplate,
+                            location,
+                            format!(r#"template "{}" is not defined or imported"#, name),
+                        );
+                    }
+                }
+            }
+        }
+        if !collector.has_dynamic_template_ref {
+            for (i, import) in imports.iter().enumerate() {
+                let p = crate::path::resolve(&self.tree.path, &import.src.name);
+                if self.group.get_tree(&p).is_ok() && !used_imports[i] {
+                    self.report(
+                        LintRule::UnusedImport,
+                        import.src_location.clone(),
+                        format!(
+                            r#"templates imported from "{}" are never used"#,
+                            import.src.name
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+struct UsageCollector {
+    script_count: usize,
+    used_scripts: HashSet<usize>,
+    template_refs: Vec<(String, Range<Position>)>,
+    has_dynamic_template_ref: bool,
+    findings: Vec<(LintRule, Range<Position>, String)>,
+}
+
+impl Visit for UsageCollector {
+    fn visit_element(&mut self, element: &Element) {
+        match &element.kind {
+            ElementKind::For { list, key, .. } if key.1.name.is_empty() => {
+                self.findings.push((
+                    LintRule::WxForKey,
+                    list.0.clone(),
+                    "`wx:for` should be used with `wx:key`".to_string(),
+                ));
+            }
+            ElementKind::TemplateRef { target, .. } => match &target.1 {
+                Value::Static { value, location } => {
+                    self.template_refs
+                        .push((value.to_string(), location.clone()));
+                }
+                Value::Dynamic { .. } => {
+                    self.has_dynamic_template_ref = true;
+                }
+            },
+            ElementKind::Normal { common, .. } | ElementKind::Slot { common, .. } => {
+                for attr in common.data.iter() {
+                    if attr.prefix_location.is_some()
+                        && attr.name.name.chars().any(|c| c.is_ascii_uppercase())
+                    {
+                        self.findings.push((
+                            LintRule::DataUppercase,
+                            attr.name.location.clone(),
+                            format!(
+                                r#"data attribute "{}" contains uppercase letters"#,
+                                attr.name.name
+                            ),
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+        walk::walk_element(self, element);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        if let Expression::ScopeRef { index, .. } = expr {
+            if *index < self.script_count {
+                self.used_scripts.insert(*index);
+            }
+        }
+        walk::walk_expression(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lint(sources: &[(&str, &str)], config: &LintConfig) -> Vec<(&'static str, String)> {
+        let mut group = TmplGroup::new();
+        for (path, src) in sources {
+            group.add_tmpl(path, src);
+        }
+        group
+            .lint_all(config)
+            .into_iter()
+            .map(|x| {
+                (
+                    x.rule.id(),
+                    format!("{}:{}", x.path, x.location.start.utf16_col),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wx_for_key() {
+        let config = LintConfig::new();
+        assert_eq!(
+            lint(&[("a", r#"<view wx:for="{{ list }}" />"#)], &config),
+            [("wx-for-key", "a:6".to_string())],
+        );
+        assert_eq!(
+            lint(
+                &[("a", r#"<view wx:for="{{ list }}" wx:key="id" />"#)],
+                &config
+            ),
+            [],
+        );
+    }
+
+    #[test]
+    fn unused_script() {
+        let config = LintConfig::new();
+        assert_eq!(
+            lint(
+                &[(
+                    "a",
+                    r#"<wxs module="m1" src="/m" /><wxs module="m2" src="/m" />{{ m1.a }}"#
+                )],
+                &config
+            ),
+            [("unused-wxs", "a:33".to_string())],
+        );
+    }
+
+    #[test]
+    fn template_refs() {
+        let config = LintConfig::new();
+        let lib = ("lib", r#"<template name="a" />"#);
+        assert_eq!(
+            lint(
+                &[lib, ("a", r#"<import src="lib" /><template is="a" />"#)],
+                &config
+            ),
+            [],
+        );
+        assert_eq!(
+            lint(
+                &[lib, ("a", r#"<import src="lib" /><template is="b" />"#)],
+                &config
+            ),
+            [
+                ("unknown-template", "a:34".to_string()),
+                ("unused-import", "a:8".to_string()),
+            ],
+        );
+        assert_eq!(
+            lint(
+                &[
+                    lib,
+                    ("a", r#"<import src="lib" /><template is="{{ b }}" />"#)
+                ],
+                &config
+            ),
+            [],
+        );
+        assert_eq!(
+            lint(
+                &[(
+                    "a",
+                    r#"<template name="b" /><template is="b" /><template is="c" />"#
+                )],
+                &config
+            ),
+            [("unknown-template", "a:54".to_string())],
+        );
+        // the later import takes precedence, so the earlier one is unused
+        assert_eq!(
+            lint(
+                &[
+                    lib,
+                    ("lib2", r#"<template name="a" />"#),
+                    (
+                        "a",
+                        r#"<import src="lib" /><import src="lib2" /><template is="a" />"#
+                    )
+                ],
+                &config
+            ),
+            [("unused-import", "a:8".to_string())],
+        );
+    }
+
+    #[test]
+    fn data_uppercase() {
+        let config = LintConfig::new();
+        assert_eq!(
+            lint(&[("a", r#"<view data:aB="1" data:c="2" />"#)], &config),
+            [("data-uppercase", "a:11".to_string())],
+        );
+    }
+
+    #[test]
+    fn config_levels() {
+        let mut config = LintConfig::new();
+        config.apply_str("wx-for-key = off").unwrap();
+        assert!(config.apply_str("wx-for-key=unknown").is_err());
+        assert!(config.apply_str("unknown=off").is_err());
+        assert_eq!(
+            lint(&[("a", r#"<view wx:for="{{ list }}" />"#)], &config),
+            [],
+        );
+        config.apply_str("wx-for-key=error").unwrap();
+        let mut group = TmplGroup::new();
+        group.add_tmpl("a", r#"<view wx:for="{{ list }}" />"#);
+        let messages = group.lint("a", &config).unwrap();
+        assert!(messages[0].prevent_success());
+    }
+
+    #[test]
+    fn disable_comments() {
+        let config = LintConfig::new();
+        const SRC: &str = r#"
+<!-- lint-disable-next-line wx-for-key -->
+<view wx:for="{{ list }}" />
+<view wx:for="{{ list }}" />
+<!-- lint-disable -->
+<view wx:for="{{ list }}" />
+<!-- lint-enable -->
+<view wx:for="{{ list }}" />
+<!-- lint-disable-next-line unused-wxs -->
+<view wx:for="{{ list }}" />
+"#;
+        let mut group = TmplGroup::new();
+        group.add_tmpl("a", SRC);
+        let lines: Vec<_> = group
+            .lint("a", &config)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.location.start.line)
+            .collect();
+        assert_eq!(lines, [3, 7, 9]);
+    }
+
+    #[test]
+    fn disable_comments_unknown_rules() {
+        let config = LintConfig::new();
+        const SRC: &str = r#"<!-- lint-disable wx-for-kye -->
+<view wx:for="{{ list }}" />"#;
+        assert_eq!(
+            lint(&[("a", SRC)], &config),
+            [
+                ("unknown-lint-rule", "a:0".to_string()),
+                ("wx-for-key", "a:6".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn disable_comments_per_rule() {
+        let config = LintConfig::new();
+        const SRC: &str = r#"
+<!-- lint-disable wx-for-key unknown-template -->
+<view wx:for="{{ list }}" /><template is="a" />
+<!-- lint-enable wx-for-key -->
+<view wx:for="{{ list }}" /><template is="a" />
+<!-- lint-disable -->
+<view wx:for="{{ list }}" />
+<!-- lint-enable wx-for-key -->
+<view wx:for="{{ list }}" /><view data:aB="1" />
+"#;
+        let mut group = TmplGroup::new();
+        group.add_tmpl("a", SRC);
+        let messages: Vec<_> = group
+            .lint("a", &config)
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.rule.id(), x.location.start.line))
+            .collect();
+        assert_eq!(messages, [("wx-for-key", 4), ("wx-for-key", 8)]);
+    }
+}
@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate log;
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, SubCommand};
 use glass_easel_template_compiler::*;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -11,18 +11,22 @@ struct CmdArgs {
     interactive: bool,
     input: Option<PathBuf>,
     output: Option<PathBuf>,
+    source_map: Option<PathBuf>,
     target: TargetType,
+    lint: Option<Vec<String>>,
 }
 
 #[derive(Debug)]
 enum TargetType {
     WxGenObject,
     Wxml,
+    AstJson,
 }
 
 fn parse_cmd() -> CmdArgs {
     let matches = App::new("The Template Compiler for glass-easel")
         .author("wechat-miniprogram")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("interactive")
                 .short("i")
@@ -42,22 +46,68 @@ fn parse_cmd() -> CmdArgs {
                 .short("t")
                 .long("target")
                 .takes_value(true)
-                .possible_values(&["gen-object", "wxml"])
+                .possible_values(&["gen-object", "wxml", "ast-json"])
                 .help("Compiling target"),
         )
+        .arg(
+            Arg::with_name("source-map")
+                .short("s")
+                .long("source-map")
+                .value_name("FILE")
+                .help("Writes the source map of the gen-object target to the file")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("DIRECTORY")
                 .help("Sets the root directory of WXML files")
                 .required_unless("interactive")
                 .index(1),
         )
+        .subcommand(
+            SubCommand::with_name("lint")
+                .about("Check WXML files with lint rules")
+                .arg(
+                    Arg::with_name("rule")
+                        .short("r")
+                        .long("rule")
+                        .value_name("RULE=LEVEL")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Sets the level of a rule (off, note, warn, error or fatal)"),
+                )
+                .arg(
+                    Arg::with_name("DIRECTORY")
+                        .help("Sets the root directory of WXML files")
+                        .required(true)
+                        .index(1),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("lint") {
+        return CmdArgs {
+            interactive: false,
+            input: matches.value_of("DIRECTORY").map(|x| x.into()),
+            output: None,
+            source_map: None,
+            target: TargetType::WxGenObject,
+            lint: Some(
+                matches
+                    .values_of("rule")
+                    .map(|x| x.map(|x| x.to_string()).collect())
+                    .unwrap_or_default(),
+            ),
+        };
+    }
+
     let interactive = matches.is_present("interactive");
     let output = matches.value_of("output-single-file").map(|x| x.into());
+    let source_map = matches.value_of("source-map").map(|x| x.into());
     let target = match matches.value_of("target").unwrap_or("gen-object") {
         "gen-object" => TargetType::WxGenObject,
         "wxml" => TargetType::Wxml,
+        "ast-json" => TargetType::AstJson,
         _ => unreachable!(),
     };
     let input = matches.value_of("DIRECTORY").map(|x| x.into());
@@ -66,7 +116,9 @@ fn parse_cmd() -> CmdArgs {
         interactive,
         input,
         output,
+        source_map,
         target,
+        lint: None,
     }
 }
 
@@ -90,6 +142,38 @@ fn load_wxml_files(group: &mut TmplGroup, dir: &Path, wxml_path: &mut Vec<String
                             wxml_path.push(entry.file_name().to_str().unwrap().into());
                             size += load_wxml_files(group, &path, wxml_path);
                             wxml_path.pop();
+                        } else if path
+                            .extension()
+                            .map(|x| x.to_str().unwrap_or(""))
+                            .unwrap_or("")
+                            == "wxs"
+                        {
+                            match fs::read_to_string(&path) {
+                                Err(_) => {
+                                    warn!("Read wxs failed: {}", path.to_str().unwrap_or(""));
+                                }
+                                Ok(content) => {
+                                    trace!("Found wxs file: {}", path.to_str().unwrap_or(""));
+                                    wxml_path.push(
+                                        entry
+                                            .path()
+                                            .file_stem()
+                                            .unwrap()
+                                            .to_str()
+                                            .unwrap()
+                                            .to_string(),
+                                    );
+                                    for err in group.add_script(&wxml_path.join("/"), &content) {
+                                        if err.prevent_success() {
+                                            error!("{}", err);
+                                        } else {
+                                            warn!("{}", err);
+                                        }
+                                    }
+                                    wxml_path.pop();
+                                    size += fsize;
+                                }
+                            }
                         } else if path
                             .extension()
                             .map(|x| x.to_str().unwrap_or(""))
@@ -131,9 +215,36 @@ fn load_wxml_files(group: &mut TmplGroup, dir: &Path, wxml_path: &mut Vec<String
     size
 }
 
+fn lint(dir: &Path, rules: &[String]) -> bool {
+    let mut config = lint::LintConfig::new();
+    for rule in rules {
+        if let Err(err) = config.apply_str(rule) {
+            error!("{}", err);
+            return false;
+        }
+    }
+    let mut group = TmplGroup::new();
+    load_wxml_files(&mut group, dir, &mut vec![]);
+    let mut success = true;
+    for msg in group.lint_all(&config) {
+        if msg.prevent_success() {
+            success = false;
+        }
+        println!("{}", msg);
+    }
+    success
+}
+
 fn main() {
     env_logger::init();
     let args = parse_cmd();
+    if let Some(rules) = args.lint {
+        let dir = args.input.unwrap_or(std::env::current_dir().unwrap());
+        if !lint(&dir, &rules) {
+            std::process::exit(1);
+        }
+        return;
+    }
     let mut group = TmplGroup::new();
     let size = if args.interactive {
         use std::io::Read;
@@ -156,7 +267,15 @@ fn main() {
     };
     let s = match args.target {
         TargetType::WxGenObject => {
-            let s = group.get_wx_gen_object_groups().unwrap();
+            let s = if let Some(source_map_path) = args.source_map.as_ref() {
+                let (s, source_map) = group.get_wx_gen_object_groups_with_source_map().unwrap();
+                let mut buf = vec![];
+                source_map.to_writer(&mut buf).unwrap();
+                fs::write(source_map_path, buf).unwrap();
+                s
+            } else {
+                group.get_wx_gen_object_groups().unwrap()
+            };
             trace!(
                 "Generated GenObject. {} bytes read. {} bytes generated.",
                 size,
@@ -173,6 +292,15 @@ fn main() {
             );
             s
         }
+        TargetType::AstJson => {
+            let s = group.get_ast_json_groups().unwrap();
+            trace!(
+                "Generated AST JSON. {} bytes read. {} bytes generated.",
+                size,
+                s.len()
+            );
+            s
+        }
     };
     if let Some(output) = args.output {
         fs::write(output, s).unwrap();
@@ -210,4 +338,21 @@ mod tests {
 
         fs::remove_dir_all(test_dir).unwrap();
     }
+
+    #[test]
+    fn it_lints_wxml_files() {
+        let test_dir = std::env::temp_dir().join("glass_easel_template_compiler_tests_lint");
+
+        if test_dir.exists() {
+            fs::remove_dir_all(&test_dir).unwrap();
+        }
+        fs::create_dir(&test_dir).unwrap();
+        fs::write(test_dir.join("index.wxml"), r#"<template is="a" />"#).unwrap();
+
+        assert!(!lint(&test_dir, &[]));
+        assert!(lint(&test_dir, &["unknown-template=warn".to_string()]));
+        assert!(!lint(&test_dir, &["unknown-rule=warn".to_string()]));
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
 }
@@ -1,12 +1,13 @@
 use std::ops::Range;
 
 use compact_str::CompactString;
+use serde::Serialize;
 
 use crate::binding_map::{BindingMapCollector, BindingMapKeys};
 
 use super::{ParseErrorKind, ParseState, Position, TemplateStructure};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub enum Expression {
     /// A reference to a data scopes.
@@ -67,6 +68,10 @@ pub enum Expression {
         fields: Vec<ArrayFieldKind>,
         bracket_location: (Range<Position>, Range<Position>),
     },
+    LitTemplateStr {
+        fields: Vec<TemplateStrFieldKind>,
+        backtick_location: (Range<Position>, Range<Position>),
+    },
 
     StaticMember {
         obj: Box<Expression>,
@@ -84,6 +89,34 @@ pub enum Expression {
         args: Vec<Expression>,
         paren_location: (Range<Position>, Range<Position>),
     },
+    OptionalStaticMember {
+        obj: Box<Expression>,
+        field_name: CompactString,
+        dot_location: Range<Position>,
+        field_location: Range<Position>,
+    },
+    OptionalDynamicMember {
+        obj: Box<Expression>,
+        field_name: Box<Expression>,
+        dot_location: Range<Position>,
+        bracket_location: (Range<Position>, Range<Position>),
+    },
+    OptionalFuncCall {
+        func: Box<Expression>,
+        args: Vec<Expression>,
+        dot_location: Range<Position>,
+        paren_location: (Range<Position>, Range<Position>),
+    },
+    ArrowFunc {
+        param: Option<(CompactString, Range<Position>)>,
+        paren_location: Option<(Range<Position>, Range<Position>)>,
+        arrow_location: Range<Position>,
+        body: Box<Expression>,
+    },
+    ArrowFuncParam {
+        name: CompactString,
+        location: Range<Position>,
+    },
 
     Reverse {
         value: Box<Expression>,
@@ -239,7 +272,7 @@ pub enum Expression {
     },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ObjectFieldKind {
     Named {
         name: CompactString,
@@ -253,7 +286,7 @@ pub enum ObjectFieldKind {
     },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ArrayFieldKind {
     Normal {
         value: Expression,
@@ -265,6 +298,18 @@ pub enum ArrayFieldKind {
     EmptySlot,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub enum TemplateStrFieldKind {
+    Str {
+        value: CompactString,
+        location: Range<Position>,
+    },
+    Interpolation {
+        value: Expression,
+        brace_location: (Range<Position>, Range<Position>),
+    },
+}
+
 impl TemplateStructure for Expression {
     fn location(&self) -> Range<Position> {
         self.location_start()..self.location_end()
@@ -285,9 +330,26 @@ impl TemplateStructure for Expression {
             Self::LitArr {
                 bracket_location, ..
             } => bracket_location.0.start,
+            Self::LitTemplateStr {
+                backtick_location, ..
+            } => backtick_location.0.start,
             Self::StaticMember { obj, .. } => obj.location_start(),
             Self::DynamicMember { obj, .. } => obj.location_start(),
             Self::FuncCall { func, .. } => func.location_start(),
+            Self::OptionalStaticMember { obj, .. } => obj.location_start(),
+            Self::OptionalDynamicMember { obj, .. } => obj.location_start(),
+            Self::OptionalFuncCall { func, .. } => func.location_start(),
+            Self::ArrowFunc {
+                param,
+                paren_location,
+                arrow_location,
+                ..
+            } => match (paren_location, param) {
+                (Some(paren_location), _) => paren_location.0.start,
+                (None, Some((_, location))) => location.start,
+                (None, None) => arrow_location.start,
+            },
+            Self::ArrowFuncParam { location, .. } => location.start,
             Self::Reverse { location, .. } => location.start,
             Self::BitReverse { location, .. } => location.start,
             Self::Positive { location, .. } => location.start,
@@ -336,9 +398,17 @@ impl TemplateStructure for Expression {
             Self::LitArr {
                 bracket_location, ..
             } => bracket_location.1.end,
+            Self::LitTemplateStr {
+                backtick_location, ..
+            } => backtick_location.1.end,
             Self::StaticMember { obj, .. } => obj.location_end(),
             Self::DynamicMember { obj, .. } => obj.location_end(),
             Self::FuncCall { func, .. } => func.location_end(),
+            Self::OptionalStaticMember { obj, .. } => obj.location_end(),
+            Self::OptionalDynamicMember { obj, .. } => obj.location_end(),
+            Self::OptionalFuncCall { func, .. } => func.location_end(),
+            Self::ArrowFunc { body, .. } => body.location_end(),
+            Self::ArrowFuncParam { location, .. } => location.end,
             Self::Reverse { location, .. } => location.end,
             Self::BitReverse { location, .. } => location.end,
             Self::Positive { location, .. } => location.end,
@@ -381,6 +451,9 @@ impl Expression {
         ps.parse_on_auto_whitespace(
             |ps| ps.skip_whitespace_with_js_comments(),
             |ps| {
+                if let Some(head) = Self::try_parse_arrow_func_head(ps) {
+                    return Self::parse_arrow_func_body(ps, head);
+                }
                 let mut is_object_inner = false;
                 ps.try_parse(|ps| -> Option<()> {
                     // try parse as an object
@@ -417,6 +490,42 @@ impl Expression {
         )
     }
 
+    fn try_parse_arrow_func_head(ps: &mut ParseState) -> Option<ArrowFuncHead> {
+        ps.try_parse(|ps| {
+            let (param, paren_location) = if let Some(paren_start) = ps.consume_str("(") {
+                let param = Self::try_parse_field_name(ps);
+                let paren_end = ps.consume_str(")")?;
+                (param, Some((paren_start, paren_end)))
+            } else {
+                (Some(Self::try_parse_field_name(ps)?), None)
+            };
+            let arrow_location = ps.consume_str("=>")?;
+            Some(ArrowFuncHead {
+                param,
+                paren_location,
+                arrow_location,
+            })
+        })
+    }
+
+    fn parse_arrow_func_body(ps: &mut ParseState, head: ArrowFuncHead) -> Option<Box<Self>> {
+        if ps.peek::<0>()? == '{' {
+            // block body is not supported (use `=> ({ ... })` for object results)
+            ps.add_warning_at_current_position(ParseErrorKind::UnsupportedSyntax);
+            return None;
+        }
+        let mut body = Self::parse_cond(ps)?;
+        if let Some((name, _)) = head.param.as_ref() {
+            body.convert_arrow_func_param(name);
+        }
+        Some(Box::new(Self::ArrowFunc {
+            param: head.param,
+            paren_location: head.paren_location,
+            arrow_location: head.arrow_location,
+            body,
+        }))
+    }
+
     fn try_parse_field_name(ps: &mut ParseState) -> Option<(CompactString, Range<Position>)> {
         let peek = ps.peek::<0>()?;
         if is_ident_start_char(peek) {
@@ -665,62 +774,7 @@ impl Expression {
                     break;
                 };
                 if next == '\\' {
-                    let next = ps.next()?;
-                    let ch = match next {
-                        'r' => '\r',
-                        'n' => '\n',
-                        't' => '\t',
-                        'b' => '\x08',
-                        'f' => '\x0C',
-                        'v' => '\x0B',
-                        '0' => '\0',
-                        'x' | 'u' => {
-                            let range = if next == 'x' { 0..2 } else { 0..4 };
-                            let pos = ps.position();
-                            let ch = ps.try_parse(|ps| {
-                                let mut v = 0;
-                                for _ in range {
-                                    let next = ps.next()?;
-                                    let x = match next {
-                                        '0' => 0,
-                                        '1' => 1,
-                                        '2' => 2,
-                                        '3' => 3,
-                                        '4' => 4,
-                                        '5' => 5,
-                                        '6' => 6,
-                                        '7' => 7,
-                                        '8' => 8,
-                                        '9' => 9,
-                                        'a' | 'A' => 10,
-                                        'b' | 'B' => 11,
-                                        'c' | 'C' => 12,
-                                        'd' | 'D' => 13,
-                                        'e' | 'E' => 14,
-                                        'f' | 'F' => 15,
-                                        _ => {
-                                            ps.add_warning(
-                                                ParseErrorKind::IllegalEscapeSequence,
-                                                pos..ps.position(),
-                                            );
-                                            return None;
-                                        }
-                                    };
-                                    v = v * 16 + x;
-                                }
-                                let Some(ch) = char::from_u32(v) else {
-                                    ps.add_warning(
-                                        ParseErrorKind::IllegalEscapeSequence,
-                                        pos..ps.position(),
-                                    );
-                                    return None;
-                                };
-                                Some(ch)
-                            });
-                            ch.unwrap_or(' ')
-                        }
-                        x => x,
-                    };
+                    let ch = Self::parse_escape_sequence(ps)?;
                     ret.push(ch);
                 } else {
                     ret.push(next);
@@ -733,6 +787,126 @@ impl Expression {
         })
     }
 
+    fn parse_lit_template_str(ps: &mut ParseState) -> Option<Box<Self>> {
+        let Some(backtick_start) = ps.consume_str("`") else {
+            ps.add_warning_at_current_position(ParseErrorKind::UnexpectedExpressionCharacter);
+            return None;
+        };
+        ps.parse_off_auto_whitespace(|ps| {
+            let mut fields = vec![];
+            let mut s = CompactString::new_inline("");
+            let mut s_start = ps.position();
+            loop {
+                let s_end = ps.position();
+                if let Some(backtick_end) = ps.consume_str("`") {
+                    if !s.is_empty() {
+                        fields.push(TemplateStrFieldKind::Str {
+                            value: s,
+                            location: s_start..s_end,
+                        });
+                    }
+                    return Some(Box::new(Expression::LitTemplateStr {
+                        fields,
+                        backtick_location: (backtick_start, backtick_end),
+                    }));
+                }
+                if let Some(brace_start) = ps.consume_str("${") {
+                    if !s.is_empty() {
+                        fields.push(TemplateStrFieldKind::Str {
+                            value: std::mem::replace(&mut s, CompactString::new_inline("")),
+                            location: s_start..s_end,
+                        });
+                    }
+                    let (value, brace_end) = ps.parse_on_auto_whitespace(
+                        |ps| ps.skip_whitespace_with_js_comments(),
+                        |ps| {
+                            let value = Self::parse_cond(ps)?;
+                            let Some(brace_end) = ps.consume_str("}") else {
+                                if !ps.ended() {
+                                    ps.add_warning_at_current_position(
+                                        ParseErrorKind::UnmatchedBracket,
+                                    );
+                                }
+                                return None;
+                            };
+                            Some((value, brace_end))
+                        },
+                    )?;
+                    fields.push(TemplateStrFieldKind::Interpolation {
+                        value: *value,
+                        brace_location: (brace_start, brace_end),
+                    });
+                    s_start = ps.position();
+                    continue;
+                }
+                let next = ps.next()?;
+                if next == '\\' {
+                    let ch = Self::parse_escape_sequence(ps)?;
+                    s.push(ch);
+                } else {
+                    s.push(next);
+                }
+            }
+        })
+    }
+
+    fn parse_escape_sequence(ps: &mut ParseState) -> Option<char> {
+        let next = ps.next()?;
+        let ch = match next {
+            'r' => '\r',
+            'n' => '\n',
+            't' => '\t',
+            'b' => '\x08',
+            'f' => '\x0C',
+            'v' => '\x0B',
+            '0' => '\0',
+            'x' | 'u' => {
+                let range = if next == 'x' { 0..2 } else { 0..4 };
+                let pos = ps.position();
+                let ch = ps.try_parse(|ps| {
+                    let mut v = 0;
+                    for _ in range {
+                        let next = ps.next()?;
+                        let x = match next {
+                            '0' => 0,
+                            '1' => 1,
+                            '2' => 2,
+                            '3' => 3,
+                            '4' => 4,
+                            '5' => 5,
+                            '6' => 6,
+                            '7' => 7,
+                            '8' => 8,
+                            '9' => 9,
+                            'a' | 'A' => 10,
+                            'b' | 'B' => 11,
+                            'c' | 'C' => 12,
+                            'd' | 'D' => 13,
+                            'e' | 'E' => 14,
+                            'f' | 'F' => 15,
+                            _ => {
+                                ps.add_warning(
+                                    ParseErrorKind::IllegalEscapeSequence,
+                                    pos..ps.position(),
+                                );
+                                return None;
+                            }
+                        };
+                        v = v * 16 + x;
+                    }
+                    let Some(ch) = char::from_u32(v) else {
+                        ps.add_warning(ParseErrorKind::IllegalEscapeSequence, pos..ps.position());
+                        return None;
+                    };
+                    Some(ch)
+                });
+                ch.unwrap_or(' ')
+            }
+            x => x,
+        };
+        Some(ch)
+    }
+
     fn parse_number(ps: &mut ParseState) -> Option<Box<Self>> {
         let peek = ps.peek::<0>()?;
         if !('0'..='9').contains(&peek) && peek != '.' {
@@ -922,6 +1096,9 @@ impl Expression {
         if ch == '"' || ch == '\'' {
             return Self::parse_lit_str(ps);
         }
+        if ch == '`' {
+            return Self::parse_lit_template_str(ps);
+        }
         if ('0'..='9').contains(&ch) || ch == '.' {
             return Self::parse_number(ps);
         }
@@ -976,23 +1153,7 @@ impl Expression {
                 continue;
             }
             if let Some(start) = ParseOperator::func_call(ps) {
-                let mut args = vec![];
-                loop {
-                    if ps.peek::<0>()? == ')' {
-                        break;
-                    }
-                    let value = *Self::parse_cond(ps)?;
-                    args.push(value);
-                    if ps.consume_str(",").is_none() {
-                        break;
-                    }
-                }
-                let Some(end) = ParseOperator::func_call_end(ps) else {
-                    if !ps.ended() {
-                        ps.add_warning_at_current_position(ParseErrorKind::UnmatchedParenthesis);
-                    }
-                    return None;
-                };
+                let (args, end) = Self::parse_func_call_args(ps)?;
                 obj = Box::new(Self::FuncCall {
                     func: obj,
                     args,
@@ -1000,11 +1161,69 @@ impl Expression {
                 });
                 continue;
             }
+            if let Some(dot_location) = ParseOperator::optional_chain(ps) {
+                if let Some(start) = ParseOperator::dynamic_member(ps) {
+                    let field_name = Self::parse_cond(ps)?;
+                    let Some(end) = ParseOperator::dynamic_member_end(ps) else {
+                        if !ps.ended() {
+                            ps.add_warning_at_current_position(ParseErrorKind::UnmatchedBracket);
+                        }
+                        return None;
+                    };
+                    obj = Box::new(Self::OptionalDynamicMember {
+                        obj,
+                        field_name,
+                        dot_location,
+                        bracket_location: (start, end),
+                    });
+                } else if let Some(start) = ParseOperator::func_call(ps) {
+                    let (args, end) = Self::parse_func_call_args(ps)?;
+                    obj = Box::new(Self::OptionalFuncCall {
+                        func: obj,
+                        args,
+                        dot_location,
+                        paren_location: (start, end),
+                    });
+                } else {
+                    let Some((field_name, field_location)) = Self::try_parse_field_name(ps) else {
+                        ps.add_warning_at_current_position(ParseErrorKind::InvalidIdentifier);
+                        return None;
+                    };
+                    obj = Box::new(Self::OptionalStaticMember {
+                        obj,
+                        field_name,
+                        dot_location,
+                        field_location,
+                    });
+                }
+                continue;
+            }
             break;
         }
         Some(obj)
     }
 
+    fn parse_func_call_args(ps: &mut ParseState) -> Option<(Vec<Self>, Range<Position>)> {
+        let mut args = vec![];
+        loop {
+            if ps.peek::<0>()? == ')' {
+                break;
+            }
+            let value = *Self::parse_cond(ps)?;
+            args.push(value);
+            if ps.consume_str(",").is_none() {
+                break;
+            }
+        }
+        let Some(end) = ParseOperator::func_call_end(ps) else {
+            if !ps.ended() {
+                ps.add_warning_at_current_position(ParseErrorKind::UnmatchedParenthesis);
+            }
+            return None;
+        };
+        Some((args, end))
+    }
+
     fn parse_reverse(ps: &mut ParseState) -> Option<Box<Self>> {
         if let Some(location) = ParseOperator::reverse(ps) {
             let value = Self::parse_reverse(ps)?;
@@ -1086,6 +1305,12 @@ parse_left_to_right!(parse_bit_or, parse_bit_xor, bit_or => BitOr);
 parse_left_to_right!(parse_logic_and, parse_bit_or, logic_and => LogicAnd);
 parse_left_to_right!(parse_logic_or, parse_logic_and, logic_or => LogicOr, nullish_coalescing => NullishCoalescing);
 
+struct ArrowFuncHead {
+    param: Option<(CompactString, Range<Position>)>,
+    paren_location: Option<(Range<Position>, Range<Position>)>,
+    arrow_location: Range<Position>,
+}
+
 struct ParseOperator();
 
 macro_rules! define_operator {
@@ -1110,7 +1335,6 @@ define_operator!(dynamic_member, "[", []);
 define_operator!(dynamic_member_end, "]", []);
 define_operator!(func_call, "(", []);
 define_operator!(func_call_end, ")", []);
-// `?.` is not supported
 // `new` is treated as an identifier
 
 // `++` `--` are not allowed
@@ -1175,6 +1399,13 @@ impl ParseOperator {
     }
 }
 
+impl ParseOperator {
+    fn optional_chain(ps: &mut ParseState) -> Option<Range<Position>> {
+        // `?.1` is not an optional chain (it is `?` and `.1` )
+        ps.consume_str_except_followed_char("?.", |ch| ch.is_ascii_digit())
+    }
+}
+
 // `=` `+=` `-=` `**=` `*=` `/=` `%=` `<<=` `>>=` `>>>=` `&=` `^=` `|=` `&&=` `||=` `??=` are not allowed
 
 fn is_ident_char(ch: char) -> bool {
@@ -1270,6 +1501,104 @@ impl Expression {
         }
     }
 
+    /// Visit the string literals that are analyzed in `possible_string_values` .
+    pub(crate) fn for_each_string_literal_mut(&mut self, f: &mut impl FnMut(&mut CompactString)) {
+        match self {
+            Self::LitStr { value, .. } => f(value),
+            Self::ToStringWithoutUndefined { value, .. } => value.for_each_string_literal_mut(f),
+            Self::Plus { left, right, .. }
+            | Self::LogicOr { left, right, .. }
+            | Self::NullishCoalescing { left, right, .. } => {
+                left.for_each_string_literal_mut(f);
+                right.for_each_string_literal_mut(f);
+            }
+            Self::Cond {
+                true_br, false_br, ..
+            } => {
+                true_br.for_each_string_literal_mut(f);
+                false_br.for_each_string_literal_mut(f);
+            }
+            Self::LogicAnd { right, .. } => right.for_each_string_literal_mut(f),
+            Self::LitTemplateStr { fields, .. } => {
+                for field in fields {
+                    match field {
+                        TemplateStrFieldKind::Str { value, .. } => f(value),
+                        TemplateStrFieldKind::Interpolation { value, .. } => {
+                            value.for_each_string_literal_mut(f)
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Get all possible string values of the expression.
+    ///
+    /// Only literals, string concatenations and conditional branches are analyzed.
+    /// Returns `None` if the value cannot be determined statically, e.g. `{{ a }}` or `{{ f(a) }}` .
+    pub fn possible_string_values(&self) -> Option<Vec<String>> {
+        const LIMIT: usize = 64;
+        let union = |a: &Expression, b: &Expression| -> Option<Vec<String>> {
+            let mut ret = a.possible_string_values()?;
+            ret.extend(b.possible_string_values()?);
+            Some(ret)
+        };
+        let concat = |a: Vec<String>, b: Vec<String>| -> Option<Vec<String>> {
+            if a.len() * b.len() > LIMIT {
+                return None;
+            }
+            Some(
+                a.iter()
+                    .flat_map(|a| b.iter().map(move |b| format!("{}{}", a, b)))
+                    .collect(),
+            )
+        };
+        let mut ret = match self {
+            Self::LitStr { value, .. } => vec![value.to_string()],
+            Self::LitUndefined { .. } | Self::LitNull { .. } => vec![String::new()],
+            Self::LitInt { value, .. } => vec![value.to_string()],
+            Self::LitFloat { value, .. } => vec![value.to_string()],
+            Self::LitBool { value, .. } => vec![value.to_string()],
+            Self::ToStringWithoutUndefined { value, .. } => value.possible_string_values()?,
+            Self::Plus { left, right, .. } => concat(
+                left.possible_string_values()?,
+                right.possible_string_values()?,
+            )?,
+            Self::Cond {
+                true_br, false_br, ..
+            } => union(true_br, false_br)?,
+            Self::LogicAnd { right, .. } => {
+                let mut ret = right.possible_string_values()?;
+                ret.push(String::new());
+                ret
+            }
+            Self::LogicOr { left, right, .. } | Self::NullishCoalescing { left, right, .. } => {
+                union(left, right)?
+            }
+            Self::LitTemplateStr { fields, .. } => {
+                let mut ret = vec![String::new()];
+                for field in fields {
+                    let values = match field {
+                        TemplateStrFieldKind::Str { value, .. } => vec![value.to_string()],
+                        TemplateStrFieldKind::Interpolation { value, .. } => {
+                            value.possible_string_values()?
+                        }
+                    };
+                    ret = concat(ret, values)?;
+                }
+                ret
+            }
+            _ => return None,
+        };
+        ret.sort();
+        ret.dedup();
+        if ret.len() > LIMIT {
+            return None;
+        }
+        Some(ret)
+    }
+
     pub(super) fn validate_scopes(
         &self,
         ps: &mut ParseState,
@@ -1319,6 +1648,30 @@ impl Expression {
         }
     }
 
+    fn convert_arrow_func_param(&mut self, param: &str) {
+        if let Self::DataField { name, location } = self {
+            if name.as_str() == param {
+                *self = Self::ArrowFuncParam {
+                    name: name.clone(),
+                    location: location.clone(),
+                };
+            }
+            return;
+        }
+        for sub in self.sub_expressions_mut() {
+            sub.convert_arrow_func_param(param);
+        }
+    }
+
+    /// Find an arrow function inside the expression (including the expression itself).
+    pub(super) fn arrow_func_location(&self) -> Option<Range<Position>> {
+        if let Self::ArrowFunc { .. } = self {
+            return Some(self.location());
+        }
+        self.sub_expressions()
+            .find_map(|sub| sub.arrow_func_location())
+    }
+
     pub(super) fn collect_binding_map_keys(
         &self,
         bmc: &mut BindingMapCollector,
@@ -1375,7 +1728,9 @@ macro_rules! iter_sub_expr {
         impl<'a> $s<'a> {
             fn next_item(&mut self) -> Option<<Self as Iterator>::Item> {
                 let value = match &mut self.inner {
-                    Expression::ScopeRef { .. } | Expression::DataField { .. } => {
+                    Expression::ScopeRef { .. }
+                    | Expression::DataField { .. }
+                    | Expression::ArrowFuncParam { .. } => {
                         return None;
                     }
                     Expression::ToStringWithoutUndefined { value, .. } => {
@@ -1413,7 +1768,21 @@ macro_rules! iter_sub_expr {
                             }
                         }
                     }
-                    Expression::StaticMember { obj, .. } => {
+                    Expression::LitTemplateStr { fields, .. } => {
+                        let index = self.index
+                            + fields[self.index..].iter().position(|x| {
+                                matches!(x, TemplateStrFieldKind::Interpolation { .. })
+                            })?;
+                        self.index = index + 1;
+                        match fields.$get(index)? {
+                            TemplateStrFieldKind::Interpolation { value, .. } => value,
+                            TemplateStrFieldKind::Str { .. } => {
+                                return None;
+                            }
+                        }
+                    }
+                    Expression::StaticMember { obj, .. }
+                    | Expression::OptionalStaticMember { obj, .. } => {
                         if self.index == 0 {
                             self.index = 1;
                             obj
@@ -1423,6 +1792,9 @@ macro_rules! iter_sub_expr {
                     }
                     Expression::DynamicMember {
                         obj, field_name, ..
+                    }
+                    | Expression::OptionalDynamicMember {
+                        obj, field_name, ..
                     } => {
                         if self.index == 0 {
                             self.index = 1;
@@ -1434,7 +1806,8 @@ macro_rules! iter_sub_expr {
                             return None;
                         }
                     }
-                    Expression::FuncCall { func, args, .. } => {
+                    Expression::FuncCall { func, args, .. }
+                    | Expression::OptionalFuncCall { func, args, .. } => {
                         if self.index == 0 {
                             self.index = 1;
                             func
@@ -1444,7 +1817,8 @@ macro_rules! iter_sub_expr {
                             value
                         }
                     }
-                    Expression::Reverse { value, .. }
+                    Expression::ArrowFunc { body: value, .. }
+                    | Expression::Reverse { value, .. }
                     | Expression::BitReverse { value, .. }
                     | Expression::Positive { value, .. }
                     | Expression::Negative { value, .. }
@@ -1569,6 +1943,49 @@ mod test {
         case!(r#"{{ "" }}"#, "");
     }
 
+    #[test]
+    fn lit_template_str() {
+        case!(r#"{{ `` }}"#, r#"{{``}}"#);
+        case!(r#"{{ `a${ b }c` }}"#, r#"{{`a${b}c`}}"#);
+        case!(r#"{{ `${a}${ b + 1 }` }}"#, r#"{{`${a}${b+1}`}}"#);
+        case!(r#"{{ `a\n\`\${b}` }}"#, r#"{{`a\n\`\${b}`}}"#);
+        case!(r#"{{ `${ `-${a}-` }` }}"#, r#"{{`${`-${a}-`}`}}"#);
+        case!(r#"{{ `${ {a: 1}.a }` }}"#, r#"{{`${{a:1}.a}`}}"#);
+        case!(
+            r#"{{ `${ a` }}"#,
+            "",
+            ParseErrorKind::UnmatchedBracket,
+            8..8
+        );
+        case!(
+            r#"{{ `\u0` }}"#,
+            "{{` 0`}}",
+            ParseErrorKind::IllegalEscapeSequence,
+            6..8
+        );
+
+        let mut ps = ParseState::new("TEST", "`a${ b }c${d}`", Default::default());
+        let expr = Expression::parse_expression_or_object_inner(&mut ps, false).unwrap();
+        let Expression::LitTemplateStr {
+            fields,
+            backtick_location,
+        } = &*expr
+        else {
+            panic!("not a template string");
+        };
+        assert_eq!(backtick_location.0.start.utf16_col, 0);
+        assert_eq!(backtick_location.1.end.utf16_col, 14);
+        let locations: Vec<_> = fields
+            .iter()
+            .map(|x| match x {
+                TemplateStrFieldKind::Str { location, .. } => location.clone(),
+                TemplateStrFieldKind::Interpolation { value, .. } => value.location(),
+            })
+            .map(|x| x.start.utf16_col..x.end.utf16_col)
+            .collect();
+        assert_eq!(locations, [1..2, 5..6, 8..9, 11..12]);
+    }
+
     #[test]
     fn number() {
         case!(r#"{{ 0 }}"#, r#"{{0}}"#);
@@ -1718,6 +2135,62 @@ mod test {
         case!("{{ a(0 , a ? b : c) }}", "{{a(0,a?b:c)}}");
     }
 
+    #[test]
+    fn optional_chain() {
+        case!("{{ a ?. b ?. c }}", "{{a?.b?.c}}");
+        case!("{{ a?.[b] }}", "{{a?.[b]}}");
+        case!("{{ a?.(0 , b) }}", "{{a?.(0,b)}}");
+        case!("{{ a?.b.c[d](e) }}", "{{a?.b.c[d](e)}}");
+        case!("{{ a?. }}", "", ParseErrorKind::InvalidIdentifier, 7..7);
+        case!("{{ a?.[0 }}", "", ParseErrorKind::UnmatchedBracket, 9..9);
+        case!(
+            "{{ a?.(0 }}",
+            "",
+            ParseErrorKind::UnmatchedParenthesis,
+            9..9
+        );
+        case!("{{ a?.1:2 }}", "{{a?0.1:2}}");
+    }
+
+    #[test]
+    fn arrow_func() {
+        case!(
+            r#"<div bind:tap="{{ ( e ) => f ( e , a ) }}" />"#,
+            r#"<div bind:tap="{{(e)=>f(e,a)}}"/>"#
+        );
+        case!(
+            r#"<div change:v="{{ e => e.detail }}" />"#,
+            r#"<div change:v="{{(e)=>e.detail}}"/>"#
+        );
+        case!(
+            r#"<div catch:tap="{{ () => a ? b() : c }}" />"#,
+            r#"<div catch:tap="{{()=>a?b():c}}"/>"#
+        );
+        case!(
+            r#"<div bind:tap="{{ () => { a } }}" />"#,
+            r#"<div bind:tap/>"#,
+            ParseErrorKind::UnsupportedSyntax,
+            24..24
+        );
+        let (_, ps) = crate::parse::parse("TEST", r#"<div a="{{ () => b }}" />{{ () => d }}"#);
+        let warnings: Vec<_> = ps
+            .warnings()
+            .map(|x| {
+                (
+                    x.kind.clone(),
+                    x.location.start.utf16_col..x.location.end.utf16_col,
+                )
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (ParseErrorKind::ArrowFunctionNotAllowed, 11..18),
+                (ParseErrorKind::ArrowFunctionNotAllowed, 28..35),
+            ]
+        );
+    }
+
     #[test]
     fn reverse() {
         case!("{{ ! a.b }}", "{{!a.b}}");
@@ -2162,17 +2635,12 @@ mod test {
             ParseErrorKind::UnexpectedExpressionCharacter,
             9..9
         );
-        case!(
-            "{{ a ?. b }}",
-            "",
-            ParseErrorKind::UnexpectedExpressionCharacter,
-            5..5
-        );
+        case!("{{ a ?. b }}", "{{a?.b}}");
         case!(
             "{{ a ?. 1 : 2 }}",
             "",
-            ParseErrorKind::UnexpectedExpressionCharacter,
-            5..5
+            ParseErrorKind::InvalidIdentifier,
+            8..8
         );
         case!("{{ a ?.1 : 2 }}", "{{a?0.1:2}}");
         case!("{{ a ? b : c }}", "{{a?b:c}}");
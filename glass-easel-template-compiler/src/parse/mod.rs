@@ -3,6 +3,7 @@ use std::ops::Range;
 use super::binding_map;
 use serde::{Deserialize, Serialize};
 pub use tag::Template;
+pub use visit::{Visit, VisitMut};
 
 pub mod iter;
 
@@ -45,6 +46,7 @@ macro_rules! case {
 
 pub mod expr;
 pub mod tag;
+pub mod visit;
 
 pub(crate) const fn is_template_whitespace(c: char) -> bool {
     match c {
@@ -54,6 +56,11 @@ pub(crate) const fn is_template_whitespace(c: char) -> bool {
     }
 }
 
+/// The version of the JSON AST format (see `TmplGroup::get_tree_ast_json` ).
+///
+/// It is increased whenever the JSON structure changes incompatibly.
+pub const AST_JSON_VERSION: u32 = 1;
+
 pub trait TemplateStructure {
     fn location(&self) -> Range<Position>;
 
@@ -396,7 +403,7 @@ pub fn parse<'s>(path: &str, source: &'s str) -> (tag::Template, ParseState<'s>)
 }
 
 /// A location in source code.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
 pub struct Position {
     pub line: u32,
     pub utf16_col: u32,
@@ -521,6 +528,14 @@ pub enum ParseErrorKind {
     InvalidInlineStyleString,
     DuplicatedStylePropertyNames,
     IncompatibleWithStyleColonAttributes,
+    ArrowFunctionNotAllowed,
+    DependencyNotFound,
+    TemplateNotFound,
+    CircularInclude,
+    ShadowedTemplateName,
+    IllegalScriptSyntax,
+    UnsupportedScriptFeature,
+    ClassNamesNotRenamed,
 }
 
 impl ParseErrorKind {
@@ -574,6 +589,22 @@ impl ParseErrorKind {
             Self::IncompatibleWithStyleColonAttributes => {
                 "style data bindings are incompatible with `style:` attributes"
             }
+            Self::ArrowFunctionNotAllowed => {
+                "arrow functions are only allowed in event bindings and `change:` attributes"
+            }
+            Self::DependencyNotFound => "the imported or included template is not found",
+            Self::TemplateNotFound => {
+                "the template is not defined in this file or the imported files"
+            }
+            Self::CircularInclude => "the include chain is circular",
+            Self::ShadowedTemplateName => "the template name shadows another template",
+            Self::IllegalScriptSyntax => "illegal script syntax",
+            Self::UnsupportedScriptFeature => {
+                "this script syntax is not supported in WXS (ES5 only)"
+            }
+            Self::ClassNamesNotRenamed => {
+                "the class names in this value cannot be renamed with the class name mapping"
+            }
         }
     }
 
@@ -619,6 +650,14 @@ impl ParseErrorKind {
             Self::InvalidInlineStyleString => ParseErrorLevel::Error,
             Self::DuplicatedStylePropertyNames => ParseErrorLevel::Error,
             Self::IncompatibleWithStyleColonAttributes => ParseErrorLevel::Error,
+            Self::ArrowFunctionNotAllowed => ParseErrorLevel::Error,
+            Self::DependencyNotFound => ParseErrorLevel::Error,
+            Self::TemplateNotFound => ParseErrorLevel::Error,
+            Self::CircularInclude => ParseErrorLevel::Error,
+            Self::ShadowedTemplateName => ParseErrorLevel::Warn,
+            Self::IllegalScriptSyntax => ParseErrorLevel::Error,
+            Self::UnsupportedScriptFeature => ParseErrorLevel::Error,
+            Self::ClassNamesNotRenamed => ParseErrorLevel::Warn,
         }
     }
 }
@@ -1,6 +1,7 @@
 use std::{borrow::Cow, ops::Range};
 
 use compact_str::CompactString;
+use serde::Serialize;
 
 use crate::escape::dash_to_camel;
 
@@ -13,7 +14,7 @@ use super::{
 pub const DEFAULT_FOR_ITEM_SCOPE_NAME: &'static str = "item";
 pub const DEFAULT_FOR_INDEX_SCOPE_NAME: &'static str = "index";
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct Template {
     pub path: String,
@@ -21,17 +22,18 @@ pub struct Template {
     pub globals: TemplateGlobals,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct TemplateGlobals {
     pub imports: Vec<ImportElement>,
     pub includes: Vec<IncludeElement>,
     pub sub_templates: Vec<TemplateDefinition>,
     pub scripts: Vec<Script>,
+    #[serde(skip)]
     pub(crate) binding_map_collector: BindingMapCollector,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct ImportElement {
     pub tag_location: TagLocation,
@@ -39,7 +41,7 @@ pub struct ImportElement {
     pub src: StrName,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct IncludeElement {
     pub tag_location: TagLocation,
@@ -47,16 +49,18 @@ pub struct IncludeElement {
     pub src: StrName,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct TemplateDefinition {
     pub tag_location: TagLocation,
     pub name_location: Range<Position>,
     pub name: StrName,
+    /// The TypeScript type of the data, declared by the `data-type` attribute.
+    pub data_type: Option<(Range<Position>, StrName)>,
     pub content: Vec<Node>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct TagLocation {
     pub start: (Range<Position>, Range<Position>),
@@ -257,7 +261,7 @@ impl Template {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub enum Node {
     Text(Value),
@@ -266,7 +270,7 @@ pub enum Node {
     UnknownMetaTag(UnknownMetaTag),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct Comment {
     pub content: String,
@@ -282,7 +286,7 @@ impl Comment {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct UnknownMetaTag {
     pub tag_name: Vec<Ident>,
@@ -360,6 +364,7 @@ impl Node {
                 }
                 false
             });
+            value.check_arrow_func(ps, false);
             let is_whitespace = match &value {
                 Value::Static { value, .. } => {
                     value.trim_matches(super::is_template_whitespace).is_empty()
@@ -389,14 +394,14 @@ impl Node {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct Element {
     pub kind: ElementKind,
     pub tag_location: TagLocation,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub enum ElementKind {
     #[non_exhaustive]
@@ -757,6 +762,7 @@ impl Element {
         let mut wx_for_item: Option<(Range<Position>, StrName)> = None;
         let mut wx_key: Option<(Range<Position>, StrName)> = None;
         let mut template_name: Option<(Range<Position>, StrName)> = None;
+        let mut template_data_type: Option<(Range<Position>, StrName)> = None;
         let mut script_module: Option<(Range<Position>, StrName)> = None;
         let mut class_attrs: Vec<(Range<Position>, Ident, Option<Value>)> = vec![];
         let mut style_attrs: Vec<(Range<Position>, Ident, Value)> = vec![];
@@ -790,6 +796,7 @@ impl Element {
                     WxForItem(Range<Position>),
                     WxKey(Range<Position>),
                     TemplateName,
+                    TemplateDataType,
                     TemplateIs,
                     TemplateData,
                     Src(&'static str),
@@ -823,6 +830,9 @@ impl Element {
                             (ElementKind::TemplateRef { .. }, "name") => {
                                 AttrPrefixKind::TemplateName
                             }
+                            (ElementKind::TemplateRef { .. }, "data-type") => {
+                                AttrPrefixKind::TemplateDataType
+                            }
                             (ElementKind::TemplateRef { .. }, "is") => AttrPrefixKind::TemplateIs,
                             (ElementKind::TemplateRef { .. }, "data") => {
                                 AttrPrefixKind::TemplateData
@@ -917,6 +927,7 @@ impl Element {
                     AttrPrefixKind::WxForItem(_) => AttrPrefixParseKind::ScopeName,
                     AttrPrefixKind::WxKey(_) => AttrPrefixParseKind::StaticStr,
                     AttrPrefixKind::TemplateName => AttrPrefixParseKind::StaticStr,
+                    AttrPrefixKind::TemplateDataType => AttrPrefixParseKind::StaticStr,
                     AttrPrefixKind::TemplateIs => AttrPrefixParseKind::Value,
                     AttrPrefixKind::TemplateData => AttrPrefixParseKind::TemplateData,
                     AttrPrefixKind::Src(_) => AttrPrefixParseKind::StaticStr,
@@ -1032,6 +1043,21 @@ impl Element {
                     }
                 };
 
+                // arrow functions are only meaningful as event handlers
+                if let AttrPrefixParseResult::Value(Some(value)) = &attr_value {
+                    let allow_arrow_func = matches!(
+                        prefix,
+                        AttrPrefixKind::Change(_)
+                            | AttrPrefixKind::Bind(_)
+                            | AttrPrefixKind::MutBind(_)
+                            | AttrPrefixKind::Catch(_)
+                            | AttrPrefixKind::CaptureBind(_)
+                            | AttrPrefixKind::CaptureMutBind(_)
+                            | AttrPrefixKind::CaptureCatch(_)
+                    );
+                    value.check_arrow_func(ps, allow_arrow_func);
+                }
+
                 // unwrap an optional value
                 fn unwrap_option_value_for_attr(
                     ps: &mut ParseState,
@@ -1356,6 +1382,18 @@ impl Element {
                             }
                         }
                     }
+                    AttrPrefixKind::TemplateDataType => {
+                        if let AttrPrefixParseResult::StaticStr(s) = attr_value {
+                            if template_data_type.is_some() {
+                                ps.add_warning(
+                                    ParseErrorKind::DuplicatedAttribute,
+                                    attr_name.location,
+                                );
+                            } else {
+                                template_data_type = Some((attr_name.location(), s));
+                            }
+                        }
+                    }
                     AttrPrefixKind::TemplateIs => match &mut element {
                         ElementKind::TemplateRef { target, .. } => {
                             if let AttrPrefixParseResult::Value(value) = attr_value {
@@ -2105,6 +2143,9 @@ impl Element {
                 if target.1.location().end == default_attr_position {
                     ps.add_warning(ParseErrorKind::MissingModuleName, target.0.clone());
                 }
+                if let Some((loc, _)) = template_data_type.as_ref() {
+                    ps.add_warning(ParseErrorKind::InvalidAttribute, loc.clone());
+                }
             }
         };
         let allow_for_if = template_name.is_none() && external_tag_type == ExternalTagKind::Include;
@@ -2499,6 +2540,7 @@ impl Element {
                     tag_location: tag_location.clone(),
                     name_location: loc,
                     name: name.clone(),
+                    data_type: template_data_type,
                     content: new_children,
                 });
             }
@@ -2784,7 +2826,7 @@ impl Element {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[non_exhaustive]
 pub struct CommonElementAttributes {
     pub id: Option<(Range<Position>, Value)>,
@@ -2848,7 +2890,7 @@ impl CommonElementAttributes {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CustomAttribute {
     pub colon_separated_name: Vec<Ident>,
     pub value: Option<Value>,
@@ -2894,20 +2936,20 @@ impl CustomAttribute {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct NormalAttribute {
     pub name: Ident,
     pub value: Option<Value>,
     pub prefix: NormalAttributePrefix,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum NormalAttributePrefix {
     None,
     Model(Range<Position>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct Attribute {
     pub name: Ident,
@@ -3040,7 +3082,7 @@ impl Attribute {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct StaticAttribute {
     pub name: Ident,
@@ -3072,7 +3114,7 @@ impl StaticAttribute {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub enum ClassAttribute {
     None,
@@ -3080,7 +3122,160 @@ pub enum ClassAttribute {
     Multiple(Vec<(Range<Position>, Ident, Option<Value>)>),
 }
 
-#[derive(Debug, Clone)]
+/// The class names referenced by an element.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClassNames {
+    /// The class names that may be applied, with the locations of the attributes.
+    pub names: Vec<(CompactString, Range<Position>)>,
+    /// Whether some class names cannot be determined statically, e.g. `class="{{ a }}"` .
+    pub incomplete: bool,
+}
+
+impl ClassNames {
+    /// Add the class names in an attribute value.
+    ///
+    /// For dynamic values, all the possible values are analyzed.
+    pub fn add_value(&mut self, value: &Value) {
+        match value {
+            Value::Static { value, location } => {
+                for name in value.split_whitespace() {
+                    self.names.push((name.into(), location.clone()));
+                }
+            }
+            Value::Dynamic { expression, .. } => match expression.possible_string_values() {
+                Some(values) => {
+                    let location = expression.location();
+                    for value in values.iter() {
+                        for name in value.split_whitespace() {
+                            if self.names.iter().all(|x| x.0 != name) {
+                                self.names.push((name.into(), location.clone()));
+                            }
+                        }
+                    }
+                }
+                None => {
+                    self.incomplete = true;
+                }
+            },
+        }
+    }
+}
+
+impl ClassAttribute {
+    /// Get the class names in `class` and `class:` attributes.
+    pub fn class_names(&self) -> ClassNames {
+        let mut ret = ClassNames::default();
+        match self {
+            Self::None => {}
+            Self::String(_, value) => ret.add_value(value),
+            Self::Multiple(list) => {
+                for (_, name, _) in list.iter() {
+                    ret.names.push((name.name.clone(), name.location.clone()));
+                }
+            }
+        }
+        ret
+    }
+
+    /// Rename the class names.
+    ///
+    /// `f` returns the new name, or `None` if the class name should not be changed.
+    /// In dynamic values, the class names in string literals are renamed
+    /// if all the possible values can be analyzed, e.g. `class="a {{ b ? 'c' : '' }}"` .
+    /// Returns the location of the dynamic value if it cannot be renamed in this way.
+    pub fn rename_classes(
+        &mut self,
+        mut f: impl FnMut(&str) -> Option<CompactString>,
+    ) -> Option<Range<Position>> {
+        match self {
+            Self::None => {}
+            Self::String(_, Value::Static { value, .. }) => {
+                if let Some(x) = rename_class_tokens(value, &mut f) {
+                    *value = x;
+                }
+            }
+            Self::String(_, Value::Dynamic { expression, .. }) => {
+                let location = expression.location();
+                let Some(values) = expression.possible_string_values() else {
+                    return Some(location);
+                };
+                let mut expected: Vec<_> = values
+                    .iter()
+                    .map(|x| match rename_class_tokens(x, &mut f) {
+                        Some(x) => normalize_class_tokens(&x),
+                        None => normalize_class_tokens(x),
+                    })
+                    .collect();
+                let mut renamed = expression.clone();
+                renamed.for_each_string_literal_mut(&mut |value| {
+                    if let Some(x) = rename_class_tokens(value, &mut f) {
+                        *value = x;
+                    }
+                });
+                // a class name split into different literals cannot be renamed in this way
+                let mut actual: Vec<_> = renamed
+                    .possible_string_values()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|x| normalize_class_tokens(x))
+                    .collect();
+                expected.sort();
+                expected.dedup();
+                actual.sort();
+                actual.dedup();
+                if actual != expected {
+                    return Some(location);
+                }
+                *expression = renamed;
+            }
+            Self::Multiple(list) => {
+                for (_, name, _) in list.iter_mut() {
+                    if let Some(x) = f(&name.name) {
+                        name.name = x;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Rename the whitespace-separated class names in `s` , returning `None` if nothing is changed.
+///
+/// The whitespace is kept as-is, so that the tokens split into different string literals are still joined.
+fn rename_class_tokens(
+    s: &str,
+    f: &mut impl FnMut(&str) -> Option<CompactString>,
+) -> Option<CompactString> {
+    let mut changed = false;
+    let mut ret = String::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let ws_len = rest.len() - rest.trim_start().len();
+        ret.push_str(&rest[..ws_len]);
+        rest = &rest[ws_len..];
+        let token_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token = &rest[..token_len];
+        rest = &rest[token_len..];
+        if token.is_empty() {
+            break;
+        }
+        match f(token) {
+            Some(x) => {
+                changed = true;
+                ret.push_str(&x);
+            }
+            None => ret.push_str(token),
+        }
+    }
+    changed.then(|| ret.into())
+}
+
+fn normalize_class_tokens(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub enum StyleAttribute {
     None,
@@ -3088,7 +3283,7 @@ pub enum StyleAttribute {
     Multiple(Vec<(Range<Position>, Ident, Value)>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct EventBinding {
     pub name: Ident,
@@ -3104,7 +3299,7 @@ pub struct EventBinding {
 /// It can be used as tag name and attribute name.
 /// Unlike JavaScript identifier, it can contain `-` .
 ///
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct Ident {
     pub name: CompactString,
@@ -3220,7 +3415,7 @@ impl Ident {
 }
 
 /// A static string with location information.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct StrName {
     pub name: CompactString,
@@ -3414,7 +3609,7 @@ impl StrName {
 }
 
 /// A static string or an expression.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub enum Value {
     #[non_exhaustive]
@@ -3426,6 +3621,7 @@ pub enum Value {
     Dynamic {
         expression: Box<Expression>,
         double_brace_location: (Range<Position>, Range<Position>),
+        #[serde(skip)]
         binding_map_keys: Option<BindingMapKeys>,
     },
 }
@@ -3470,6 +3666,19 @@ impl Value {
         }
     }
 
+    fn check_arrow_func(&self, ps: &mut ParseState, allowed: bool) {
+        if let Self::Dynamic { expression, .. } = self {
+            if allowed {
+                if let Expression::ArrowFunc { .. } = &**expression {
+                    return;
+                }
+            }
+            if let Some(location) = expression.arrow_func_location() {
+                ps.add_warning(ParseErrorKind::ArrowFunctionNotAllowed, location);
+            }
+        }
+    }
+
     fn parse_data_binding(ps: &mut ParseState, is_template_data: bool) -> Option<Self> {
         let Some(double_brace_left) = ps.consume_str("{{") else {
             return None;
@@ -3727,7 +3936,7 @@ impl Value {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub enum Script {
     #[non_exhaustive]
@@ -4400,6 +4609,22 @@ mod test {
             ParseErrorKind::InvalidAttribute,
             19..23
         );
+        case!(
+            "<template name='a' data-type='{ b: string }'>{{ b }}</template>",
+            r#"<template name="a" data-type="{ b: string }">{{b}}</template>"#
+        );
+        case!(
+            "<template is='a' data-type='{ b: string }' />",
+            r#"<template is="a"/>"#,
+            ParseErrorKind::InvalidAttribute,
+            17..26
+        );
+        case!(
+            "<template name='a' data-type='A' data-type='B' />",
+            r#"<template name="a" data-type="A"/>"#,
+            ParseErrorKind::DuplicatedAttribute,
+            33..42
+        );
         case!(
             "<template is='a' data='  ' />",
             r#"<template is="a"/>"#,
@@ -0,0 +1,431 @@
+//! Visitors for the template tree.
+//!
+//! `Visit` walks the tree by shared references and `VisitMut` walks it by mutable references.
+//! Each `visit_*` method walks the sub-tree by default,
+//! and the `walk` / `walk_mut` module functions can be called in overridden methods to keep walking.
+//!
+//! While walking, `enter_scope` and `exit_scope` are called for each scope in the same order as
+//! the scope indices are assigned (see `Expression::ScopeRef` ),
+//! so a visitor maintaining a stack of entered scopes can lookup a `ScopeRef` by its index directly.
+
+use std::ops::Range;
+
+use compact_str::CompactString;
+
+use super::{
+    expr::Expression,
+    tag::{
+        Attribute, ClassAttribute, CommonElementAttributes, Element, ElementKind, EventBinding,
+        Node, NormalAttribute, StaticAttribute, StyleAttribute, Template, TemplateDefinition,
+        Value,
+    },
+    Position, TemplateStructure,
+};
+
+macro_rules! visit {
+    ($t:ident, $walk:ident, $sub_expressions:ident, $($mut_t:tt)*) => {
+        pub trait $t {
+            fn visit_template(&mut self, template: & $($mut_t)* Template) {
+                $walk::walk_template(self, template)
+            }
+
+            fn visit_sub_template(&mut self, sub_template: & $($mut_t)* TemplateDefinition) {
+                $walk::walk_sub_template(self, sub_template)
+            }
+
+            fn visit_node(&mut self, node: & $($mut_t)* Node) {
+                $walk::walk_node(self, node)
+            }
+
+            fn visit_element(&mut self, element: & $($mut_t)* Element) {
+                $walk::walk_element(self, element)
+            }
+
+            fn visit_attribute(&mut self, attr: & $($mut_t)* Attribute) {
+                $walk::walk_attribute(self, attr)
+            }
+
+            fn visit_normal_attribute(&mut self, attr: & $($mut_t)* NormalAttribute) {
+                $walk::walk_normal_attribute(self, attr)
+            }
+
+            fn visit_static_attribute(&mut self, _attr: & $($mut_t)* StaticAttribute) {
+                // static attributes contain nothing to walk
+            }
+
+            fn visit_event_binding(&mut self, ev: & $($mut_t)* EventBinding) {
+                $walk::walk_event_binding(self, ev)
+            }
+
+            fn visit_value(&mut self, value: & $($mut_t)* Value) {
+                $walk::walk_value(self, value)
+            }
+
+            fn visit_expression(&mut self, expr: & $($mut_t)* Expression) {
+                $walk::walk_expression(self, expr)
+            }
+
+            /// Called when a new scope is introduced.
+            fn enter_scope(&mut self, _name: &str, _location: &Range<Position>) {}
+
+            /// Called when a scope is no longer available (in the reversed order of `enter_scope` ).
+            fn exit_scope(&mut self, _name: &str) {}
+        }
+
+        pub mod $walk {
+            use super::*;
+
+            pub fn walk_template<V: $t + ?Sized>(v: &mut V, template: & $($mut_t)* Template) {
+                let scopes: Vec<_> = template
+                    .globals
+                    .scripts
+                    .iter()
+                    .map(|x| {
+                        let name = x.module_name();
+                        (name.name.clone(), name.location())
+                    })
+                    .collect();
+                enter_scopes(v, &scopes);
+                for node in & $($mut_t)* template.content {
+                    v.visit_node(node);
+                }
+                for sub in & $($mut_t)* template.globals.sub_templates {
+                    v.visit_sub_template(sub);
+                }
+                exit_scopes(v, &scopes);
+            }
+
+            pub fn walk_sub_template<V: $t + ?Sized>(
+                v: &mut V,
+                sub_template: & $($mut_t)* TemplateDefinition,
+            ) {
+                for node in & $($mut_t)* sub_template.content {
+                    v.visit_node(node);
+                }
+            }
+
+            pub fn walk_node<V: $t + ?Sized>(v: &mut V, node: & $($mut_t)* Node) {
+                match node {
+                    Node::Text(value) => v.visit_value(value),
+                    Node::Element(elem) => v.visit_element(elem),
+                    Node::Comment(..) | Node::UnknownMetaTag(..) => {}
+                }
+            }
+
+            pub fn walk_element<V: $t + ?Sized>(v: &mut V, element: & $($mut_t)* Element) {
+                // scopes introduced by slot values and `let:`
+                let mut scopes = vec![];
+                if let Some(refs) = element.slot_value_refs() {
+                    for attr in refs {
+                        scopes.push((attr.value.name.clone(), attr.value.location.clone()));
+                    }
+                }
+                if let Some(refs) = element.let_var_refs() {
+                    for attr in refs {
+                        scopes.push((attr.name.name.clone(), attr.name.location.clone()));
+                    }
+                }
+                enter_scopes(v, &scopes);
+
+                // self values
+                match & $($mut_t)* element.kind {
+                    ElementKind::Normal {
+                        tag_name: _,
+                        attributes,
+                        class,
+                        style,
+                        change_attributes,
+                        worklet_attributes,
+                        children: _,
+                        generics,
+                        extra_attr,
+                        let_vars,
+                        common,
+                    } => {
+                        for attr in let_vars {
+                            v.visit_attribute(attr);
+                        }
+                        for attr in attributes {
+                            v.visit_normal_attribute(attr);
+                        }
+                        match class {
+                            ClassAttribute::None => {}
+                            ClassAttribute::String(_, value) => v.visit_value(value),
+                            ClassAttribute::Multiple(list) => {
+                                for (_, _, value) in list {
+                                    if let Some(value) = value {
+                                        v.visit_value(value);
+                                    }
+                                }
+                            }
+                        }
+                        match style {
+                            StyleAttribute::None => {}
+                            StyleAttribute::String(_, value) => v.visit_value(value),
+                            StyleAttribute::Multiple(list) => {
+                                for (_, _, value) in list {
+                                    v.visit_value(value);
+                                }
+                            }
+                        }
+                        for attr in change_attributes {
+                            v.visit_attribute(attr);
+                        }
+                        for attr in worklet_attributes
+                            .into_iter()
+                            .chain(generics)
+                            .chain(extra_attr)
+                        {
+                            v.visit_static_attribute(attr);
+                        }
+                        walk_common(v, common);
+                    }
+                    ElementKind::Pure {
+                        children: _,
+                        let_vars,
+                        slot,
+                        slot_value_refs: _,
+                    } => {
+                        for attr in let_vars {
+                            v.visit_attribute(attr);
+                        }
+                        if let Some((_, value)) = slot {
+                            v.visit_value(value);
+                        }
+                    }
+                    ElementKind::For { list, .. } => {
+                        v.visit_value(& $($mut_t)* list.1);
+                    }
+                    ElementKind::If { branches, .. } => {
+                        for (_, value, _) in branches {
+                            v.visit_value(value);
+                        }
+                    }
+                    ElementKind::TemplateRef { target, data } => {
+                        v.visit_value(& $($mut_t)* target.1);
+                        v.visit_value(& $($mut_t)* data.1);
+                    }
+                    ElementKind::Include { .. } => {}
+                    ElementKind::Slot {
+                        name,
+                        values,
+                        common,
+                    } => {
+                        v.visit_value(& $($mut_t)* name.1);
+                        for attr in values {
+                            v.visit_attribute(attr);
+                        }
+                        walk_common(v, common);
+                    }
+                }
+
+                // scopes introduced by for loop
+                let for_scopes = match &element.kind {
+                    ElementKind::For {
+                        item_name,
+                        index_name,
+                        ..
+                    } => vec![
+                        (item_name.1.name.clone(), item_name.1.location.clone()),
+                        (index_name.1.name.clone(), index_name.1.location.clone()),
+                    ],
+                    _ => vec![],
+                };
+                enter_scopes(v, &for_scopes);
+
+                // children
+                match & $($mut_t)* element.kind {
+                    ElementKind::Normal { children, .. }
+                    | ElementKind::Pure { children, .. }
+                    | ElementKind::For { children, .. } => {
+                        for child in children {
+                            v.visit_node(child);
+                        }
+                    }
+                    ElementKind::If {
+                        branches,
+                        else_branch,
+                    } => {
+                        for (_, _, children) in branches {
+                            for child in children {
+                                v.visit_node(child);
+                            }
+                        }
+                        if let Some((_, children)) = else_branch {
+                            for child in children {
+                                v.visit_node(child);
+                            }
+                        }
+                    }
+                    ElementKind::TemplateRef { .. }
+                    | ElementKind::Include { .. }
+                    | ElementKind::Slot { .. } => {}
+                }
+
+                exit_scopes(v, &for_scopes);
+                exit_scopes(v, &scopes);
+            }
+
+            fn walk_common<V: $t + ?Sized>(v: &mut V, common: & $($mut_t)* CommonElementAttributes) {
+                let CommonElementAttributes {
+                    id,
+                    slot,
+                    slot_value_refs: _,
+                    event_bindings,
+                    data,
+                    marks,
+                } = common;
+                if let Some((_, value)) = id {
+                    v.visit_value(value);
+                }
+                if let Some((_, value)) = slot {
+                    v.visit_value(value);
+                }
+                for ev in event_bindings {
+                    v.visit_event_binding(ev);
+                }
+                for attr in data {
+                    v.visit_attribute(attr);
+                }
+                for attr in marks {
+                    v.visit_attribute(attr);
+                }
+            }
+
+            pub fn walk_attribute<V: $t + ?Sized>(v: &mut V, attr: & $($mut_t)* Attribute) {
+                if let Some(value) = & $($mut_t)* attr.value {
+                    v.visit_value(value);
+                }
+            }
+
+            pub fn walk_normal_attribute<V: $t + ?Sized>(
+                v: &mut V,
+                attr: & $($mut_t)* NormalAttribute,
+            ) {
+                if let Some(value) = & $($mut_t)* attr.value {
+                    v.visit_value(value);
+                }
+            }
+
+            pub fn walk_event_binding<V: $t + ?Sized>(v: &mut V, ev: & $($mut_t)* EventBinding) {
+                if let Some(value) = & $($mut_t)* ev.value {
+                    v.visit_value(value);
+                }
+            }
+
+            pub fn walk_value<V: $t + ?Sized>(v: &mut V, value: & $($mut_t)* Value) {
+                match value {
+                    Value::Static { .. } => {}
+                    Value::Dynamic { expression, .. } => v.visit_expression(expression),
+                }
+            }
+
+            pub fn walk_expression<V: $t + ?Sized>(v: &mut V, expr: & $($mut_t)* Expression) {
+                for sub in expr.$sub_expressions() {
+                    v.visit_expression(sub);
+                }
+            }
+
+            fn enter_scopes<V: $t + ?Sized>(
+                v: &mut V,
+                scopes: &[(CompactString, Range<Position>)],
+            ) {
+                for (name, location) in scopes {
+                    v.enter_scope(name, location);
+                }
+            }
+
+            fn exit_scopes<V: $t + ?Sized>(
+                v: &mut V,
+                scopes: &[(CompactString, Range<Position>)],
+            ) {
+                for (name, _) in scopes.iter().rev() {
+                    v.exit_scope(name);
+                }
+            }
+        }
+    };
+}
+
+visit!(Visit, walk, sub_expressions,);
+visit!(VisitMut, walk_mut, sub_expressions_mut, mut);
+
+#[cfg(test)]
+mod test {
+    use compact_str::CompactString;
+
+    use super::*;
+    use crate::stringify::Stringify;
+
+    #[test]
+    fn scope_indices() {
+        const SRC: &str = r#"
+            <wxs module="m" src="/m" />
+            <view wx:for="{{ list }}" wx:for-item="x">
+                <block slot:a="s" let:y="{{ x }}">{{ m.f(s, x, index, y, z) }}</block>
+            </view>
+            <template name="t">{{ m }}</template>
+        "#;
+        #[derive(Default)]
+        struct Collector {
+            stack: Vec<CompactString>,
+            refs: Vec<String>,
+        }
+        impl Visit for Collector {
+            fn visit_expression(&mut self, expr: &Expression) {
+                match expr {
+                    Expression::ScopeRef { index, .. } => {
+                        self.refs.push(self.stack[*index].to_string());
+                    }
+                    Expression::DataField { name, .. } => {
+                        self.refs.push(format!("D.{}", name));
+                    }
+                    _ => walk::walk_expression(self, expr),
+                }
+            }
+
+            fn enter_scope(&mut self, name: &str, _location: &Range<Position>) {
+                self.stack.push(name.into());
+            }
+
+            fn exit_scope(&mut self, name: &str) {
+                assert_eq!(self.stack.pop().unwrap(), name);
+            }
+        }
+        let (template, _) = crate::parse::parse("TEST", SRC);
+        let mut collector = Collector::default();
+        collector.visit_template(&template);
+        assert!(collector.stack.is_empty());
+        assert_eq!(
+            collector.refs,
+            ["D.list", "x", "m", "s", "x", "index", "y", "D.z", "m"]
+        );
+    }
+
+    #[test]
+    fn mutate_values() {
+        const SRC: &str = r#"<div a="{{ b }}">{{ c }}<span wx:if="{{ d }}" /></div>"#;
+        struct Renamer();
+        impl VisitMut for Renamer {
+            fn visit_expression(&mut self, expr: &mut Expression) {
+                if let Expression::DataField { name, .. } = expr {
+                    *name = format!("{}{}", name, name).into();
+                }
+                walk_mut::walk_expression(self, expr);
+            }
+        }
+        let (mut template, _) = crate::parse::parse("TEST", SRC);
+        Renamer().visit_template(&mut template);
+        let options = crate::stringify::StringifyOptions {
+            minimize: true,
+            ..Default::default()
+        };
+        let mut stringifier =
+            crate::stringify::Stringifier::new(String::new(), "test", Some(SRC), options);
+        template.stringify_write(&mut stringifier).unwrap();
+        assert_eq!(
+            stringifier.finish().0,
+            r#"<div a="{{bb}}">{{cc}}<span wx:if="{{dd}}"/></div>"#
+        );
+    }
+}
@@ -4,8 +4,11 @@ use compact_str::CompactString;
 
 use super::{JsExprWriter, JsFunctionScopeWriter, JsIdent, ScopeVar, ScopeVarLvaluePath};
 use crate::{
-    escape::gen_lit_str,
-    parse::expr::{ArrayFieldKind, Expression, ObjectFieldKind},
+    escape::{gen_lit_str, gen_lit_template_str_part},
+    parse::{
+        expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrFieldKind},
+        TemplateStructure,
+    },
     stringify::expr::ExpressionLevel,
     TmplError,
 };
@@ -17,6 +20,8 @@ pub(crate) enum PathSlice {
     ScopeIndex(usize),
     StaticMember(CompactString),
     IndirectValue(JsIdent),
+    OptionalStaticMember(CompactString),
+    OptionalIndirectValue(JsIdent),
     CombineObj(Vec<(Option<CompactString>, PathAnalysisState, Vec<PathSliceList>)>),
     CombineArr(
         Vec<(PathAnalysisState, Vec<PathSliceList>)>,
@@ -112,7 +117,10 @@ impl PathSliceList {
         // TODO split the first segment as another type to avoid this check
         for x in iter {
             match x {
-                PathSlice::StaticMember(_) | PathSlice::IndirectValue(_) => {}
+                PathSlice::StaticMember(_)
+                | PathSlice::IndirectValue(_)
+                | PathSlice::OptionalStaticMember(_)
+                | PathSlice::OptionalIndirectValue(_) => {}
                 _ => return false,
             }
         }
@@ -174,8 +182,12 @@ impl PathSliceList {
                         need_comma = true;
                     }
                     match x {
-                        PathSlice::StaticMember(s) => write!(w, "{}", gen_lit_str(s))?,
-                        PathSlice::IndirectValue(i) => write!(w, "{}", i)?,
+                        PathSlice::StaticMember(s) | PathSlice::OptionalStaticMember(s) => {
+                            write!(w, "{}", gen_lit_str(s))?
+                        }
+                        PathSlice::IndirectValue(i) | PathSlice::OptionalIndirectValue(i) => {
+                            write!(w, "{}", i)?
+                        }
                         _ => break,
                     }
                 }
@@ -230,10 +242,10 @@ impl PathSliceList {
                         write!(&mut ret, "undefined")?
                     }
                 }
-                PathSlice::StaticMember(s) => {
+                PathSlice::StaticMember(s) | PathSlice::OptionalStaticMember(s) => {
                     ret = format!("Z({},{})", ret, gen_lit_str(s));
                 }
-                PathSlice::IndirectValue(i) => {
+                PathSlice::IndirectValue(i) | PathSlice::OptionalIndirectValue(i) => {
                     ret = format!("Z({},{})", ret, i);
                 }
                 PathSlice::CombineObj(v) => {
@@ -434,6 +446,7 @@ impl Expression {
             write!(value, ")")?;
             return Ok(ret);
         }
+        w.write_source_position(value, self.location_start())?;
         let path_analysis_state: PathAnalysisState = match self {
             Expression::ScopeRef { index, .. } => {
                 let scope = &scopes[*index];
@@ -598,18 +611,49 @@ impl Expression {
                 )]))
             }
 
+            Expression::LitTemplateStr { fields, .. } => {
+                write!(value, "`")?;
+                for field in fields.iter() {
+                    match field {
+                        TemplateStrFieldKind::Str { value: x, .. } => {
+                            write!(value, "{}", gen_lit_template_str_part(x))?;
+                        }
+                        TemplateStrFieldKind::Interpolation { value: x, .. } => {
+                            write!(value, "${{")?;
+                            x.to_proc_gen_rec_and_end_path(
+                                w,
+                                scopes,
+                                ExpressionLevel::Cond,
+                                path_calc,
+                                value,
+                            )?;
+                            write!(value, "}}")?;
+                        }
+                    }
+                }
+                write!(value, "`")?;
+                PathAnalysisState::NotInPath
+            }
             Expression::StaticMember {
                 obj, field_name, ..
+            }
+            | Expression::OptionalStaticMember {
+                obj, field_name, ..
             } => {
+                // `X` already short-circuits nullish objects, so `?.` shares the same output
                 write!(value, "X(")?;
                 let mut pas =
                     obj.to_proc_gen_rec(w, scopes, ExpressionLevel::Cond, path_calc, value)?;
                 write!(value, ").{}", field_name)?;
                 match &mut pas {
                     PathAnalysisState::InPath(path_slices) => {
-                        path_slices
-                            .0
-                            .push(PathSlice::StaticMember(field_name.clone()));
+                        let field_name = field_name.clone();
+                        path_slices.0.push(match self {
+                            Expression::OptionalStaticMember { .. } => {
+                                PathSlice::OptionalStaticMember(field_name)
+                            }
+                            _ => PathSlice::StaticMember(field_name),
+                        });
                     }
                     PathAnalysisState::NotInPath => {
                         // do nothing
@@ -619,6 +663,9 @@ impl Expression {
             }
             Expression::DynamicMember {
                 obj, field_name, ..
+            }
+            | Expression::OptionalDynamicMember {
+                obj, field_name, ..
             } => {
                 let ident = {
                     let ident = w.gen_private_ident();
@@ -642,7 +689,12 @@ impl Expression {
                 write!(value, ")[{}]", ident)?;
                 match &mut pas {
                     PathAnalysisState::InPath(path_slices) => {
-                        path_slices.0.push(PathSlice::IndirectValue(ident));
+                        path_slices.0.push(match self {
+                            Expression::OptionalDynamicMember { .. } => {
+                                PathSlice::OptionalIndirectValue(ident)
+                            }
+                            _ => PathSlice::IndirectValue(ident),
+                        });
                     }
                     PathAnalysisState::NotInPath => {
                         // do nothing
@@ -650,15 +702,25 @@ impl Expression {
                 }
                 pas
             }
-            Expression::FuncCall { func, args, .. } => {
+            Expression::FuncCall { func, args, .. }
+            | Expression::OptionalFuncCall { func, args, .. } => {
                 write!(value, "P(")?;
-                func.to_proc_gen_rec_and_end_path(
-                    w,
-                    scopes,
-                    ExpressionLevel::Cond,
-                    path_calc,
-                    value,
-                )?;
+                match &**func {
+                    // inside an arrow function, a bare callee is a method of the component,
+                    // resolved in the same way as a string event handler
+                    Expression::DataField { name, .. } if w.in_arrow_func => {
+                        write!(value, "R.gm({})", gen_lit_str(name))?;
+                    }
+                    func => {
+                        func.to_proc_gen_rec_and_end_path(
+                            w,
+                            scopes,
+                            ExpressionLevel::Cond,
+                            path_calc,
+                            value,
+                        )?;
+                    }
+                }
                 write!(value, ")(")?;
                 for (i, y) in args.iter().enumerate() {
                     if i > 0 {
@@ -676,6 +738,36 @@ impl Expression {
                 PathAnalysisState::NotInPath
             }
 
+            Expression::ArrowFunc { param, body, .. } => {
+                let args = match param {
+                    Some((name, _)) => format!("$${}", name),
+                    None => String::new(),
+                };
+                w.function_args_to_string(&args, value, |w| {
+                    let mut s = String::new();
+                    let mut inner_path_calc = vec![];
+                    body.to_proc_gen_rec_and_end_path(
+                        w,
+                        scopes,
+                        ExpressionLevel::Cond,
+                        &mut inner_path_calc,
+                        &mut s,
+                    )?;
+                    w.expr_stmt(|w| {
+                        write!(w, "return {}", s)?;
+                        Ok(())
+                    })
+                })?;
+                // the closure captures values when it is created,
+                // so it should be re-created whenever any captured field changes
+                body.collect_captured_paths(scopes, path_calc);
+                PathAnalysisState::NotInPath
+            }
+            Expression::ArrowFuncParam { name, .. } => {
+                write!(value, "$${}", name)?;
+                PathAnalysisState::NotInPath
+            }
+
             Expression::Reverse { value: x, .. } => {
                 write!(value, "!")?;
                 x.to_proc_gen_rec_and_end_path(
@@ -1194,6 +1286,43 @@ impl Expression {
         Ok(path_analysis_state)
     }
 
+    fn collect_captured_paths(&self, scopes: &Vec<ScopeVar>, path_calc: &mut Vec<PathSliceList>) {
+        match self {
+            Expression::ScopeRef { index, .. } => {
+                let scope = &scopes[*index];
+                let in_path = match scope.lvalue_path {
+                    ScopeVarLvaluePath::Script { .. } | ScopeVarLvaluePath::InlineScript { .. } => {
+                        true
+                    }
+                    ScopeVarLvaluePath::Invalid | ScopeVarLvaluePath::Var { .. } => {
+                        scope.update_path_tree.is_some()
+                    }
+                };
+                if in_path {
+                    path_calc.push(PathSliceList(vec![PathSlice::ScopeIndex(*index)]));
+                }
+            }
+            Expression::DataField { name, .. } => {
+                path_calc.push(PathSliceList(vec![PathSlice::Ident(name.clone())]));
+            }
+            Expression::FuncCall { func, args, .. }
+            | Expression::OptionalFuncCall { func, args, .. } => {
+                // method callees are resolved when called, so they are not captured
+                if !matches!(&**func, Expression::DataField { .. }) {
+                    func.collect_captured_paths(scopes, path_calc);
+                }
+                for arg in args.iter() {
+                    arg.collect_captured_paths(scopes, path_calc);
+                }
+            }
+            _ => {
+                for sub in self.sub_expressions() {
+                    sub.collect_captured_paths(scopes, path_calc);
+                }
+            }
+        }
+    }
+
     pub(crate) fn to_proc_gen_prepare<W: Write>(
         &self,
         w: &mut JsFunctionScopeWriter<W>,
@@ -1311,9 +1440,15 @@ fn proc_gen_expression_level(expr: &Expression) -> ExpressionLevel {
         Expression::LitBool { .. } => ExpressionLevel::Lit,
         Expression::LitObj { .. } => ExpressionLevel::Member,
         Expression::LitArr { .. } => ExpressionLevel::Member,
+        Expression::LitTemplateStr { .. } => ExpressionLevel::Lit,
         Expression::StaticMember { .. } => ExpressionLevel::Member,
         Expression::DynamicMember { .. } => ExpressionLevel::Member,
         Expression::FuncCall { .. } => ExpressionLevel::Member,
+        Expression::OptionalStaticMember { .. } => ExpressionLevel::Member,
+        Expression::OptionalDynamicMember { .. } => ExpressionLevel::Member,
+        Expression::OptionalFuncCall { .. } => ExpressionLevel::Member,
+        Expression::ArrowFunc { .. } => ExpressionLevel::Cond,
+        Expression::ArrowFuncParam { .. } => ExpressionLevel::Lit,
         Expression::Reverse { .. } => ExpressionLevel::Unary,
         Expression::BitReverse { .. } => ExpressionLevel::Unary,
         Expression::Positive { .. } => ExpressionLevel::Unary,
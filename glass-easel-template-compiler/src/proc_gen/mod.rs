@@ -1,6 +1,10 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::fmt::Write;
 
+use sourcemap::{SourceMap, SourceMapBuilder};
+
+use crate::parse::Position;
 use crate::TmplError;
 
 mod expr;
@@ -19,6 +23,71 @@ const VAR_NAME_START_CHARS: [char; 52] = [
 ];
 const VAR_NAME_INDEX_PRESERVE: usize = 26; // 'A' ~ 'Z' are preserved
 
+// source map markers are written inline and stripped by `extract_source_map`
+// (these noncharacters are escaped wherever raw source text is written, see `escape_source_markers` )
+const SOURCE_POSITION_MARKER_START: char = '\u{FDD0}';
+const SOURCE_FILE_MARKER_START: char = '\u{FDD1}';
+const SOURCE_MARKER_END: char = '\u{FDD2}';
+
+/// Escape the source map marker characters in raw source text, e.g. inline script content.
+///
+/// They are written as `\uXXXX` , which means the same in string literals and regular expressions,
+/// so the generated code is the same with or without source maps.
+pub(crate) fn escape_source_markers(s: &str) -> Cow<'_, str> {
+    let is_marker = |c: char| ('\u{FDD0}'..='\u{FDD2}').contains(&c);
+    if !s.contains(is_marker) {
+        return Cow::Borrowed(s);
+    }
+    let mut ret = String::with_capacity(s.len() + 8);
+    for c in s.chars() {
+        if is_marker(c) {
+            write!(ret, "\\u{:04X}", c as u32).unwrap();
+        } else {
+            ret.push(c);
+        }
+    }
+    Cow::Owned(ret)
+}
+
+/// Strip the source map markers in the generated code and collect them as a source map.
+pub(crate) fn extract_source_map(code: &str) -> (String, SourceMap) {
+    let mut smb = SourceMapBuilder::new(None);
+    let mut ret = String::with_capacity(code.len());
+    let mut line = 0;
+    let mut utf16_col = 0;
+    let mut source: Option<&str> = None;
+    let mut rest = code;
+    while let Some(index) = rest.find([SOURCE_POSITION_MARKER_START, SOURCE_FILE_MARKER_START]) {
+        let (before, marker) = rest.split_at(index);
+        for c in before.chars() {
+            if c == '\n' {
+                line += 1;
+                utf16_col = 0;
+            } else {
+                utf16_col += c.len_utf16() as u32;
+            }
+        }
+        ret.push_str(before);
+        let kind = marker.chars().next().unwrap();
+        let content_start = kind.len_utf8();
+        let Some(end) = marker.find(SOURCE_MARKER_END) else {
+            rest = marker;
+            break;
+        };
+        let content = &marker[content_start..end];
+        rest = &marker[(end + SOURCE_MARKER_END.len_utf8())..];
+        if kind == SOURCE_FILE_MARKER_START {
+            source = Some(content);
+        } else if let Some((src_line, src_col)) = content.split_once(',') {
+            let src_line = src_line.parse().unwrap_or(0);
+            let src_col = src_col.parse().unwrap_or(0);
+            smb.add(line, utf16_col, src_line, src_col, source, None);
+        }
+    }
+    ret.push_str(rest);
+    (ret, smb.into_sourcemap())
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct JsIdent {
     name: String,
@@ -71,6 +140,7 @@ pub(crate) struct JsTopScopeWriter<W: fmt::Write> {
     top_declares: Vec<String>,
     sub_strs: Vec<String>,
     block: JsBlockStat,
+    source_map: bool,
 }
 
 impl<'a, W: fmt::Write> JsTopScopeWriter<W> {
@@ -80,11 +150,22 @@ impl<'a, W: fmt::Write> JsTopScopeWriter<W> {
             top_declares: vec![],
             sub_strs: vec![],
             block: JsBlockStat::new(),
+            source_map: false,
         }
     }
 
+    /// Create a writer which writes source map markers.
+    ///
+    /// The output should be processed by `extract_source_map` .
+    pub(crate) fn new_with_source_map(w: W) -> Self {
+        let mut this = Self::new(w);
+        this.source_map = true;
+        this
+    }
+
     pub(crate) fn align<WW: fmt::Write>(&mut self, w: &JsFunctionScopeWriter<'a, WW>) {
         self.block.align(w.get_block());
+        self.source_map = w.top_scope.source_map;
     }
 
     pub(crate) fn finish(self) -> W {
@@ -122,6 +203,7 @@ impl<'a, W: fmt::Write> JsTopScopeWriter<W> {
             w: &mut sub_str,
             block: None,
             top_scope: self,
+            in_arrow_func: false,
         });
         self.sub_strs.push(sub_str);
         self.block.need_stat_sep = need_stat_sep;
@@ -193,6 +275,7 @@ pub(crate) struct JsFunctionScopeWriter<'a, W: fmt::Write> {
     w: &'a mut String,
     block: Option<&'a mut JsBlockStat>,
     top_scope: &'a mut JsTopScopeWriter<W>,
+    in_arrow_func: bool,
 }
 
 fn get_var_name(mut var_id: usize) -> String {
@@ -241,6 +324,21 @@ impl<'a, W: fmt::Write> JsFunctionScopeWriter<'a, W> {
         }
     }
 
+    pub(crate) fn write_source_position(
+        &self,
+        value: &mut String,
+        pos: Position,
+    ) -> Result<(), TmplError> {
+        if self.top_scope.source_map {
+            write!(
+                value,
+                "{}{},{}{}",
+                SOURCE_POSITION_MARKER_START, pos.line, pos.utf16_col, SOURCE_MARKER_END
+            )?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn custom_stmt_str(&mut self, content: &str) -> Result<(), TmplError> {
         let block = self.get_block_mut();
         if block.need_stat_sep {
@@ -283,6 +381,24 @@ impl<'a, W: fmt::Write> JsFunctionScopeWriter<'a, W> {
         })
     }
 
+    pub(crate) fn function_args_to_string<R>(
+        &mut self,
+        args: &str,
+        s: &mut String,
+        f: impl FnOnce(&mut JsFunctionScopeWriter<W>) -> Result<R, TmplError>,
+    ) -> Result<R, TmplError> {
+        write!(s, "({})=>{{", args)?;
+        let mut block = self.get_block().extend();
+        let ret = f(&mut JsFunctionScopeWriter {
+            w: s,
+            block: Some(&mut block),
+            top_scope: &mut self.top_scope,
+            in_arrow_func: true,
+        })?;
+        write!(s, "}}")?;
+        Ok(ret)
+    }
+
     #[allow(dead_code)]
     pub(crate) fn set_var_on_top_scope(&mut self, name: &str) -> Result<(), TmplError> {
         self.top_scope.declare_on_top(name)
@@ -349,6 +465,7 @@ impl<'a, W: fmt::Write> JsExprWriter<'a, W> {
             w: self.w,
             block: Some(&mut block),
             top_scope: &mut self.top_scope,
+            in_arrow_func: false,
         })?;
         write!(&mut self.w, "}}")?;
         Ok(ret)
@@ -365,6 +482,7 @@ impl<'a, W: fmt::Write> JsExprWriter<'a, W> {
             w: self.w,
             block: Some(&mut block),
             top_scope: &mut self.top_scope,
+            in_arrow_func: false,
         })?;
         write!(&mut self.w, "}}")?;
         Ok(ret)
@@ -393,6 +511,7 @@ impl<'a, W: fmt::Write> JsExprWriter<'a, W> {
                 w: self.w,
                 block: Some(&mut block),
                 top_scope: &mut self.top_scope,
+                in_arrow_func: false,
             },
             args,
         )?;
@@ -411,12 +530,24 @@ impl<'a, W: fmt::Write> JsExprWriter<'a, W> {
             w: self.w,
             block: Some(&mut child_block),
             top_scope: &mut self.top_scope,
+            in_arrow_func: false,
         };
         let ret = f(&mut child_scope)?;
         write!(&mut self.w, "}}")?;
         Ok(ret)
     }
 
+    pub(crate) fn write_source_file(&mut self, path: &str) -> Result<(), TmplError> {
+        if self.top_scope.source_map {
+            write!(
+                &mut self.w,
+                "{}{}{}",
+                SOURCE_FILE_MARKER_START, path, SOURCE_MARKER_END
+            )?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn paren<R>(
         &mut self,
         f: impl FnOnce(&mut Self) -> Result<R, TmplError>,
@@ -1,7 +1,8 @@
-use std::{collections::HashMap, fmt::Write, ops::Range};
+use std::{collections::BTreeMap, fmt::Write, ops::Range};
 
 use super::{
-    JsExprWriter, JsFunctionScopeWriter, JsIdent, JsTopScopeWriter, ScopeVar, ScopeVarLvaluePath,
+    escape_source_markers, JsExprWriter, JsFunctionScopeWriter, JsIdent, JsTopScopeWriter,
+    ScopeVar, ScopeVarLvaluePath,
 };
 use crate::{
     binding_map::BindingMapCollector,
@@ -24,6 +25,7 @@ impl Template {
         w: &mut JsExprWriter<W>,
         group: &TmplGroup,
     ) -> Result<(), TmplError> {
+        w.write_source_file(&self.path)?;
         w.paren(|w| {
             w.function(|w| {
                 w.expr_stmt(|w| {
@@ -174,7 +176,10 @@ impl Template {
                                     write!(
                                         w,
                                         "var {}=D('{}#{}',(require,exports,module)=>{{{}}})()",
-                                        ident, &self.path, module_name.name, content
+                                        ident,
+                                        escape_source_markers(&self.path),
+                                        escape_source_markers(&module_name.name),
+                                        escape_source_markers(content),
                                     )?;
                                     Ok(())
                                 })?;
@@ -278,7 +283,7 @@ impl Node {
 
     fn to_proc_gen_define_children_content_inner<W: std::fmt::Write>(
         list: &[Self],
-        var_slot_map: Option<&HashMap<String, (JsIdent, JsIdent)>>,
+        var_slot_map: Option<&BTreeMap<String, (JsIdent, JsIdent)>>,
         w: &mut JsFunctionScopeWriter<W>,
         scopes: &mut Vec<ScopeVar>,
         bmc: &BindingMapCollector,
@@ -369,7 +374,7 @@ impl Node {
             let mut writer = JsTopScopeWriter::new(String::new());
             writer.align(w);
             writer.function_scope(|w| {
-                let mut var_slot_map: HashMap<String, (JsIdent, JsIdent)> = HashMap::new();
+                let mut var_slot_map: BTreeMap<String, (JsIdent, JsIdent)> = BTreeMap::new();
                 for slot_value_name in var_slot_names.iter() {
                     let var_scope = w.declare_var_on_top_scope_init(|w, var_scope| {
                         write!(w, "X(V).{}", slot_value_name)?;
@@ -2,8 +2,8 @@ use std::fmt::{Result as FmtResult, Write as FmtWrite};
 
 use super::stringifier::*;
 use crate::{
-    escape::gen_lit_str,
-    parse::expr::{ArrayFieldKind, Expression, ObjectFieldKind},
+    escape::{gen_lit_str, gen_lit_template_str_part},
+    parse::expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrFieldKind},
 };
 
 #[repr(u8)]
@@ -39,9 +39,15 @@ impl ExpressionLevel {
             Expression::LitBool { .. } => ExpressionLevel::Lit,
             Expression::LitObj { .. } => ExpressionLevel::Lit,
             Expression::LitArr { .. } => ExpressionLevel::Lit,
+            Expression::LitTemplateStr { .. } => ExpressionLevel::Lit,
             Expression::StaticMember { .. } => ExpressionLevel::Member,
             Expression::DynamicMember { .. } => ExpressionLevel::Member,
             Expression::FuncCall { .. } => ExpressionLevel::Member,
+            Expression::OptionalStaticMember { .. } => ExpressionLevel::Member,
+            Expression::OptionalDynamicMember { .. } => ExpressionLevel::Member,
+            Expression::OptionalFuncCall { .. } => ExpressionLevel::Member,
+            Expression::ArrowFunc { .. } => ExpressionLevel::Cond,
+            Expression::ArrowFuncParam { .. } => ExpressionLevel::Lit,
             Expression::Reverse { .. } => ExpressionLevel::Unary,
             Expression::BitReverse { .. } => ExpressionLevel::Unary,
             Expression::Positive { .. } => ExpressionLevel::Unary,
@@ -269,6 +275,58 @@ pub(super) fn expression_strigify_write<W: FmtWrite>(
             )?;
         }
 
+        Expression::LitTemplateStr {
+            fields,
+            backtick_location,
+        } => {
+            stringifier.write_token_state(
+                "`",
+                None,
+                &backtick_location.0,
+                StringifierLineState::NoSpaceAfter,
+            )?;
+            for field in fields.iter() {
+                match field {
+                    TemplateStrFieldKind::Str { value, location } => {
+                        stringifier.write_token_state(
+                            &gen_lit_template_str_part(value),
+                            None,
+                            location,
+                            StringifierLineState::NoSpaceAround,
+                        )?;
+                    }
+                    TemplateStrFieldKind::Interpolation {
+                        value,
+                        brace_location,
+                    } => {
+                        stringifier.write_token_state(
+                            "${",
+                            None,
+                            &brace_location.0,
+                            StringifierLineState::NoSpaceAround,
+                        )?;
+                        expression_strigify_write(
+                            value,
+                            stringifier,
+                            ExpressionLevel::Cond,
+                            filter,
+                        )?;
+                        stringifier.write_token_state(
+                            "}",
+                            None,
+                            &brace_location.1,
+                            StringifierLineState::NoSpaceAround,
+                        )?;
+                    }
+                }
+            }
+            stringifier.write_token_state(
+                "`",
+                None,
+                &backtick_location.1,
+                StringifierLineState::NoSpaceBefore,
+            )?;
+        }
         Expression::StaticMember {
             obj,
             field_name,
@@ -335,6 +393,133 @@ pub(super) fn expression_strigify_write<W: FmtWrite>(
             )?;
         }
 
+        Expression::OptionalStaticMember {
+            obj,
+            field_name,
+            dot_location,
+            field_location,
+        } => {
+            expression_strigify_write(obj, stringifier, ExpressionLevel::Member, filter)?;
+            stringifier.write_token_state(
+                "?.",
+                None,
+                dot_location,
+                StringifierLineState::NoSpaceAround,
+            )?;
+            stringifier.write_token_state(
+                &field_name,
+                Some(&field_name),
+                field_location,
+                StringifierLineState::Normal,
+            )?;
+        }
+        Expression::OptionalDynamicMember {
+            obj,
+            field_name,
+            dot_location,
+            bracket_location,
+        } => {
+            expression_strigify_write(obj, stringifier, ExpressionLevel::Member, filter)?;
+            stringifier.write_token_state(
+                "?.",
+                None,
+                dot_location,
+                StringifierLineState::NoSpaceAround,
+            )?;
+            stringifier.write_token_state(
+                "[",
+                None,
+                &bracket_location.0,
+                StringifierLineState::ParenCall,
+            )?;
+            expression_strigify_write(&field_name, stringifier, ExpressionLevel::Cond, filter)?;
+            stringifier.write_token_state(
+                "]",
+                None,
+                &bracket_location.1,
+                StringifierLineState::ParenEnd,
+            )?;
+        }
+        Expression::OptionalFuncCall {
+            func,
+            args,
+            dot_location,
+            paren_location,
+        } => {
+            expression_strigify_write(func, stringifier, ExpressionLevel::Member, filter)?;
+            stringifier.write_token_state(
+                "?.",
+                None,
+                dot_location,
+                StringifierLineState::NoSpaceAround,
+            )?;
+            stringifier.write_token_state(
+                "(",
+                None,
+                &paren_location.0,
+                StringifierLineState::ParenCall,
+            )?;
+            for (index, arg) in args.iter().enumerate() {
+                if index > 0 {
+                    stringifier.write_str_state(",", StringifierLineState::NoSpaceBefore)?;
+                }
+                expression_strigify_write(&arg, stringifier, ExpressionLevel::Cond, filter)?;
+            }
+            stringifier.write_token_state(
+                ")",
+                None,
+                &paren_location.1,
+                StringifierLineState::ParenEnd,
+            )?;
+        }
+        Expression::ArrowFunc {
+            param,
+            paren_location,
+            arrow_location,
+            body,
+        } => {
+            match paren_location {
+                Some(x) => stringifier.write_token_state(
+                    "(",
+                    None,
+                    &x.0,
+                    StringifierLineState::ParenStart,
+                )?,
+                None => stringifier.write_str_state("(", StringifierLineState::ParenStart)?,
+            }
+            if let Some((name, location)) = param {
+                stringifier.write_token_state(
+                    name,
+                    Some(name),
+                    location,
+                    StringifierLineState::Normal,
+                )?;
+            }
+            match paren_location {
+                Some(x) => stringifier.write_token_state(
+                    ")",
+                    None,
+                    &x.1,
+                    StringifierLineState::ParenEnd,
+                )?,
+                None => stringifier.write_str_state(")", StringifierLineState::ParenEnd)?,
+            }
+            stringifier.write_token_state(
+                "=>",
+                None,
+                arrow_location,
+                StringifierLineState::Normal,
+            )?;
+            expression_strigify_write(&body, stringifier, ExpressionLevel::Cond, filter)?;
+        }
+        Expression::ArrowFuncParam { name, location } => {
+            stringifier.write_token_state(
+                name,
+                Some(name),
+                location,
+                StringifierLineState::Normal,
+            )?;
+        }
         Expression::Reverse { value, location } => {
             stringifier.write_token_state(
                 "!",
@@ -694,6 +879,16 @@ mod tests {
         case(r#"{{ [a, b, ...c] }}"#);
     }
 
+    #[test]
+    fn lit_template_str() {
+        case(r#"{{ `a ${b + 1} c` }}"#);
+    }
+
+    #[test]
+    fn arrow_func() {
+        case(r#"<div bind:tap="{{ (e) => f(e, a) }}" />"#);
+    }
+
     #[test]
     fn paren() {
         case(r#"{{ a * (b + c) }}"#);
@@ -714,6 +909,11 @@ mod tests {
         case(r#"{{ a(b, c) }}"#);
     }
 
+    #[test]
+    fn optional_chain() {
+        case(r#"{{ a?.b?.[c]?.(d, e) }}"#);
+    }
+
     #[test]
     fn reverse() {
         case(r#"{{ !a }}"#);
@@ -1,3 +1,4 @@
+use compact_str::CompactString;
 pub use sourcemap::SourceMap;
 
 pub use options::StringifyOptions;
@@ -9,6 +10,20 @@ mod stringifier;
 mod tag;
 pub(crate) mod typescript;
 
+fn escape_typescript_keyword(
+    name: &CompactString,
+    extra_preserved: &[&'static str],
+) -> CompactString {
+    if is_typescript_keyword(name)
+        || extra_preserved.contains(&name.as_str())
+        || name.starts_with("_")
+    {
+        compact_str::format_compact!("${}", name)
+    } else {
+        name.clone()
+    }
+}
+
 fn is_typescript_keyword(s: &str) -> bool {
     const TS_KEYWORDS: [&'static str; 53] = [
         "break",
@@ -120,14 +120,7 @@ impl<'s, 't, W: FmtWrite> StringifierBlock<'s, 't, W> {
         name: &CompactString,
         extra_preserved: &[&'static str],
     ) -> CompactString {
-        let name = if super::is_typescript_keyword(name)
-            || extra_preserved.contains(&name.as_str())
-            || name.starts_with("_")
-        {
-            compact_str::format_compact!("${}", name)
-        } else {
-            name.clone()
-        };
+        let name = super::escape_typescript_keyword(name, extra_preserved);
         self.scope_names.push(name.clone());
         name
     }
@@ -156,6 +149,32 @@ impl<'s, 't, W: FmtWrite> StringifierBlock<'s, 't, W> {
         ret
     }
 
+    /// Write in a new scope space without the scopes outside, e.g. for the content of another file.
+    pub(super) fn isolated_scope_space(
+        &mut self,
+        f: impl FnOnce(&mut StringifierBlock<'s, '_, W>) -> FmtResult,
+    ) -> FmtResult {
+        let mut scope_names = vec![];
+        let mut b = StringifierBlock {
+            top: self.top,
+            indent_level: self.indent_level,
+            scope_names: &mut scope_names,
+        };
+        f(&mut b)
+    }
+
+    /// Write with the source map pointing to another source file.
+    pub(super) fn with_source_path(
+        &mut self,
+        source_path: &'s str,
+        f: impl FnOnce(&mut Self) -> FmtResult,
+    ) -> FmtResult {
+        let old = std::mem::replace(&mut self.top.source_path, source_path);
+        let ret = f(self);
+        self.top.source_path = old;
+        ret
+    }
+
     pub(super) fn write_sub_block(
         &mut self,
         f: impl FnOnce(&mut StringifierBlock<'s, '_, W>) -> FmtResult,
@@ -311,6 +330,18 @@ impl<'s, 't, 'u, W: FmtWrite> StringifierLine<'s, 't, 'u, W> {
         Ok(ret)
     }
 
+    /// Write with the source map pointing to another source file.
+    pub(super) fn with_source_path(
+        &mut self,
+        source_path: &'s str,
+        f: impl FnOnce(&mut Self) -> FmtResult,
+    ) -> FmtResult {
+        let old = std::mem::replace(&mut self.block.top.source_path, source_path);
+        let ret = f(self);
+        self.block.top.source_path = old;
+        ret
+    }
+
     pub(super) fn write_str_name_quoted(&mut self, n: &StrName) -> FmtResult {
         let quoted = escape_html_quote(&n.name);
         self.write_str("\"")?;
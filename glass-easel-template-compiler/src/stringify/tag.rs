@@ -116,6 +116,12 @@ impl Stringify for Template {
                     stringifier.write_token("name", None, &t.name_location)?;
                     stringifier.write_str(r#"="#)?;
                     stringifier.write_str_name_quoted(&t.name)?;
+                    if let Some((loc, ty)) = t.data_type.as_ref() {
+                        stringifier.write_str(" ")?;
+                        stringifier.write_token("data-type", None, loc)?;
+                        stringifier.write_str(r#"="#)?;
+                        stringifier.write_str_name_quoted(ty)?;
+                    }
                     if !t.content.is_empty() {
                         stringifier.write_str(r#">"#)?;
                         children_inline_stringify_write(
@@ -2,10 +2,23 @@ use std::fmt::{Result as FmtResult, Write as FmtWrite};
 
 use sourcemap::SourceMap;
 
+use std::collections::HashMap;
+use std::ops::Range;
+
+use compact_str::CompactString;
+use serde::Deserialize;
+
 use crate::{
     escape::dash_to_camel,
-    parse::{expr::*, tag::*, Position, Template, TemplateStructure},
+    group::TmplGroup,
+    parse::{
+        expr::*,
+        tag::*,
+        visit::{walk, Visit},
+        Position, Template, TemplateStructure,
+    },
     stringify::{
+        escape_typescript_keyword,
         expr::{expression_strigify_write, ExpressionLevel},
         Stringifier, StringifierBlock, StringifierLine, StringifierLineState, StringifyOptions,
     },
@@ -15,6 +28,7 @@ trait ConvertedExprWriteBlock {
     fn converted_expr_write<'s, 't, W: FmtWrite>(
         &self,
         w: &mut StringifierBlock<'s, 't, W>,
+        ctx: &mut ConvertContext<'s>,
     ) -> FmtResult;
 }
 
@@ -32,13 +46,56 @@ pub(crate) const fn tmpl_converted_expr_runtime_string() -> &'static str {
 type _ForIndex_<T> = T extends any[] ? number : T extends { [key: string | symbol]: any } ? string | symbol : number;
 type _ForItem_<T> = T extends (infer T)[] ? T : T extends { [key: string | symbol]: infer V } ? V : any;
 type _ForKey_<T, N extends string> = N extends "*this" ? _ForItem_<T> : _ForItem_<T> extends { [k: string]: any } ? _ForItem_<T>[N] : unknown;
+type _EventHandler_<T extends string, N extends string> = T extends keyof _TagEvents_ ? N extends keyof _TagEvents_[T] ? (event: _TagEvents_[T][N]) => any : Function : Function;
 "#
 }
 
-pub(crate) fn generate_tmpl_converted_expr(
-    tree: &Template,
+/// Declare `_TagEvents_` , which is used to check the event handlers.
+///
+/// `tag_events` maps tag names to event names to TypeScript event types,
+/// i.e. `{ "tag-name": { "event-name": "EventType" } }` .
+/// A handler of an event not in this map is only checked to be a function.
+pub(crate) fn tmpl_converted_expr_tag_events(
+    tag_events: &HashMap<String, HashMap<String, String>>,
+) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+    let mut tags: Vec<_> = tag_events.iter().collect();
+    tags.sort_by_key(|(tag_name, _)| *tag_name);
+    let mut ret = String::from("type _TagEvents_ = {");
+    for (tag_name, events) in tags {
+        let mut events: Vec<_> = events.iter().collect();
+        events.sort_by_key(|(name, _)| *name);
+        ret += &format!(" {}: {{", quote(tag_name));
+        for (name, ty) in events {
+            ret += &format!(" {}: {};", quote(name), ty);
+        }
+        ret += " };";
+    }
+    ret += " };\n";
+    ret
+}
+
+/// The TypeScript types of the custom components, used to check their attributes.
+///
+/// In JSON, it is `{ "properties": { "tag-name": "PropertyType" }, "generics": { "tag-name": { "generic-name": "PropertyType" } } }` .
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct ComponentTypes {
+    /// Maps custom component tag names to TypeScript types of their property values,
+    /// so that the attributes (including `model:` bindings) are checked against them.
+    pub(crate) properties: HashMap<String, String>,
+    /// Maps custom component tag names and their generic names to the property values passed to the generics,
+    /// which the tag given in `generic:GENERIC` should accept.
+    pub(crate) generics: HashMap<String, HashMap<String, String>>,
+}
+
+/// Convert a template to TypeScript for type checking.
+pub(crate) fn generate_tmpl_converted_expr<'a>(
+    group: &'a TmplGroup,
+    tree: &'a Template,
     ts_env: &str,
     runtime: &str,
+    component_types: &'a ComponentTypes,
 ) -> (String, SourceMap) {
     let ret = String::new();
     let options = StringifyOptions {
@@ -48,30 +105,42 @@ pub(crate) fn generate_tmpl_converted_expr(
         ..Default::default()
     };
     let mut w = Stringifier::new(ret, &tree.path, None, options);
+    let mut ctx = ConvertContext {
+        group,
+        tree,
+        include_stack: vec![tree.path.as_str()],
+        component_types,
+    };
     w.block(|w| {
         w.write_line(|w| w.write_str(ts_env))?;
         w.write_line(|w| w.write_str(runtime))?;
         for script in tree.globals.scripts.iter() {
             w.add_scope_with_ts_keyword_escape(&script.module_name().name, &PRESERVED_VAR_NAMES);
         }
-
-        // TODO tree.globals.imports
-        // TODO tree.globals.includes
-
         for tmpl in tree.globals.sub_templates.iter() {
             wrap_brace_block(w, &tmpl.tag_location, |w| {
                 let pos = tmpl.tag_location.start.0.start;
-                w.write_line(|w| {
-                    write_token_series(
+                w.write_line(|w| match tmpl.data_type.as_ref() {
+                    Some((_, ty)) => {
+                        write_token_series(
+                            ["const ", "data", "=", "0 as unknown as "],
+                            &(pos..pos),
+                            w,
+                        )?;
+                        write_data_type(ty, w)?;
+                        let pos = ty.location.end;
+                        write_token_series([";"], &(pos..pos), w)
+                    }
+                    None => write_token_series(
                         ["const ", "data", ":", "any", "=", "0", ";"],
                         &(pos..pos),
                         w,
-                    )
+                    ),
                 })?;
-                tmpl.content.converted_expr_write(w)
+                tmpl.content.converted_expr_write(w, &mut ctx)
             })?;
         }
-        tree.content.converted_expr_write(w)
+        tree.content.converted_expr_write(w, &mut ctx)
     })
     .unwrap();
 
@@ -79,13 +148,153 @@ pub(crate) fn generate_tmpl_converted_expr(
     (s, sm.unwrap())
 }
 
+/// The state shared while converting a template and the templates included by it.
+struct ConvertContext<'s> {
+    group: &'s TmplGroup,
+    /// The template being converted (may be an included one).
+    tree: &'s Template,
+    /// The paths of the templates being converted, used to avoid recursive includes.
+    include_stack: Vec<&'s str>,
+    component_types: &'s ComponentTypes,
+}
+
+impl<'s> ConvertContext<'s> {
+    /// Find the sub-template that `<template is>` refers to.
+    fn find_sub_template(&self, name: &str) -> Option<(&'s Template, &'s TemplateDefinition)> {
+        self.group
+            .find_sub_template(self.tree, name)
+            .map(|(tree, x, _)| (tree, x))
+    }
+}
+
+/// Collect the data fields used in a sub-template, with the locations where they are first used.
+///
+/// A field is required if it is read unconditionally,
+/// i.e. not in `wx:if` / `wx:for` children or in the short-circuited operands of an expression,
+/// and not tested directly like `{{ title || '' }}` .
+fn sub_template_data_fields(
+    tmpl: &TemplateDefinition,
+) -> Vec<(CompactString, Range<Position>, bool)> {
+    struct Collector {
+        fields: Vec<(CompactString, Range<Position>, bool)>,
+        conditional_depth: usize,
+    }
+    impl Collector {
+        fn visit_conditional(&mut self, f: impl FnOnce(&mut Self)) {
+            self.conditional_depth += 1;
+            f(self);
+            self.conditional_depth -= 1;
+        }
+
+        fn visit_tested_expression(&mut self, expr: &Expression) {
+            match expr {
+                Expression::DataField { .. } => {
+                    self.visit_conditional(|this| this.visit_expression(expr))
+                }
+                _ => self.visit_expression(expr),
+            }
+        }
+    }
+    impl Visit for Collector {
+        fn visit_element(&mut self, element: &Element) {
+            match &element.kind {
+                ElementKind::If {
+                    branches,
+                    else_branch,
+                } => {
+                    // only the first condition is always evaluated
+                    if let Some((_, value, _)) = branches.first() {
+                        match value {
+                            Value::Dynamic { expression, .. } => {
+                                self.visit_tested_expression(expression)
+                            }
+                            Value::Static { .. } => {}
+                        }
+                    }
+                    self.visit_conditional(|this| {
+                        for (index, (_, value, children)) in branches.iter().enumerate() {
+                            if index > 0 {
+                                this.visit_value(value);
+                            }
+                            children.iter().for_each(|x| this.visit_node(x));
+                        }
+                        if let Some((_, children)) = else_branch {
+                            children.iter().for_each(|x| this.visit_node(x));
+                        }
+                    });
+                }
+                ElementKind::For { list, children, .. } => {
+                    self.visit_value(&list.1);
+                    self.visit_conditional(|this| {
+                        children.iter().for_each(|x| this.visit_node(x));
+                    });
+                }
+                _ => walk::walk_element(self, element),
+            }
+        }
+
+        fn visit_expression(&mut self, expr: &Expression) {
+            match expr {
+                Expression::DataField { name, location } => {
+                    let required = self.conditional_depth == 0;
+                    match self.fields.iter_mut().find(|x| x.0 == *name) {
+                        Some(field) => field.2 |= required,
+                        None => self.fields.push((name.clone(), location.clone(), required)),
+                    }
+                }
+                Expression::LogicAnd { left, right, .. }
+                | Expression::LogicOr { left, right, .. }
+                | Expression::NullishCoalescing { left, right, .. } => {
+                    self.visit_tested_expression(left);
+                    self.visit_conditional(|this| this.visit_expression(right));
+                }
+                Expression::Cond {
+                    cond,
+                    true_br,
+                    false_br,
+                    ..
+                } => {
+                    self.visit_tested_expression(cond);
+                    self.visit_conditional(|this| {
+                        this.visit_expression(true_br);
+                        this.visit_expression(false_br);
+                    });
+                }
+                Expression::OptionalDynamicMember {
+                    obj, field_name, ..
+                } => {
+                    self.visit_expression(obj);
+                    self.visit_conditional(|this| this.visit_expression(field_name));
+                }
+                Expression::OptionalFuncCall { func, args, .. } => {
+                    self.visit_expression(func);
+                    self.visit_conditional(|this| {
+                        args.iter().for_each(|x| this.visit_expression(x));
+                    });
+                }
+                Expression::ArrowFunc { .. } => {
+                    self.visit_conditional(|this| walk::walk_expression(this, expr));
+                }
+                _ => walk::walk_expression(self, expr),
+            }
+        }
+    }
+    let mut collector = Collector {
+        fields: vec![],
+        conditional_depth: 0,
+    };
+    collector.visit_sub_template(tmpl);
+    collector.fields
+}
+
 impl ConvertedExprWriteBlock for Vec<Node> {
     fn converted_expr_write<'s, 't, W: FmtWrite>(
         &self,
         w: &mut StringifierBlock<'s, 't, W>,
+        ctx: &mut ConvertContext<'s>,
     ) -> FmtResult {
         for node in self {
-            node.converted_expr_write(w)?;
+            node.converted_expr_write(w, ctx)?;
         }
         Ok(())
     }
@@ -95,10 +304,11 @@ impl ConvertedExprWriteBlock for Node {
     fn converted_expr_write<'s, 't, W: FmtWrite>(
         &self,
         w: &mut StringifierBlock<'s, 't, W>,
+        ctx: &mut ConvertContext<'s>,
     ) -> FmtResult {
         match self {
             Self::Text(x) => write_dynamic_value(x, w),
-            Self::Element(x) => x.converted_expr_write(w),
+            Self::Element(x) => x.converted_expr_write(w, ctx),
             Self::Comment(_) | Self::UnknownMetaTag(_) => Ok(()),
         }
     }
@@ -124,6 +334,13 @@ fn wrap_brace_block<'s, 't, W: FmtWrite>(
     })
 }
 
+fn write_data_type<'s, 't, 'u, W: FmtWrite>(
+    ty: &StrName,
+    w: &mut StringifierLine<'s, 't, 'u, W>,
+) -> FmtResult {
+    w.write_token_state(&ty.name, None, &ty.location, StringifierLineState::Normal)
+}
+
 fn write_token_series<'s, 't, 'u, W: FmtWrite, const N: usize>(
     tokens: [&str; N],
     location: &std::ops::Range<Position>,
@@ -211,16 +428,29 @@ fn write_slot_value_refs<'s, 't, W: FmtWrite>(
     Ok(())
 }
 
+/// Write an event handler.
+///
+/// When `tag_name` is given, the handler is checked against the event type in `_TagEvents_`
+/// (see `tmpl_converted_expr_tag_events` ).
 fn write_event_method<'s, 't, W: FmtWrite>(
+    tag_name: Option<&str>,
     name: &Ident,
     value: &Option<Value>,
     w: &mut StringifierBlock<'s, 't, W>,
 ) -> FmtResult {
-    // IDEA impl event typing
     if let Some(value) = value.as_ref() {
         w.write_line(|w| {
             let pos = name.location.start;
-            write_token_series(["var ", "_event_", ":", "Function", "="], &(pos..pos), w)?;
+            write_token_series(["var ", "_event_", ":"], &(pos..pos), w)?;
+            match tag_name {
+                Some(tag_name) => write_token_series(
+                    ["_EventHandler_", "<'", tag_name, "','", &name.name, "'>"],
+                    &(pos..pos),
+                    w,
+                )?,
+                None => write_token_series(["Function"], &(pos..pos), w)?,
+            }
+            write_token_series(["="], &(pos..pos), w)?;
             if let Value::Static { value, location } = value {
                 w.write_token_state("methods", None, &(pos..pos), StringifierLineState::Normal)?;
                 w.write_token_state(".", None, &(pos..pos), StringifierLineState::Normal)?;
@@ -240,6 +470,7 @@ fn write_event_method<'s, 't, W: FmtWrite>(
 }
 
 fn write_common<'s, 't, W: FmtWrite>(
+    tag_name: Option<&str>,
     common: &CommonElementAttributes,
     w: &mut StringifierBlock<'s, 't, W>,
 ) -> FmtResult {
@@ -263,7 +494,7 @@ fn write_common<'s, 't, W: FmtWrite>(
         }
     }
     for ev in event_bindings.iter() {
-        write_event_method(&ev.name, &ev.value, w)?;
+        write_event_method(tag_name, &ev.name, &ev.value, w)?;
     }
     Ok(())
 }
@@ -272,6 +503,7 @@ impl ConvertedExprWriteBlock for Element {
     fn converted_expr_write<'s, 't, W: FmtWrite>(
         &self,
         w: &mut StringifierBlock<'s, 't, W>,
+        ctx: &mut ConvertContext<'s>,
     ) -> FmtResult {
         match &self.kind {
             ElementKind::Normal {
@@ -282,11 +514,13 @@ impl ConvertedExprWriteBlock for Element {
                 change_attributes,
                 worklet_attributes,
                 children,
-                generics: _,
+                generics,
                 extra_attr: _,
                 let_vars,
                 common,
             } => {
+                let component_types = ctx.component_types;
+                let props_type = component_types.properties.get(tag_name.name.as_str());
                 wrap_brace_block(w, &self.tag_location, |w| {
                     w.write_line(|w| {
                         write_token_series(
@@ -300,11 +534,12 @@ impl ConvertedExprWriteBlock for Element {
                             &tag_name.location,
                             StringifierLineState::Normal,
                         )?;
-                        write_token_series(
-                            ["']", ";"],
-                            &(tag_name.location.end..tag_name.location.end),
-                            w,
-                        )
+                        let pos = tag_name.location.end;
+                        write_token_series(["']"], &(pos..pos), w)?;
+                        if let Some(props_type) = props_type {
+                            write_token_series([" as unknown as ", props_type], &(pos..pos), w)?;
+                        }
+                        write_token_series([";"], &(pos..pos), w)
                     })?;
                     write_slot_value_refs(&common.slot_value_refs, w)?;
                     write_let_vars(let_vars, w)?;
@@ -386,6 +621,61 @@ impl ConvertedExprWriteBlock for Element {
                         })?;
                     }
 
+                    // `model:` bindings also write the property values back to the data fields
+                    // (member paths are converted to optional chains, which cannot be assigned)
+                    if props_type.is_some() {
+                        let model_attrs = attributes
+                            .iter()
+                            .filter(|x| matches!(x.prefix, NormalAttributePrefix::Model(_)));
+                        for attr in model_attrs {
+                            let Some(value @ Value::Dynamic { expression, .. }) = &attr.value
+                            else {
+                                continue;
+                            };
+                            if !matches!(**expression, Expression::DataField { .. }) {
+                                continue;
+                            }
+                            w.write_line(|w| {
+                                value.converted_expr_write(w)?;
+                                let name = &attr.name;
+                                let pos = name.location.start;
+                                write_token_series(["=", "_tag_", "."], &(pos..pos), w)?;
+                                w.write_token_state(
+                                    &dash_to_camel(&name.name),
+                                    Some(&name.name),
+                                    &name.location,
+                                    StringifierLineState::Normal,
+                                )?;
+                                let pos = name.location.end;
+                                write_token_series([";"], &(pos..pos), w)
+                            })?;
+                        }
+                    }
+
+                    // generic implementations
+                    for attr in generics {
+                        let expected = component_types
+                            .generics
+                            .get(tag_name.name.as_str())
+                            .and_then(|x| x.get(attr.name.name.as_str()));
+                        let (Some(expected), Some(impl_type)) = (
+                            expected,
+                            component_types.properties.get(attr.value.name.as_str()),
+                        ) else {
+                            continue;
+                        };
+                        w.write_line(|w| {
+                            let loc = attr.value.location();
+                            write_token_series(["var ", "_generic_", ":", impl_type], &loc, w)?;
+                            let pos = loc.end;
+                            write_token_series(
+                                ["=", "0 as unknown as ", expected, ";"],
+                                &(pos..pos),
+                                w,
+                            )
+                        })?;
+                    }
+
                     // class
                     match class {
                         ClassAttribute::None => {}
@@ -458,12 +748,12 @@ impl ConvertedExprWriteBlock for Element {
                             value: attr.value.name.clone(),
                             location: attr.value.location(),
                         };
-                        write_event_method(&attr.name, &Some(v), w)?;
+                        write_event_method(None, &attr.name, &Some(v), w)?;
                     }
 
                     // children
-                    write_common(common, w)?;
-                    children.converted_expr_write(w)
+                    write_common(Some(&tag_name.name), common, w)?;
+                    children.converted_expr_write(w, ctx)
                 })?;
             }
             ElementKind::Pure {
@@ -478,7 +768,7 @@ impl ConvertedExprWriteBlock for Element {
                     if let Some(x) = slot {
                         write_dynamic_value(&x.1, w)?;
                     }
-                    children.converted_expr_write(w)
+                    children.converted_expr_write(w, ctx)
                 })?;
             }
             ElementKind::For {
@@ -586,7 +876,7 @@ impl ConvertedExprWriteBlock for Element {
                             write_token_series([">", ";"], &(key.0.end..key.0.end), w)
                         })?;
                     }
-                    children.converted_expr_write(w)
+                    children.converted_expr_write(w, ctx)
                 })?;
             }
             ElementKind::If {
@@ -595,18 +885,123 @@ impl ConvertedExprWriteBlock for Element {
             } => {
                 for (_loc, cond, children) in branches {
                     write_dynamic_value(cond, w)?;
-                    children.converted_expr_write(w)?;
+                    children.converted_expr_write(w, ctx)?;
                 }
                 if let Some((_loc, children)) = else_branch {
-                    children.converted_expr_write(w)?;
+                    children.converted_expr_write(w, ctx)?;
                 }
             }
-            ElementKind::TemplateRef { target: _, data } => {
-                // IDEA impl template typing
-                write_dynamic_value(&data.1, w)?;
+            ElementKind::TemplateRef { target, data } => {
+                let sub_template = match &target.1 {
+                    Value::Static { value, .. } => ctx.find_sub_template(value),
+                    Value::Dynamic { .. } => None,
+                };
+                let is_dynamic = matches!(data.1, Value::Dynamic { .. });
+                match sub_template {
+                    Some((tree, tmpl)) if is_dynamic && tmpl.data_type.is_some() => {
+                        // check the data against the declared type
+                        let (_, ty) = tmpl.data_type.as_ref().unwrap();
+                        wrap_brace_block(w, &self.tag_location, |w| {
+                            w.write_line(|w| {
+                                write_token_series(["const ", "_tmpl_data_", "="], &data.0, w)?;
+                                data.1.converted_expr_write(w)?;
+                                let pos = data.0.end;
+                                write_token_series([";"], &(pos..pos), w)
+                            })?;
+                            w.write_line(|w| {
+                                write_token_series(["var ", "_tmpl_fields_", ":"], &data.0, w)?;
+                                w.with_source_path(&tree.path, |w| write_data_type(ty, w))?;
+                                let pos = data.0.end;
+                                write_token_series(["=", "_tmpl_data_", ";"], &(pos..pos), w)
+                            })
+                        })?;
+                    }
+                    Some((tree, tmpl))
+                        if is_dynamic && !sub_template_data_fields(tmpl).is_empty() =>
+                    {
+                        let fields = sub_template_data_fields(tmpl);
+                        // check the data against the fields used in the sub-template
+                        wrap_brace_block(w, &self.tag_location, |w| {
+                            w.write_line(|w| {
+                                write_token_series(["const ", "_tmpl_data_", "="], &data.0, w)?;
+                                data.1.converted_expr_write(w)?;
+                                let pos = data.0.end;
+                                write_token_series([";"], &(pos..pos), w)
+                            })?;
+                            w.write_line(|w| {
+                                write_token_series(
+                                    ["var ", "_tmpl_fields_", ":", "{"],
+                                    &data.0,
+                                    w,
+                                )?;
+                                w.with_source_path(&tree.path, |w| {
+                                    for (name, location, required) in fields.iter() {
+                                        w.write_token_state(
+                                            name,
+                                            Some(name),
+                                            location,
+                                            StringifierLineState::Normal,
+                                        )?;
+                                        let pos = location.end;
+                                        let colon = if *required { ":" } else { "?:" };
+                                        write_token_series(
+                                            [colon, "unknown", ";"],
+                                            &(pos..pos),
+                                            w,
+                                        )?;
+                                    }
+                                    Ok(())
+                                })?;
+                                let pos = data.0.end;
+                                write_token_series(["}", "=", "_tmpl_data_", ";"], &(pos..pos), w)
+                            })
+                        })?;
+                    }
+                    _ => {
+                        write_dynamic_value(&data.1, w)?;
+                    }
+                }
             }
-            ElementKind::Include { path: _ } => {
-                // IDEA impl include typing
+            ElementKind::Include { path } => {
+                let target = crate::path::resolve(&ctx.tree.path, &path.1.name);
+                let Ok(tree) = ctx.group.get_tree(&target) else {
+                    return Ok(());
+                };
+                if ctx.include_stack.contains(&tree.path.as_str()) {
+                    return Ok(());
+                }
+                // check the included content in the current data context
+                wrap_brace_block(w, &self.tag_location, |w| {
+                    w.with_source_path(&tree.path, |w| {
+                        w.isolated_scope_space(|w| {
+                            for script in tree.globals.scripts.iter() {
+                                let module_name = script.module_name();
+                                let var_name = w.add_scope_with_ts_keyword_escape(
+                                    &module_name.name,
+                                    &PRESERVED_VAR_NAMES,
+                                );
+                                w.write_line(|w| {
+                                    let loc = module_name.location();
+                                    write_token_series(["const "], &loc, w)?;
+                                    w.write_token_state(
+                                        &var_name,
+                                        Some(&module_name.name),
+                                        &loc,
+                                        StringifierLineState::Normal,
+                                    )?;
+                                    let pos = loc.end;
+                                    write_token_series([":", "any", "=", "0", ";"], &(pos..pos), w)
+                                })?;
+                            }
+                            let parent = std::mem::replace(&mut ctx.tree, tree);
+                            ctx.include_stack.push(&tree.path);
+                            let ret = tree.content.converted_expr_write(w, ctx);
+                            ctx.include_stack.pop();
+                            ctx.tree = parent;
+                            ret
+                        })
+                    })
+                })?;
             }
             ElementKind::Slot {
                 name: _,
@@ -621,7 +1016,7 @@ impl ConvertedExprWriteBlock for Element {
                             write_dynamic_value(value, w)?;
                         }
                     }
-                    write_common(common, w)
+                    write_common(None, common, w)
                 })?;
             }
         }
@@ -693,6 +1088,71 @@ impl ConvertedExprWriteInline for Expression {
                     }
                     Ok(false)
                 }
+                Expression::LitObj {
+                    fields,
+                    brace_location,
+                } => {
+                    // shorthand fields cannot be used since data fields are converted to `data.x`
+                    w.write_token_state(
+                        "{",
+                        None,
+                        &brace_location.0,
+                        StringifierLineState::BraceStart,
+                    )?;
+                    for (index, field) in fields.iter().enumerate() {
+                        if index > 0 {
+                            w.write_str_state(",", StringifierLineState::NoSpaceBefore)?;
+                        }
+                        match field {
+                            ObjectFieldKind::Named {
+                                name,
+                                location,
+                                colon_location,
+                                value,
+                            } => {
+                                w.write_token_state(
+                                    name,
+                                    None,
+                                    location,
+                                    StringifierLineState::Normal,
+                                )?;
+                                w.write_token_state(
+                                    ":",
+                                    None,
+                                    colon_location.as_ref().unwrap_or(location),
+                                    StringifierLineState::NoSpaceBefore,
+                                )?;
+                                expression_strigify_write(
+                                    value,
+                                    w,
+                                    ExpressionLevel::Cond,
+                                    &filter,
+                                )?;
+                            }
+                            ObjectFieldKind::Spread { location, value } => {
+                                w.write_token_state(
+                                    "...",
+                                    None,
+                                    location,
+                                    StringifierLineState::NoSpaceAfter,
+                                )?;
+                                expression_strigify_write(
+                                    value,
+                                    w,
+                                    ExpressionLevel::Cond,
+                                    &filter,
+                                )?;
+                            }
+                        }
+                    }
+                    w.write_token_state(
+                        "}",
+                        None,
+                        &brace_location.1,
+                        StringifierLineState::BraceEnd,
+                    )?;
+                    Ok(false)
+                }
                 Expression::StaticMember {
                     obj,
                     field_name,
@@ -815,9 +1275,97 @@ impl ConvertedExprWriteInline for Expression {
                     }
                     Ok(false)
                 }
+                Expression::ArrowFunc {
+                    param,
+                    paren_location,
+                    arrow_location,
+                    body,
+                } => {
+                    let (paren_start, paren_end) = match paren_location {
+                        Some((start, end)) => (start.clone(), end.clone()),
+                        None => {
+                            let pos = arrow_location.start;
+                            (pos..pos, pos..pos)
+                        }
+                    };
+                    w.write_token_state("(", None, &paren_start, StringifierLineState::ParenStart)?;
+                    if let Some((name, location)) = param {
+                        let escaped = escape_typescript_keyword(name, &PRESERVED_VAR_NAMES);
+                        w.write_token_state(
+                            &escaped,
+                            Some(name),
+                            location,
+                            StringifierLineState::Normal,
+                        )?;
+                        let pos = location.end;
+                        write_token_series([":", "any"], &(pos..pos), w)?;
+                    }
+                    w.write_token_state(")", None, &paren_end, StringifierLineState::ParenEnd)?;
+                    w.write_token_state("=>", None, arrow_location, StringifierLineState::Normal)?;
+                    expression_strigify_write(body, w, ExpressionLevel::Cond, &arrow_body_filter)?;
+                    Ok(false)
+                }
+                Expression::ArrowFuncParam { name, location } => {
+                    let escaped = escape_typescript_keyword(name, &PRESERVED_VAR_NAMES);
+                    w.write_token_state(
+                        &escaped,
+                        Some(name),
+                        location,
+                        StringifierLineState::Normal,
+                    )?;
+                    Ok(false)
+                }
                 _ => Ok(true),
             }
         }
+        fn arrow_body_filter<'s, 't, 'u, W: FmtWrite>(
+            expr: &Expression,
+            w: &mut StringifierLine<'s, 't, 'u, W>,
+            accept_level: ExpressionLevel,
+        ) -> Result<bool, std::fmt::Error> {
+            // a bare callee inside an arrow function is a method of the component
+            let (func, args, paren_location, call_token) = match expr {
+                Expression::FuncCall {
+                    func,
+                    args,
+                    paren_location,
+                } => (func, args, paren_location, "("),
+                Expression::OptionalFuncCall {
+                    func,
+                    args,
+                    paren_location,
+                    ..
+                } => (func, args, paren_location, "?.("),
+                _ => return filter(expr, w, accept_level),
+            };
+            let Expression::DataField { name, location } = &**func else {
+                return filter(expr, w, accept_level);
+            };
+            if ExpressionLevel::Member > accept_level {
+                w.write_token_state("(", None, location, StringifierLineState::ParenStart)?;
+            }
+            w.write_token_state("methods", None, location, StringifierLineState::Normal)?;
+            w.write_token_state(".", None, location, StringifierLineState::NoSpaceAround)?;
+            w.write_token_state(name, Some(name), location, StringifierLineState::Normal)?;
+            w.write_token_state(
+                call_token,
+                None,
+                &paren_location.0,
+                StringifierLineState::ParenCall,
+            )?;
+            for (index, arg) in args.iter().enumerate() {
+                if index > 0 {
+                    w.write_str_state(",", StringifierLineState::NoSpaceBefore)?;
+                }
+                expression_strigify_write(&arg, w, ExpressionLevel::Cond, &arrow_body_filter)?;
+            }
+            w.write_token_state(")", None, &paren_location.1, StringifierLineState::ParenEnd)?;
+            if ExpressionLevel::Member > accept_level {
+                let pos = paren_location.1.end;
+                w.write_token_state(")", None, &(pos..pos), StringifierLineState::ParenEnd)?;
+            }
+            Ok(false)
+        }
         expression_strigify_write(self, w, ExpressionLevel::Cond, &filter)
     }
 }
@@ -827,9 +1375,47 @@ mod test {
     use super::*;
 
     fn convert(src: &str) -> (String, SourceMap) {
+        convert_with_deps(src, &[])
+    }
+
+    fn convert_with_deps(src: &str, deps: &[(&str, &str)]) -> (String, SourceMap) {
+        convert_with_options(src, deps, &ComponentTypes::default())
+    }
+
+    fn convert_with_props(src: &str, props: &[(&str, &str)]) -> (String, SourceMap) {
+        let component_types = ComponentTypes {
+            properties: props
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        convert_with_options(src, &[], &component_types)
+    }
+
+    fn convert_with_options(
+        src: &str,
+        deps: &[(&str, &str)],
+        component_types: &ComponentTypes,
+    ) -> (String, SourceMap) {
         let mut group = crate::TmplGroup::new();
         group.add_tmpl("TEST", src);
-        generate_tmpl_converted_expr(group.get_tree("TEST").unwrap(), "", "")
+        for (path, src) in deps {
+            group.add_tmpl(path, src);
+        }
+        generate_tmpl_converted_expr(
+            &group,
+            group.get_tree("TEST").unwrap(),
+            "",
+            "",
+            component_types,
+        )
+    }
+
+    fn find_token_in_source(sm: &SourceMap, line: u32, col: u32) -> Option<(&str, u32, u32)> {
+        let token = sm.lookup_token(line, col)?;
+        let (src_line, src_col) = token.get_src();
+        Some((token.get_source()?, src_line, src_col))
     }
 
     fn find_token(sm: &SourceMap, line: u32, col: u32) -> Option<(u32, u32)> {
@@ -921,6 +1507,32 @@ mod test {
         assert_eq!(find_token(&sm, 0, 51), Some((0, 26)));
     }
 
+    #[test]
+    fn custom_component_props() {
+        let src = r#"<comp a="{{ x }}" model:b="{{ y }}" model:c="{{ 1 }}" />"#;
+        let expect = r#"{const _tag_=tags['comp'] as unknown as P;_tag_.a=data.x;_tag_.b=data.y;_tag_.c=1;data.y=_tag_.b;}"#;
+        let (out, sm) = convert_with_props(src, &[("comp", "P")]);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 47), Some((0, 6)));
+        assert_eq!(find_token(&sm, 0, 95), Some((0, 24)));
+        let (out, _) = convert_with_props(src, &[]);
+        assert!(!out.contains("as unknown as"));
+    }
+
+    #[test]
+    fn generic_implementation() {
+        let src = r#"<comp generic:item="impl" generic:other="impl" />"#;
+        let expect =
+            r#"{const _tag_=tags['comp'] as unknown as P;var _generic_:I=0 as unknown as E;}"#;
+        let component_types: ComponentTypes = serde_json::from_str(
+            r#"{ "properties": { "comp": "P", "impl": "I" }, "generics": { "comp": { "item": "E" } } }"#,
+        )
+        .unwrap();
+        let (out, sm) = convert_with_options(src, &[], &component_types);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 46), Some((0, 20)));
+    }
+
     #[test]
     fn expr_static_field() {
         let src = r#"{{ obj.a }}"#;
@@ -958,6 +1570,34 @@ mod test {
         assert_eq!(find_token(&sm, 0, 17), Some((0, 8)));
     }
 
+    #[test]
+    fn expr_optional_chain() {
+        let src = r#"{{ obj?.a?.[b]?.(c) }}"#;
+        let expect = r#"data.obj?.a?.[data.b]?.(data.c);"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 5), Some((0, 3)));
+        assert_eq!(find_token(&sm, 0, 8), Some((0, 6)));
+        assert_eq!(find_token(&sm, 0, 10), Some((0, 8)));
+        assert_eq!(find_token(&sm, 0, 11), Some((0, 9)));
+        assert_eq!(find_token(&sm, 0, 13), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 14), Some((0, 12)));
+    }
+
+    #[test]
+    fn expr_template_str() {
+        let src = r#"{{ `a${ b }c` }}"#;
+        let expect = r#"`a${data.b}c`;"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 0), Some((0, 3)));
+        assert_eq!(find_token(&sm, 0, 1), Some((0, 4)));
+        assert_eq!(find_token(&sm, 0, 2), Some((0, 5)));
+        assert_eq!(find_token(&sm, 0, 4), Some((0, 8)));
+        assert_eq!(find_token(&sm, 0, 10), Some((0, 10)));
+        assert_eq!(find_token(&sm, 0, 11), Some((0, 11)));
+    }
+
     #[test]
     fn element_class_single() {
         let src = r#"<view class="a {{ b }}" />"#;
@@ -1045,14 +1685,66 @@ mod test {
     #[test]
     fn element_event() {
         let src = r#"<view bind:a="b" />"#;
-        let expect = r#"{const _tag_=tags['view'];var _event_:Function=methods.b;}"#;
+        let expect =
+            r#"{const _tag_=tags['view'];var _event_:_EventHandler_<'view','a'>=methods.b;}"#;
         let (out, sm) = convert(src);
         assert_eq!(out, expect);
         assert_eq!(find_token(&sm, 0, 26), Some((0, 11)));
         assert_eq!(find_token(&sm, 0, 30), Some((0, 11)));
         assert_eq!(find_token(&sm, 0, 38), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 47), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 55), Some((0, 14)));
+        assert_eq!(find_token(&sm, 0, 65), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 73), Some((0, 14)));
+    }
+
+    #[test]
+    fn element_event_arrow_func() {
+        let src = r#"<view bind:a="{{ (data) => b(data, c) }}" />"#;
+        let expect = r#"{const _tag_=tags['view'];var _event_:_EventHandler_<'view','a'>=($data:any)=>methods.b($data,data.c);}"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 65), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 66), Some((0, 18)));
+        assert_eq!(find_token(&sm, 0, 76), Some((0, 24)));
+        assert_eq!(find_token(&sm, 0, 78), Some((0, 27)));
+        assert_eq!(find_token(&sm, 0, 88), Some((0, 29)));
+    }
+
+    #[test]
+    fn custom_component_event() {
+        let src = r#"<custom-comp capture-catch:change="onChange" /><slot bind:a="b" />"#;
+        let expect = r#"{const _tag_=tags['custom-comp'];var _event_:_EventHandler_<'custom-comp','change'>=methods.onChange;}{var _event_:Function=methods.b;}"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 40), Some((0, 27)));
+        assert_eq!(find_token(&sm, 0, 92), Some((0, 35)));
+    }
+
+    #[test]
+    fn event_handler_types() {
+        let src = r#"<input bind:input="onInput" bind:other="onOther" />"#;
+        let mut group = crate::TmplGroup::new();
+        group.add_tmpl("TEST", src);
+        let mut events = HashMap::new();
+        events.insert(
+            "input".to_string(),
+            "{ detail: { value: number } }".to_string(),
+        );
+        let mut tag_events = HashMap::new();
+        tag_events.insert("input".to_string(), events);
+        let (out, _) = group
+            .get_tmpl_converted_expr("TEST", "", &ComponentTypes::default(), &tag_events)
+            .unwrap();
+        // a handler expecting `{ detail: { value: string } }` is checked against `_TagEvents_['input']['input']`
+        assert!(out.contains(
+            "type _TagEvents_ = { 'input': { 'input': { detail: { value: number } }; }; };\n"
+        ));
+        assert!(out.contains(
+            "var _event_:_EventHandler_<'input','input'>=methods.onInput;var _event_:_EventHandler_<'input','other'>=methods.onOther;"
+        ));
+        let (out, _) = group
+            .get_tmpl_converted_expr("TEST", "", &ComponentTypes::default(), &HashMap::new())
+            .unwrap();
+        assert!(out.contains("type _TagEvents_ = { };\n"));
     }
 
     #[test]
@@ -1239,4 +1931,77 @@ mod test {
         assert_eq!(find_token(&sm, 0, 18), Some((0, 22)));
         assert_eq!(find_token(&sm, 0, 23), Some((0, 22)));
     }
+
+    #[test]
+    fn typed_sub_template_definition() {
+        let src = r#"<template name="a" data-type="{ b: string }">{{ b }}</template>"#;
+        let expect = r#"{const data=0 as unknown as { b: string };data.b;}"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 28), Some((0, 30)));
+        assert_eq!(find_token(&sm, 0, 46), Some((0, 48)));
+    }
+
+    #[test]
+    fn typed_template_ref() {
+        let src = r#"<import src="a" /><template is="t" data="{{ x: 1 }}" />"#;
+        let a = r#"<template name="t" data-type="{ x: number }">{{ x }}</template>"#;
+        let expect = r#"{const _tmpl_data_={x:1};var _tmpl_fields_:{ x: number }=_tmpl_data_;}"#;
+        let (out, sm) = convert_with_deps(src, &[("a", a)]);
+        assert_eq!(out, expect);
+        assert_eq!(find_token_in_source(&sm, 0, 43), Some(("a", 0, 30)));
+        assert_eq!(find_token_in_source(&sm, 0, 56), Some(("TEST", 0, 39)));
+    }
+
+    #[test]
+    fn imported_template_ref() {
+        let src = r#"<import src="a" /><template is="t" data="{{ x: 1, w }}" />"#;
+        let a = r#"<template name="t">{{ x }}{{ y.z }}{{ x }}</template>"#;
+        let expect = r#"{const _tmpl_data_={x:1,w:data.w};var _tmpl_fields_:{x:unknown;y:unknown;}=_tmpl_data_;}"#;
+        let (out, sm) = convert_with_deps(src, &[("a", a)]);
+        assert_eq!(out, expect);
+        assert_eq!(find_token_in_source(&sm, 0, 1), Some(("TEST", 0, 35)));
+        assert_eq!(find_token_in_source(&sm, 0, 53), Some(("a", 0, 22)));
+        assert_eq!(find_token_in_source(&sm, 0, 63), Some(("a", 0, 29)));
+        assert_eq!(find_token_in_source(&sm, 0, 73), Some(("TEST", 0, 39)));
+    }
+
+    #[test]
+    fn imported_template_ref_optional_fields() {
+        let src = r#"<import src="a" /><template is="t" data="{{ x: 1 }}" />"#;
+        let a = r#"<template name="t">{{ title || '' }}{{ x ? y : z }}{{ u.name }}<view wx:if="{{ w }}">{{ v }}</view></template>"#;
+        let (out, _) = convert_with_deps(src, &[("a", a)]);
+        assert!(out.contains(
+            r#"var _tmpl_fields_:{title?:unknown;x?:unknown;y?:unknown;z?:unknown;u:unknown;w?:unknown;v?:unknown;}=_tmpl_data_;"#
+        ));
+        let a = r#"<template name="t">{{ x }}<view wx:for="{{ list }}">{{ title || x }}</view></template>"#;
+        let (out, _) = convert_with_deps(src, &[("a", a)]);
+        assert!(out.contains(
+            r#"var _tmpl_fields_:{x:unknown;list:unknown;title?:unknown;}=_tmpl_data_;"#
+        ));
+    }
+
+    #[test]
+    fn local_template_ref_takes_precedence() {
+        let src = r#"<import src="a" /><template name="t">{{ y }}</template><template is="t" data="{{ x }}" />"#;
+        let a = r#"<template name="t">{{ x }}</template>"#;
+        let (out, _) = convert_with_deps(src, &[("a", a)]);
+        assert!(out.contains(r#"var _tmpl_fields_:{y:unknown;}=_tmpl_data_;"#));
+        let (out, _) = convert_with_deps(r#"<template is="t" data="{{ x }}" />"#, &[]);
+        assert_eq!(out, r#"{x:data.x};"#);
+    }
+
+    #[test]
+    fn included_template() {
+        let src = r#"<include src="b" /><view>{{ a }}</view>"#;
+        let b = r#"<view wx:for="{{ list }}">{{ item }}</view><include src="TEST" />"#;
+        let expect = r#"{{const _for_=data.list;const item=0 as unknown as _ForItem_<typeof _for_>;const index=0 as unknown as _ForIndex_<typeof _for_>;{const _tag_=tags['view'];item;}}}{const _tag_=tags['view'];data.a;}"#;
+        let (out, sm) = convert_with_deps(src, &[("b", b)]);
+        assert_eq!(out, expect);
+        assert_eq!(find_token_in_source(&sm, 0, 0), Some(("TEST", 0, 0)));
+        assert_eq!(find_token_in_source(&sm, 0, 1), Some(("b", 0, 0)));
+        assert_eq!(find_token_in_source(&sm, 0, 154), Some(("b", 0, 29)));
+        assert_eq!(find_token_in_source(&sm, 0, 162), Some(("TEST", 0, 19)));
+        assert_eq!(find_token_in_source(&sm, 0, 188), Some(("TEST", 0, 28)));
+    }
 }
@@ -0,0 +1,1168 @@
+//! Syntax checks for WXS (inline and external script modules)
+//!
+//! The WXS runtime only supports ES5.
+//! The script is tokenized first to report broken tokens and unmatched brackets,
+//! and to flag the unsupported ES6+ syntax.
+//! If nothing is found, it is then checked by a recursive-descent ES5 parser,
+//! which reports the first syntax error (without building any syntax tree).
+
+use std::ops::Range;
+
+use crate::group::{TmplError, TmplGroup};
+use crate::parse::{tag::Script, ParseError, ParseErrorKind, Position};
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind<'a> {
+    Ident(&'a str),
+    Punct(&'static str),
+    Literal,
+}
+
+#[derive(Debug, Clone)]
+struct Token<'a> {
+    kind: TokenKind<'a>,
+    location: Range<Position>,
+}
+
+const PUNCTUATORS: [&str; 54] = [
+    ">>>=", "...", "===", "!==", "**=", "<<=", ">>=", ">>>", "=>", "==", "!=", "<=", ">=", "&&",
+    "||", "??", "?.", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<", ">>", "**",
+    "{", "}", "(", ")", "[", "]", ";", ",", "<", ">", "+", "-", "*", "/", "%", "&", "|", "^", "!",
+    "~", "?", ":", "=", ".",
+];
+
+/// Punctuators which are not ES5.
+const UNSUPPORTED_PUNCTUATORS: [&str; 6] = ["=>", "...", "?.", "??", "**", "**="];
+
+/// Keywords after which a `/` starts a regular expression rather than a division.
+const REGEXP_PRECEDING_KEYWORDS: [&str; 12] = [
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+];
+
+/// Keywords whose head is parenthesized, e.g. `if (a)` , so a `/` after the `)` starts a regular expression.
+const PAREN_HEAD_KEYWORDS: [&str; 4] = ["if", "while", "for", "with"];
+
+struct Tokenizer<'a> {
+    src: &'a str,
+    offset: usize,
+    pos: Position,
+    tokens: Vec<Token<'a>>,
+    errors: Vec<(ParseErrorKind, Range<Position>)>,
+    /// For each unclosed `(` , whether it opens the head of an `if` / `while` / `for` / `with` .
+    paren_heads: Vec<bool>,
+    /// Whether the last `)` closes the head of an `if` / `while` / `for` / `with` .
+    closed_paren_head: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.offset..].chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.src[self.offset..].chars().nth(n)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.utf16_col = 0;
+        } else {
+            self.pos.utf16_col += c.len_utf16() as u32;
+        }
+        Some(c)
+    }
+
+    fn error(&mut self, kind: ParseErrorKind, location: Range<Position>) {
+        self.errors.push((kind, location));
+    }
+
+    fn regexp_allowed(&self) -> bool {
+        match self.tokens.last().map(|x| &x.kind) {
+            None => true,
+            Some(TokenKind::Literal) => false,
+            Some(TokenKind::Ident(x)) => REGEXP_PRECEDING_KEYWORDS.contains(x),
+            Some(TokenKind::Punct(")")) => self.closed_paren_head,
+            Some(TokenKind::Punct(x)) => !matches!(*x, "]" | "++" | "--"),
+        }
+    }
+
+    fn run(&mut self) {
+        while let Some(c) = self.peek() {
+            let start = self.pos;
+            let start_offset = self.offset;
+            if c.is_whitespace() || c == '\u{feff}' {
+                self.next();
+            } else if c == '/' && self.peek_nth(1) == Some('/') {
+                while self.peek().map(|c| c != '\n').unwrap_or(false) {
+                    self.next();
+                }
+            } else if c == '/' && self.peek_nth(1) == Some('*') {
+                self.next();
+                self.next();
+                loop {
+                    match self.next() {
+                        None => {
+                            self.error(ParseErrorKind::IllegalScriptSyntax, start..self.pos);
+                            break;
+                        }
+                        Some('*') if self.peek() == Some('/') => {
+                            self.next();
+                            break;
+                        }
+                        Some(_) => {}
+                    }
+                }
+            } else if c == '\'' || c == '"' {
+                self.next();
+                self.skip_quoted(c, start);
+                self.push(TokenKind::Literal, start);
+            } else if c == '`' {
+                self.next();
+                self.skip_quoted(c, start);
+                self.error(ParseErrorKind::UnsupportedScriptFeature, start..self.pos);
+                self.push(TokenKind::Literal, start);
+            } else if c.is_ascii_digit()
+                || (c == '.'
+                    && self
+                        .peek_nth(1)
+                        .map(|c| c.is_ascii_digit())
+                        .unwrap_or(false))
+            {
+                self.next();
+                let is_hex = self.src[start_offset..].starts_with("0x")
+                    || self.src[start_offset..].starts_with("0X");
+                while let Some(c) = self.peek() {
+                    // the sign of an exponent, e.g. `1e-3`
+                    let is_exp_sign = (c == '+' || c == '-')
+                        && !is_hex
+                        && matches!(self.src[..self.offset].chars().last(), Some('e' | 'E'));
+                    if c.is_ascii_alphanumeric() || c == '.' || c == '_' || is_exp_sign {
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+                self.push(TokenKind::Literal, start);
+            } else if c == '/' && self.regexp_allowed() {
+                self.next();
+                self.skip_regexp(start);
+                self.push(TokenKind::Literal, start);
+            } else if is_ident_start(c) {
+                while self.peek().map(is_ident_char).unwrap_or(false) {
+                    self.next();
+                }
+                let name = &self.src[start_offset..self.offset];
+                self.push(TokenKind::Ident(name), start);
+            } else if let Some(mut p) = PUNCTUATORS
+                .iter()
+                .find(|p| self.src[self.offset..].starts_with(*p))
+                .copied()
+            {
+                // `?.` followed by a digit is a conditional operator, e.g. `a?.5:1`
+                if p == "?."
+                    && self
+                        .peek_nth(2)
+                        .map(|c| c.is_ascii_digit())
+                        .unwrap_or(false)
+                {
+                    p = "?";
+                }
+                for _ in 0..p.len() {
+                    self.next();
+                }
+                if UNSUPPORTED_PUNCTUATORS.contains(&p) {
+                    self.error(ParseErrorKind::UnsupportedScriptFeature, start..self.pos);
+                }
+                self.push(TokenKind::Punct(p), start);
+            } else {
+                self.next();
+                self.error(ParseErrorKind::IllegalScriptSyntax, start..self.pos);
+            }
+        }
+    }
+
+    fn push(&mut self, kind: TokenKind<'a>, start: Position) {
+        match kind {
+            TokenKind::Punct("(") => {
+                let mut rev = self.tokens.iter().rev().map(|x| &x.kind);
+                let is_head = match (rev.next(), rev.next()) {
+                    (Some(TokenKind::Ident(x)), prev) => {
+                        PAREN_HEAD_KEYWORDS.contains(x) && prev != Some(&TokenKind::Punct("."))
+                    }
+                    _ => false,
+                };
+                self.paren_heads.push(is_head);
+            }
+            TokenKind::Punct(")") => {
+                self.closed_paren_head = self.paren_heads.pop().unwrap_or(false);
+            }
+            _ => {}
+        }
+        self.tokens.push(Token {
+            kind,
+            location: start..self.pos,
+        });
+    }
+
+    fn skip_quoted(&mut self, quote: char, start: Position) {
+        loop {
+            match self.peek() {
+                None => break,
+                Some('\n') if quote != '`' => break,
+                Some('\\') => {
+                    self.next();
+                    self.next();
+                }
+                Some(c) => {
+                    self.next();
+                    if c == quote {
+                        return;
+                    }
+                }
+            }
+        }
+        self.error(ParseErrorKind::IllegalScriptSyntax, start..self.pos);
+    }
+
+    fn skip_regexp(&mut self, start: Position) {
+        let mut in_class = false;
+        loop {
+            match self.peek() {
+                None | Some('\n') => {
+                    self.error(ParseErrorKind::IllegalScriptSyntax, start..self.pos);
+                    return;
+                }
+                Some('\\') => {
+                    self.next();
+                    if self.peek() != Some('\n') {
+                        self.next();
+                    }
+                }
+                Some(c) => {
+                    self.next();
+                    match c {
+                        '[' => in_class = true,
+                        ']' => in_class = false,
+                        '/' if !in_class => break,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        while self.peek().map(is_ident_char).unwrap_or(false) {
+            self.next();
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c == '$' || c.is_alphabetic()
+}
+
+fn is_ident_char(c: char) -> bool {
+    c == '_' || c == '$' || c.is_alphanumeric()
+}
+
+fn check_brackets(tokens: &[Token], errors: &mut Vec<(ParseErrorKind, Range<Position>)>) {
+    let mut stack: Vec<&Token> = vec![];
+    for token in tokens {
+        let TokenKind::Punct(p) = token.kind else {
+            continue;
+        };
+        let open = match p {
+            "(" | "[" | "{" => {
+                stack.push(token);
+                continue;
+            }
+            ")" => "(",
+            "]" => "[",
+            "}" => "{",
+            _ => continue,
+        };
+        match stack.last() {
+            Some(x) if x.kind == TokenKind::Punct(open) => {
+                stack.pop();
+            }
+            _ => {
+                errors.push((ParseErrorKind::IllegalScriptSyntax, token.location.clone()));
+                return;
+            }
+        }
+    }
+    if let Some(x) = stack.pop() {
+        errors.push((ParseErrorKind::IllegalScriptSyntax, x.location.clone()));
+    }
+}
+
+fn check_keywords(tokens: &[Token], errors: &mut Vec<(ParseErrorKind, Range<Position>)>) {
+    for (i, token) in tokens.iter().enumerate() {
+        let TokenKind::Ident(name) = token.kind else {
+            continue;
+        };
+        let prev = i.checked_sub(1).map(|i| &tokens[i].kind);
+        let next = tokens.get(i + 1).map(|x| &x.kind);
+        // property names such as `a.class` and `{ class: 1 }`
+        if prev == Some(&TokenKind::Punct(".")) || next == Some(&TokenKind::Punct(":")) {
+            continue;
+        }
+        let unsupported = match name {
+            "class" | "const" => true,
+            "let" => matches!(
+                next,
+                Some(TokenKind::Ident(_)) | Some(TokenKind::Punct("[" | "{"))
+            ),
+            "async" => matches!(next, Some(TokenKind::Ident(_))),
+            _ => false,
+        };
+        if unsupported {
+            errors.push((
+                ParseErrorKind::UnsupportedScriptFeature,
+                token.location.clone(),
+            ));
+        }
+    }
+}
+
+/// Words which cannot be used as identifiers in ES5.
+const RESERVED_WORDS: [&str; 36] = [
+    "break",
+    "case",
+    "catch",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "in",
+    "instanceof",
+    "new",
+    "return",
+    "switch",
+    "this",
+    "throw",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "null",
+    "true",
+    "false",
+    "class",
+    "const",
+    "enum",
+    "export",
+    "extends",
+    "import",
+    "super",
+];
+
+const ASSIGNMENT_OPERATORS: [&str; 12] = [
+    "=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", ">>>=", "&=", "|=", "^=",
+];
+
+fn binary_precedence(kind: &TokenKind, no_in: bool) -> Option<u8> {
+    let ret = match kind {
+        TokenKind::Punct(p) => match *p {
+            "||" => 1,
+            "&&" => 2,
+            "|" => 3,
+            "^" => 4,
+            "&" => 5,
+            "==" | "!=" | "===" | "!==" => 6,
+            "<" | ">" | "<=" | ">=" => 7,
+            "<<" | ">>" | ">>>" => 8,
+            "+" | "-" => 9,
+            "*" | "/" | "%" => 10,
+            _ => return None,
+        },
+        TokenKind::Ident("instanceof") => 7,
+        TokenKind::Ident("in") if !no_in => 7,
+        _ => return None,
+    };
+    Some(ret)
+}
+
+/// The result of parsing, with the location of the unexpected token as the error.
+type SyntaxResult<T> = Result<T, Range<Position>>;
+
+/// A recursive-descent parser for ES5 programs.
+///
+/// The expression parsing methods return whether the expression can be assigned to.
+struct Parser<'a, 'b> {
+    tokens: &'b [Token<'a>],
+    index: usize,
+    end: Position,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn peek_kind(&self) -> Option<&'b TokenKind<'a>> {
+        self.tokens.get(self.index).map(|x| &x.kind)
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self.peek_kind(), Some(TokenKind::Punct(x)) if *x == p)
+    }
+
+    fn is_ident(&self, name: &str) -> bool {
+        matches!(self.peek_kind(), Some(TokenKind::Ident(x)) if *x == name)
+    }
+
+    fn eat_punct(&mut self, p: &str) -> bool {
+        let ret = self.is_punct(p);
+        if ret {
+            self.index += 1;
+        }
+        ret
+    }
+
+    fn eat_ident(&mut self, name: &str) -> bool {
+        let ret = self.is_ident(name);
+        if ret {
+            self.index += 1;
+        }
+        ret
+    }
+
+    /// The location of the current token (or the end of the script).
+    fn unexpected(&self) -> Range<Position> {
+        match self.tokens.get(self.index) {
+            Some(x) => x.location.clone(),
+            None => self.end..self.end,
+        }
+    }
+
+    fn expect_punct(&mut self, p: &str) -> SyntaxResult<()> {
+        if self.eat_punct(p) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn expect_ident(&mut self, name: &str) -> SyntaxResult<()> {
+        if self.eat_ident(name) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Whether there is a line break between the previous token and the current token.
+    fn newline_before(&self) -> bool {
+        let (Some(prev), Some(cur)) = (
+            self.index.checked_sub(1).and_then(|i| self.tokens.get(i)),
+            self.tokens.get(self.index),
+        ) else {
+            return false;
+        };
+        cur.location.start.line > prev.location.end.line
+    }
+
+    /// Consume a `;` or insert one automatically.
+    fn consume_semicolon(&mut self) -> SyntaxResult<()> {
+        if self.eat_punct(";")
+            || self.is_punct("}")
+            || self.peek_kind().is_none()
+            || self.newline_before()
+        {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn binding_identifier(&mut self) -> SyntaxResult<()> {
+        match self.peek_kind() {
+            Some(TokenKind::Ident(name)) if !RESERVED_WORDS.contains(name) => {
+                self.index += 1;
+                Ok(())
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_program(&mut self) -> SyntaxResult<()> {
+        while self.peek_kind().is_some() {
+            self.parse_statement()?;
+        }
+        Ok(())
+    }
+
+    fn parse_block(&mut self) -> SyntaxResult<()> {
+        self.expect_punct("{")?;
+        while self.peek_kind().is_some() && !self.is_punct("}") {
+            self.parse_statement()?;
+        }
+        self.expect_punct("}")
+    }
+
+    fn parse_statement(&mut self) -> SyntaxResult<()> {
+        let Some(kind) = self.peek_kind() else {
+            return Err(self.unexpected());
+        };
+        match kind {
+            TokenKind::Punct("{") => self.parse_block(),
+            TokenKind::Punct(";") => {
+                self.index += 1;
+                Ok(())
+            }
+            TokenKind::Ident("var") => {
+                self.index += 1;
+                self.parse_variable_declarations(false)?;
+                self.consume_semicolon()
+            }
+            TokenKind::Ident("if") => {
+                self.index += 1;
+                self.parse_paren_expression()?;
+                self.parse_statement()?;
+                if self.eat_ident("else") {
+                    self.parse_statement()?;
+                }
+                Ok(())
+            }
+            TokenKind::Ident("do") => {
+                self.index += 1;
+                self.parse_statement()?;
+                self.expect_ident("while")?;
+                self.parse_paren_expression()?;
+                self.eat_punct(";");
+                Ok(())
+            }
+            TokenKind::Ident("while" | "with") => {
+                self.index += 1;
+                self.parse_paren_expression()?;
+                self.parse_statement()
+            }
+            TokenKind::Ident("for") => {
+                self.index += 1;
+                self.parse_for_statement()
+            }
+            TokenKind::Ident("continue" | "break") => {
+                self.index += 1;
+                if !self.newline_before() {
+                    if let Some(TokenKind::Ident(name)) = self.peek_kind() {
+                        if !RESERVED_WORDS.contains(name) {
+                            self.index += 1;
+                        }
+                    }
+                }
+                self.consume_semicolon()
+            }
+            TokenKind::Ident("return") => {
+                self.index += 1;
+                if !self.newline_before()
+                    && self.peek_kind().is_some()
+                    && !self.is_punct(";")
+                    && !self.is_punct("}")
+                {
+                    self.parse_expression(false)?;
+                }
+                self.consume_semicolon()
+            }
+            TokenKind::Ident("throw") => {
+                self.index += 1;
+                if self.newline_before() {
+                    return Err(self.unexpected());
+                }
+                self.parse_expression(false)?;
+                self.consume_semicolon()
+            }
+            TokenKind::Ident("switch") => {
+                self.index += 1;
+                self.parse_switch_body()
+            }
+            TokenKind::Ident("try") => {
+                self.index += 1;
+                self.parse_block()?;
+                let mut handled = false;
+                if self.eat_ident("catch") {
+                    self.expect_punct("(")?;
+                    self.binding_identifier()?;
+                    self.expect_punct(")")?;
+                    self.parse_block()?;
+                    handled = true;
+                }
+                if self.eat_ident("finally") {
+                    self.parse_block()?;
+                    handled = true;
+                }
+                if !handled {
+                    return Err(self.unexpected());
+                }
+                Ok(())
+            }
+            TokenKind::Ident("debugger") => {
+                self.index += 1;
+                self.consume_semicolon()
+            }
+            TokenKind::Ident("function") => {
+                self.index += 1;
+                self.binding_identifier()?;
+                self.parse_function_rest()
+            }
+            TokenKind::Ident(name)
+                if !RESERVED_WORDS.contains(name)
+                    && matches!(
+                        self.tokens.get(self.index + 1).map(|x| &x.kind),
+                        Some(TokenKind::Punct(":"))
+                    ) =>
+            {
+                // labelled statement
+                self.index += 2;
+                self.parse_statement()
+            }
+            _ => {
+                self.parse_expression(false)?;
+                self.consume_semicolon()
+            }
+        }
+    }
+
+    /// Parse declarations after `var` and returns the count of them.
+    fn parse_variable_declarations(&mut self, no_in: bool) -> SyntaxResult<usize> {
+        let mut count = 0;
+        loop {
+            self.binding_identifier()?;
+            if self.eat_punct("=") {
+                self.parse_assignment(no_in)?;
+            }
+            count += 1;
+            if !self.eat_punct(",") {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    fn parse_paren_expression(&mut self) -> SyntaxResult<()> {
+        self.expect_punct("(")?;
+        self.parse_expression(false)?;
+        self.expect_punct(")")
+    }
+
+    fn parse_for_statement(&mut self) -> SyntaxResult<()> {
+        self.expect_punct("(")?;
+        let for_in = if self.eat_ident("var") {
+            let count = self.parse_variable_declarations(true)?;
+            count == 1 && self.eat_ident("in")
+        } else if !self.is_punct(";") {
+            let assignable = self.parse_expression(true)?;
+            assignable && self.eat_ident("in")
+        } else {
+            false
+        };
+        if for_in {
+            self.parse_expression(false)?;
+        } else {
+            self.expect_punct(";")?;
+            if !self.is_punct(";") {
+                self.parse_expression(false)?;
+            }
+            self.expect_punct(";")?;
+            if !self.is_punct(")") {
+                self.parse_expression(false)?;
+            }
+        }
+        self.expect_punct(")")?;
+        self.parse_statement()
+    }
+
+    fn parse_switch_body(&mut self) -> SyntaxResult<()> {
+        self.parse_paren_expression()?;
+        self.expect_punct("{")?;
+        while !self.eat_punct("}") {
+            if self.eat_ident("case") {
+                self.parse_expression(false)?;
+            } else if !self.eat_ident("default") {
+                return Err(self.unexpected());
+            }
+            self.expect_punct(":")?;
+            while self.peek_kind().is_some()
+                && !self.is_punct("}")
+                && !self.is_ident("case")
+                && !self.is_ident("default")
+            {
+                self.parse_statement()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the parameters and the body of a function.
+    fn parse_function_rest(&mut self) -> SyntaxResult<()> {
+        self.expect_punct("(")?;
+        if !self.eat_punct(")") {
+            loop {
+                self.binding_identifier()?;
+                if self.eat_punct(")") {
+                    break;
+                }
+                self.expect_punct(",")?;
+            }
+        }
+        self.parse_block()
+    }
+
+    fn parse_expression(&mut self, no_in: bool) -> SyntaxResult<bool> {
+        let mut assignable = self.parse_assignment(no_in)?;
+        while self.eat_punct(",") {
+            self.parse_assignment(no_in)?;
+            assignable = false;
+        }
+        Ok(assignable)
+    }
+
+    fn parse_assignment(&mut self, no_in: bool) -> SyntaxResult<bool> {
+        let assignable = self.parse_conditional(no_in)?;
+        match self.peek_kind() {
+            Some(TokenKind::Punct(p)) if ASSIGNMENT_OPERATORS.contains(p) => {
+                if !assignable {
+                    return Err(self.unexpected());
+                }
+                self.index += 1;
+                self.parse_assignment(no_in)?;
+                Ok(false)
+            }
+            _ => Ok(assignable),
+        }
+    }
+
+    fn parse_conditional(&mut self, no_in: bool) -> SyntaxResult<bool> {
+        let assignable = self.parse_binary(0, no_in)?;
+        if self.eat_punct("?") {
+            self.parse_assignment(false)?;
+            self.expect_punct(":")?;
+            self.parse_assignment(no_in)?;
+            return Ok(false);
+        }
+        Ok(assignable)
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8, no_in: bool) -> SyntaxResult<bool> {
+        let mut assignable = self.parse_unary()?;
+        while let Some(precedence) = self.peek_kind().and_then(|x| binary_precedence(x, no_in)) {
+            if precedence < min_precedence {
+                break;
+            }
+            self.index += 1;
+            self.parse_binary(precedence + 1, no_in)?;
+            assignable = false;
+        }
+        Ok(assignable)
+    }
+
+    fn parse_unary(&mut self) -> SyntaxResult<bool> {
+        match self.peek_kind() {
+            Some(TokenKind::Ident("delete" | "void" | "typeof"))
+            | Some(TokenKind::Punct("+" | "-" | "~" | "!")) => {
+                self.index += 1;
+                self.parse_unary()?;
+                Ok(false)
+            }
+            Some(TokenKind::Punct("++" | "--")) => {
+                self.index += 1;
+                let operand = self.unexpected();
+                if !self.parse_unary()? {
+                    return Err(operand);
+                }
+                Ok(false)
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> SyntaxResult<bool> {
+        let assignable = self.parse_left_hand_side(true)?;
+        if (self.is_punct("++") || self.is_punct("--")) && !self.newline_before() {
+            if !assignable {
+                return Err(self.unexpected());
+            }
+            self.index += 1;
+            return Ok(false);
+        }
+        Ok(assignable)
+    }
+
+    fn parse_left_hand_side(&mut self, allow_call: bool) -> SyntaxResult<bool> {
+        let mut assignable = if self.eat_ident("new") {
+            self.parse_left_hand_side(false)?;
+            if self.is_punct("(") {
+                self.parse_arguments()?;
+            }
+            false
+        } else {
+            self.parse_primary()?
+        };
+        loop {
+            if self.eat_punct(".") {
+                // any identifier name (including reserved words) is allowed after `.`
+                match self.peek_kind() {
+                    Some(TokenKind::Ident(_)) => self.index += 1,
+                    _ => return Err(self.unexpected()),
+                }
+                assignable = true;
+            } else if self.eat_punct("[") {
+                self.parse_expression(false)?;
+                self.expect_punct("]")?;
+                assignable = true;
+            } else if allow_call && self.is_punct("(") {
+                self.parse_arguments()?;
+                assignable = false;
+            } else {
+                break;
+            }
+        }
+        Ok(assignable)
+    }
+
+    fn parse_arguments(&mut self) -> SyntaxResult<()> {
+        self.expect_punct("(")?;
+        if !self.eat_punct(")") {
+            loop {
+                self.parse_assignment(false)?;
+                if self.eat_punct(")") {
+                    break;
+                }
+                self.expect_punct(",")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_primary(&mut self) -> SyntaxResult<bool> {
+        let Some(kind) = self.peek_kind() else {
+            return Err(self.unexpected());
+        };
+        match kind {
+            TokenKind::Literal | TokenKind::Ident("this" | "null" | "true" | "false") => {
+                self.index += 1;
+                Ok(false)
+            }
+            TokenKind::Ident("function") => {
+                self.index += 1;
+                if !self.is_punct("(") {
+                    self.binding_identifier()?;
+                }
+                self.parse_function_rest()?;
+                Ok(false)
+            }
+            TokenKind::Ident(name) if !RESERVED_WORDS.contains(name) => {
+                self.index += 1;
+                Ok(true)
+            }
+            TokenKind::Punct("(") => {
+                self.index += 1;
+                let assignable = self.parse_expression(false)?;
+                self.expect_punct(")")?;
+                Ok(assignable)
+            }
+            TokenKind::Punct("[") => {
+                self.index += 1;
+                while !self.eat_punct("]") {
+                    // elisions, e.g. `[1, , 2]`
+                    if self.eat_punct(",") {
+                        continue;
+                    }
+                    self.parse_assignment(false)?;
+                    if !self.is_punct("]") {
+                        self.expect_punct(",")?;
+                    }
+                }
+                Ok(false)
+            }
+            TokenKind::Punct("{") => {
+                self.index += 1;
+                while !self.eat_punct("}") {
+                    self.parse_property_assignment()?;
+                    if !self.is_punct("}") {
+                        self.expect_punct(",")?;
+                    }
+                }
+                Ok(false)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_property_assignment(&mut self) -> SyntaxResult<()> {
+        let is_accessor = matches!(self.peek_kind(), Some(TokenKind::Ident("get" | "set")))
+            && matches!(
+                self.tokens.get(self.index + 1).map(|x| &x.kind),
+                Some(TokenKind::Ident(_) | TokenKind::Literal)
+            );
+        if is_accessor {
+            self.index += 1;
+        }
+        match self.peek_kind() {
+            Some(TokenKind::Ident(_) | TokenKind::Literal) => self.index += 1,
+            _ => return Err(self.unexpected()),
+        }
+        if is_accessor {
+            self.parse_function_rest()
+        } else {
+            self.expect_punct(":")?;
+            self.parse_assignment(false)?;
+            Ok(())
+        }
+    }
+}
+
+/// Check the script content.
+///
+/// The locations are relative to the start of the `src` .
+pub(crate) fn check_script_content(src: &str) -> Vec<(ParseErrorKind, Range<Position>)> {
+    let mut tokenizer = Tokenizer {
+        src,
+        offset: 0,
+        pos: Position {
+            line: 0,
+            utf16_col: 0,
+        },
+        tokens: vec![],
+        errors: vec![],
+        paren_heads: vec![],
+        closed_paren_head: false,
+    };
+    tokenizer.run();
+    let Tokenizer {
+        tokens,
+        mut errors,
+        pos: end,
+        ..
+    } = tokenizer;
+    check_brackets(&tokens, &mut errors);
+    check_keywords(&tokens, &mut errors);
+    if errors.is_empty() {
+        let mut parser = Parser {
+            tokens: &tokens,
+            index: 0,
+            end,
+        };
+        if let Err(location) = parser.parse_program() {
+            errors.push((ParseErrorKind::IllegalScriptSyntax, location));
+        }
+    }
+    errors.sort_by_key(|(_, location)| location.start);
+    errors
+}
+
+fn offset_position(base: Position, pos: Position) -> Position {
+    if pos.line == 0 {
+        Position {
+            line: base.line,
+            utf16_col: base.utf16_col + pos.utf16_col,
+        }
+    } else {
+        Position {
+            line: base.line + pos.line,
+            utf16_col: pos.utf16_col,
+        }
+    }
+}
+
+impl TmplGroup {
+    /// Check the syntax of the inline scripts in a template.
+    ///
+    /// It reports syntax errors and the syntax which is not supported by WXS, e.g. `let` and arrow functions.
+    pub fn check_inline_scripts(&self, path: &str) -> Result<Vec<ParseError>, TmplError> {
+        let tree = self.get_tree(path)?;
+        let mut ret = vec![];
+        for script in tree.globals.scripts.iter() {
+            let Script::Inline {
+                module_name,
+                content,
+                content_location,
+                ..
+            } = script
+            else {
+                continue;
+            };
+            let start = Position {
+                line: tree
+                    .inline_script_start_line(&module_name.name)
+                    .unwrap_or(content_location.start.line),
+                utf16_col: content_location.start.utf16_col,
+            };
+            for (kind, location) in check_script_content(content) {
+                ret.push(ParseError {
+                    path: tree.path.clone(),
+                    kind,
+                    location: offset_position(start, location.start)
+                        ..offset_position(start, location.end),
+                });
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Check the syntax of an external script added by `add_script` .
+    pub fn check_script(&self, path: &str) -> Result<Vec<ParseError>, TmplError> {
+        let ret = check_script_content(self.get_script(path)?)
+            .into_iter()
+            .map(|(kind, location)| ParseError {
+                path: path.to_string(),
+                kind,
+                location,
+            })
+            .collect();
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type CheckResult = Vec<(ParseErrorKind, (u32, u32), (u32, u32))>;
+
+    fn check(src: &str) -> CheckResult {
+        check_script_content(src)
+            .into_iter()
+            .map(|(kind, location)| {
+                (
+                    kind,
+                    (location.start.line, location.start.utf16_col),
+                    (location.end.line, location.end.utf16_col),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn valid_es5() {
+        let src = r#"
+            var a = { class: 1, 'b': "\"}" };
+            // comment with `let` and )
+            /* block
+               comment */
+            function f(x) { return x / 2 / a.class; }
+            var re = /[/)]+\//g.test('a');
+            var n = 1.5e-3 + 0x1F + .5;
+            module.exports = { f: f, re: re, let: n, c: a?.5:1 };
+        "#;
+        assert_eq!(check(src), vec![]);
+        let src = r#"
+            var o = { get x() { return 1; }, set x(v) {}, 'y': [1, , 2,], 3: new Date().getTime() };
+            for (var i = 0, j = 1; i < 10; i++) { if (i in o) continue; else break; }
+            for (var k in o) delete o[k];
+            for (k in o) {}
+            for (;;) break
+            outer: while (!(o instanceof Object)) { do { void 0 } while (false) }
+            switch (typeof o) { case 'object': o = null; default: }
+            try { throw new Error('e') } catch (e) { o = e } finally { o = function () { return } }
+            var x = 1
+            var y = x
+            ++y
+            x = y ? -x : ~x, x += y >>> 1
+            function g(a, b) { return /\//.test(a) && b }
+        "#;
+        assert_eq!(check(src), vec![]);
+    }
+
+    #[test]
+    fn regexp_after_paren() {
+        let src = r#"
+            var s = 'a', t = 0;
+            if (s) /x/.test(s);
+            while (t) /(y)/g.exec(s);
+            for (;;) /z/.test(s) && t++;
+            t = (t + 1) / 2 / (s.length);
+            t = o.if(t) / 2;
+        "#;
+        assert_eq!(check(src), vec![]);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            check("var a = 'abc\nvar b = 1;"),
+            vec![(ParseErrorKind::IllegalScriptSyntax, (0, 8), (0, 12))],
+        );
+        assert_eq!(
+            check("/* abc"),
+            vec![(ParseErrorKind::IllegalScriptSyntax, (0, 0), (0, 6))],
+        );
+        assert_eq!(
+            check("function f() {\n  return (1;\n}"),
+            vec![(ParseErrorKind::IllegalScriptSyntax, (2, 0), (2, 1))],
+        );
+        assert_eq!(
+            check("var a = [1, 2"),
+            vec![(ParseErrorKind::IllegalScriptSyntax, (0, 8), (0, 9))],
+        );
+        assert_eq!(
+            check("var a = #b;"),
+            vec![(ParseErrorKind::IllegalScriptSyntax, (0, 8), (0, 9))],
+        );
+    }
+
+    #[test]
+    fn statement_syntax_errors() {
+        let err = |start, end| vec![(ParseErrorKind::IllegalScriptSyntax, start, end)];
+        assert_eq!(check("var a = {a:1 b:2};"), err((0, 13), (0, 14)));
+        assert_eq!(check("var = 1;"), err((0, 4), (0, 5)));
+        assert_eq!(check("if x {}"), err((0, 3), (0, 4)));
+        assert_eq!(check("var if = 1;"), err((0, 4), (0, 6)));
+        assert_eq!(check("try {}\nvar a;"), err((1, 0), (1, 3)));
+        assert_eq!(check("1 = 2;"), err((0, 2), (0, 3)));
+        assert_eq!(check("a b;"), err((0, 2), (0, 3)));
+    }
+
+    #[test]
+    fn missing_commas() {
+        let err = |start, end| vec![(ParseErrorKind::IllegalScriptSyntax, start, end)];
+        assert_eq!(check("f(a b);"), err((0, 4), (0, 5)));
+        assert_eq!(check("var c = [1 2];"), err((0, 11), (0, 12)));
+        assert_eq!(check("function f(a b) {}"), err((0, 13), (0, 14)));
+        assert_eq!(check("var a = 1 b = 2;"), err((0, 10), (0, 11)));
+    }
+
+    #[test]
+    fn missing_operands() {
+        let err = |start, end| vec![(ParseErrorKind::IllegalScriptSyntax, start, end)];
+        assert_eq!(check("var a = 1 + ;"), err((0, 12), (0, 13)));
+        assert_eq!(check("a = * 2;"), err((0, 4), (0, 5)));
+        assert_eq!(check("var b = c ? d;"), err((0, 13), (0, 14)));
+        assert_eq!(check("var d = ;"), err((0, 8), (0, 9)));
+        assert_eq!(check("var e = 1 +"), err((0, 11), (0, 11)));
+        assert_eq!(check("x = a.;"), err((0, 6), (0, 7)));
+    }
+
+    #[test]
+    fn unsupported_features() {
+        let src = "class A {}\nlet a = 1;\nconst b = 2;\nvar f = (x) => x;\nasync function g() {}\nvar s = `${a}`;\nvar c = [...d];";
+        assert_eq!(
+            check(src),
+            vec![
+                (ParseErrorKind::UnsupportedScriptFeature, (0, 0), (0, 5)),
+                (ParseErrorKind::UnsupportedScriptFeature, (1, 0), (1, 3)),
+                (ParseErrorKind::UnsupportedScriptFeature, (2, 0), (2, 5)),
+                (ParseErrorKind::UnsupportedScriptFeature, (3, 12), (3, 14)),
+                (ParseErrorKind::UnsupportedScriptFeature, (4, 0), (4, 5)),
+                (ParseErrorKind::UnsupportedScriptFeature, (5, 8), (5, 14)),
+                (ParseErrorKind::UnsupportedScriptFeature, (6, 9), (6, 12)),
+            ],
+        );
+    }
+}
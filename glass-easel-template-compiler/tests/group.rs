@@ -34,3 +34,337 @@ fn basic_import() {
         vec!["a".to_string()]
     );
 }
+
+#[test]
+fn ast_json() {
+    const SRC: &str = r#"<view id="a" bind:tap="{{ b }}">{{ c + 1 }}</view>"#;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC);
+    let json: serde_json::Value =
+        serde_json::from_str(&group.get_tree_ast_json("a").unwrap()).unwrap();
+    assert_eq!(json["version"], parse::AST_JSON_VERSION);
+    assert_eq!(json["tree"]["path"], "a");
+    let elem = &json["tree"]["content"][0]["Element"];
+    let normal = &elem["kind"]["Normal"];
+    assert_eq!(normal["tag_name"]["name"], "view");
+    assert_eq!(normal["common"]["id"][1]["Static"]["value"], "a");
+    let ev = &normal["common"]["event_bindings"][0];
+    assert_eq!(ev["name"]["name"], "tap");
+    assert_eq!(
+        ev["value"]["Dynamic"]["expression"]["DataField"]["name"],
+        "b"
+    );
+    let text = &normal["children"][0]["Text"]["Dynamic"];
+    assert_eq!(
+        text["expression"]["Plus"]["location"],
+        serde_json::json!({ "start": { "line": 0, "utf16_col": 37 }, "end": { "line": 0, "utf16_col": 38 } })
+    );
+    assert!(group.get_tree_ast_json("b").is_err());
+
+    let json: serde_json::Value =
+        serde_json::from_str(&group.get_ast_json_groups().unwrap()).unwrap();
+    assert_eq!(json["version"], parse::AST_JSON_VERSION);
+    assert_eq!(json["trees"]["a"]["path"], "a");
+}
+
+#[test]
+fn ast_json_snapshot() {
+    const SRC: &str = r#"<import src="b" />
+<wxs module="m">exports.x = 1</wxs>
+<template name="t"><text>{{ a }}</text></template>
+<view wx:if="{{ c }}" id="i" class="x {{ y }}" style="color: red" data:d="{{ 1 }}" bind:tap="f" model:value="{{ v }}">
+  <block wx:for="{{ list }}" wx:key="id">{{ item.name + index }}</block>
+  <template is="t" data="{{ a: m.x }}" />
+  <slot name="s" />
+</view>
+<view wx:else>text &amp; more</view>
+"#;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC);
+    let json: serde_json::Value =
+        serde_json::from_str(&group.get_tree_ast_json("a").unwrap()).unwrap();
+    // the JSON shape is part of the public format:
+    // update the snapshot only along with `AST_JSON_VERSION` if the change is incompatible
+    assert_eq!(parse::AST_JSON_VERSION, 1);
+    let expected: serde_json::Value =
+        serde_json::from_str(include_str!("snapshots/ast_json.json")).unwrap();
+    assert_eq!(json, expected);
+}
+
+#[test]
+fn check_references() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("lib", r#"<template name="a" /><template name="b" />"#);
+    group.add_tmpl("lib2", r#"<template name="b" />"#);
+    group.add_tmpl(
+        "a",
+        r#"<import src="lib" /><import src="lib2" /><template name="a" /><template is="a" /><template is="c" /><template is="{{ d }}" />"#,
+    );
+    group.add_tmpl("b", r#"<import src="missing" /><template is="c" />"#);
+    group.add_tmpl("c", r#"<include src="d" /><include src="missing" />"#);
+    group.add_tmpl("d", r#"<include src="c" />"#);
+    group.add_tmpl("e", r#"<include src="e" /><include src="lib" />"#);
+    let errors: Vec<_> = group
+        .check_all_references()
+        .into_iter()
+        .map(|x| (x.path, x.kind, x.location.start.utf16_col))
+        .collect();
+    use parse::ParseErrorKind::*;
+    assert_eq!(
+        errors,
+        [
+            ("a".to_string(), ShadowedTemplateName, 20),
+            ("a".to_string(), ShadowedTemplateName, 41),
+            ("a".to_string(), TemplateNotFound, 81),
+            ("b".to_string(), DependencyNotFound, 0),
+            ("c".to_string(), CircularInclude, 0),
+            ("c".to_string(), DependencyNotFound, 19),
+            ("d".to_string(), CircularInclude, 0),
+            ("e".to_string(), CircularInclude, 0),
+        ],
+    );
+    assert!(group.check_references("f").is_err());
+}
+
+#[test]
+fn invalidated_templates() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", r#"<template name="t"><view /></template>"#);
+    group.add_tmpl("b", r#"<import src="a" /><template is="t" />"#);
+    group.add_tmpl("c", r#"<include src="b" /><wxs module="m" src="m" />"#);
+    group.add_tmpl("d", r#"<view />"#);
+    group.add_script("m", "");
+    assert_eq!(group.invalidated_templates("a"), ["a", "b", "c"]);
+    assert_eq!(group.invalidated_templates("b"), ["b", "c"]);
+    assert_eq!(group.invalidated_templates("m"), ["c"]);
+    assert_eq!(group.invalidated_templates("d"), ["d"]);
+    assert_eq!(group.invalidated_templates("x"), Vec::<String>::new());
+
+    group.add_tmpl("c", r#"<view />"#);
+    assert_eq!(group.invalidated_templates("b"), ["b"]);
+    assert_eq!(group.invalidated_templates("m"), Vec::<String>::new());
+    group.remove_tmpl("b");
+    assert_eq!(group.invalidated_templates("a"), ["a"]);
+}
+
+#[test]
+fn cached_gen_object() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", r#"<template name="t"><view /></template>"#);
+    group.add_tmpl(
+        "b",
+        r#"<import src="a" /><template is="t" /><text>b</text>"#,
+    );
+    let b = group.get_tmpl_gen_object("b").unwrap();
+    let all = group.get_tmpl_gen_object_groups().unwrap();
+    assert!(all.contains(&b));
+    assert_eq!(group.get_tmpl_gen_object("b").unwrap(), b);
+
+    group.add_tmpl(
+        "b",
+        r#"<import src="a" /><template is="t" /><text>c</text>"#,
+    );
+    let new_b = group.get_tmpl_gen_object("b").unwrap();
+    assert_ne!(new_b, b);
+    let new_all = group.get_tmpl_gen_object_groups().unwrap();
+    assert!(new_all.contains(&new_b));
+    assert!(!new_all.contains(&b));
+
+    group.remove_tmpl("b");
+    assert!(group.get_tmpl_gen_object("b").is_err());
+    assert!(!group.get_tmpl_gen_object_groups().unwrap().contains(&new_b));
+}
+
+#[test]
+fn deterministic_output() {
+    const SOURCES: [(&str, &str); 4] = [
+        (
+            "a",
+            r#"<template name="t"><view a="{{ a }}" b="{{ b }}" c="{{ c }}" /></template>"#,
+        ),
+        (
+            "b",
+            r#"<import src="a" /><template is="t" data="{{ a, b, c }}" />"#,
+        ),
+        (
+            "c",
+            r#"<view slot:x slot:y slot:z>{{ x + y + z }}</view><wxs module="m" src="s1" />{{ m.a }}"#,
+        ),
+        (
+            "d",
+            r#"<include src="c" /><view d="{{ d }}" e="{{ e }}" />"#,
+        ),
+    ];
+    let build = |reversed: bool| {
+        let mut group = TmplGroup::new();
+        let mut sources = SOURCES.to_vec();
+        let mut scripts = vec![("s1", "exports.a = 1"), ("s2", "exports.b = 2")];
+        if reversed {
+            sources.reverse();
+            scripts.reverse();
+        }
+        for (path, src) in sources {
+            group.add_tmpl(path, src);
+        }
+        for (path, src) in scripts {
+            group.add_script(path, src);
+        }
+        (
+            group.get_tmpl_gen_object_groups().unwrap(),
+            group.get_wx_gen_object_groups().unwrap(),
+            group.export_all_scripts().unwrap(),
+        )
+    };
+    let expected = build(false);
+    for i in 0..8 {
+        assert_eq!(build(i % 2 == 1), expected);
+    }
+}
+
+#[test]
+fn gen_object_source_map() {
+    const SRC_A: &str = r#"<view a="{{ x + y }}">{{ zz }}</view>"#;
+    const SRC_B: &str = "<view>\n  {{ w }}\n</view>";
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC_A);
+    group.add_tmpl("b", SRC_B);
+
+    let (code, source_map) = group.get_tmpl_gen_object_with_source_map("a").unwrap();
+    assert_eq!(code, group.get_tmpl_gen_object("a").unwrap());
+    let col = code.find("D.zz").unwrap() as u32;
+    let token = source_map.lookup_token(0, col).unwrap();
+    assert_eq!(token.get_source(), Some("a"));
+    assert_eq!(token.get_src(), (0, SRC_A.find("zz").unwrap() as u32));
+    let col = code.find("D.x").unwrap() as u32;
+    let token = source_map.lookup_token(0, col).unwrap();
+    assert_eq!(token.get_src(), (0, SRC_A.find('x').unwrap() as u32));
+
+    let (code, source_map) = group.get_tmpl_gen_object_groups_with_source_map().unwrap();
+    assert_eq!(code, group.get_tmpl_gen_object_groups().unwrap());
+    let find_token = |name: &str| {
+        let index = code.find(name).unwrap();
+        let line = code[..index].matches('\n').count() as u32;
+        let col = (index - code[..index].rfind('\n').map(|x| x + 1).unwrap_or(0)) as u32;
+        let token = source_map.lookup_token(line, col).unwrap();
+        (token.get_source().map(|x| x.to_string()), token.get_src())
+    };
+    assert_eq!(
+        find_token("D.zz"),
+        (Some("a".to_string()), (0, SRC_A.find("zz").unwrap() as u32))
+    );
+    assert_eq!(find_token("D.w"), (Some("b".to_string()), (1, 5)));
+
+    let (code, _) = group.get_wx_gen_object_groups_with_source_map().unwrap();
+    assert_eq!(code, group.get_wx_gen_object_groups().unwrap());
+}
+
+#[test]
+fn gen_object_source_map_marker_chars() {
+    const SRC: &str =
+        "<wxs module=\"m\">module.exports = '\u{FDD0}x\u{FDD2}';</wxs><view>{{ m }}{{ a }}</view>";
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC);
+    group.add_script("s", "var s = /\u{FDD1}/;");
+    let (code, source_map) = group.get_wx_gen_object_groups_with_source_map().unwrap();
+    assert_eq!(code, group.get_wx_gen_object_groups().unwrap());
+    assert!(code.contains(r"'\uFDD0x\uFDD2'"));
+    assert!(code.contains(r"/\uFDD1/"));
+    let index = code.find("D.a").unwrap();
+    let line = code[..index].matches('\n').count() as u32;
+    let col = (index - code[..index].rfind('\n').map(|x| x + 1).unwrap_or(0)) as u32;
+    let token = source_map.lookup_token(line, col).unwrap();
+    let src_col = SRC[..SRC.find("a }}").unwrap()].encode_utf16().count() as u32;
+    assert_eq!(token.get_src(), (0, src_col));
+}
+
+#[test]
+fn optional_chain_lvalue_paths() {
+    let gen = |src: &str| {
+        let mut group = TmplGroup::new();
+        group.add_tmpl("a", src);
+        group.get_tmpl_gen_object("a").unwrap()
+    };
+
+    // `model:` bindings
+    let code = gen(r#"<input model:value="{{ a?.b }}" />"#);
+    assert!(code.contains(r#"O(N,"value",X(D.a).b,["a","b"])"#));
+    assert_eq!(code, gen(r#"<input model:value="{{ a.b }}" />"#));
+
+    // `model:` bindings on the items of `wx:for`
+    let code = gen(r#"<view wx:for="{{ a?.list }}"><input model:value="{{ item.x }}" /></view>"#);
+    assert!(code.contains(r#"[0,"a","list"]"#));
+    assert!(code.contains(r#"[...g,"x"].slice(1)"#));
+    assert_eq!(
+        code,
+        gen(r#"<view wx:for="{{ a.list }}"><input model:value="{{ item.x }}" /></view>"#),
+    );
+    assert_eq!(
+        gen(r#"<view wx:for="{{ a?.[k] }}"><input model:value="{{ item.x }}" /></view>"#),
+        gen(r#"<view wx:for="{{ a[k] }}"><input model:value="{{ item.x }}" /></view>"#),
+    );
+}
+
+#[test]
+fn class_name_mapping() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "a",
+        r#"<view class="a b" /><view class="a" class:b="{{ c }}" /><view class="{{ a }}" /><view class="a {{ c ? 'b' : '' }}" /><view class="{{ `c ${ c ? 'a' : 'b' }` }}" /><view class="a{{ c ? 'b' : '' }}" />"#,
+    );
+    let old = group.get_tmpl_gen_object("a").unwrap();
+    let mapping = [("a", "_x"), ("b", "_y")]
+        .iter()
+        .map(|&(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let warnings = group.apply_class_name_mapping("a", &mapping).unwrap();
+    assert_ne!(group.get_tmpl_gen_object("a").unwrap(), old);
+    assert_eq!(
+        group.stringify_tmpl("a").unwrap(),
+        r#"<view class="_x _y"/><view class:_x class:_y="{{c}}"/><view class="{{a}}"/><view class="_x {{c?"_y":""}}"/><view class="{{`c ${c?"_x":"_y"}`}}"/><view class="a{{c?"b":""}}"/>"#,
+    );
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|x| (x.kind.clone(), x.location.start.utf16_col))
+            .collect::<Vec<_>>(),
+        [
+            (parse::ParseErrorKind::ClassNamesNotRenamed, 72),
+            (parse::ParseErrorKind::ClassNamesNotRenamed, 175),
+        ],
+    );
+    assert!(group
+        .apply_class_name_mapping("b", &Default::default())
+        .is_err());
+}
+
+#[test]
+fn class_usage_report() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "a",
+        r#"
+            <include src="b" />
+            <view class="a b {{ c ? 'c' : '' }} d-{{ e ? 'x' : 'y' }}" />
+            <view class:f="{{ g }}" />
+            <button hover-class="h" />
+        "#,
+    );
+    group.add_tmpl("b", r#"<view class="i {{ j && 'k' }}" />"#);
+    group.add_tmpl("c", r#"<view class="a {{ cls }}" />"#);
+    let report = group
+        .class_usage_report("a", ["a", "c", "d-x", "d-z", "f", "h", "k", "unused"])
+        .unwrap();
+    assert!(!report.incomplete);
+    assert_eq!(report.unused_classes, ["d-z", "unused"]);
+    let undefined: Vec<_> = report
+        .undefined_classes
+        .iter()
+        .map(|x| (x.path.as_str(), x.name.as_str()))
+        .collect();
+    assert_eq!(undefined, [("a", "b"), ("a", "d-y"), ("b", "i")]);
+    let report = group.class_usage_report("c", ["a", "b"]).unwrap();
+    assert!(report.incomplete);
+    assert!(report.unused_classes.is_empty());
+    assert!(report.undefined_classes.is_empty());
+    assert!(group.class_usage_report("d", []).is_err());
+}
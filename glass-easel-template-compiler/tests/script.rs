@@ -36,3 +36,44 @@ fn inline_script() {
     );
     assert_eq!(group.inline_script_content("tmpl/a", "modB").unwrap(), "");
 }
+
+#[test]
+fn check_script_syntax() {
+    const SRC_A: &str = "<wxs module=\"modA\">var a = 1;\nlet b = (x) => x;</wxs>\n<wxs module=\"modB\"> var c = 'c</wxs>";
+    let mut group = TmplGroup::new();
+    let tmpl_errors = group.add_tmpl("tmpl/a", SRC_A);
+    let script_errors = group.add_script(
+        "script/a",
+        "var a = {\n  class: 1,\n  f: function () { return `a`; },\n};",
+    );
+    let errors = group.check_inline_scripts("tmpl/a").unwrap();
+    assert_eq!(tmpl_errors, errors);
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|x| {
+            (
+                x.kind.clone(),
+                x.location.start.line,
+                x.location.start.utf16_col,
+            )
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (parse::ParseErrorKind::UnsupportedScriptFeature, 1, 0),
+            (parse::ParseErrorKind::UnsupportedScriptFeature, 1, 12),
+            (parse::ParseErrorKind::IllegalScriptSyntax, 2, 28),
+        ],
+    );
+    assert_eq!(group.inline_script_start_line("tmpl/a", "modB").unwrap(), 2);
+    let errors = group.check_script("script/a").unwrap();
+    assert_eq!(script_errors, errors);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind,
+        parse::ParseErrorKind::UnsupportedScriptFeature
+    );
+    assert_eq!(errors[0].location.start.line, 2);
+    assert!(group.check_script("script/b").is_err());
+}
@@ -1139,6 +1139,14 @@ export class ProcGenWrapper {
     elem.setMark(name, v)
   }
 
+  // get a method of the host component (used by callees in arrow function handlers)
+  gm = (methodName: string) => {
+    const host = this.shadowRoot.getHostNode()
+    const f = Component.getMethod(host, methodName)
+    if (typeof f !== 'function') return undefined
+    return (...args: unknown[]) => f.apply(host.getMethodCaller(), args) as unknown
+  }
+
   // set event handler
   v = (
     elem: Element,
@@ -118,6 +118,33 @@ const testCases = (testBackend: glassEasel.GeneralBackendContext) => {
     expect(eventOrder).toStrictEqual([3, 2])
   })
 
+  test('arrow function handlers', () => {
+    const selected: [number, unknown][] = []
+    const callers: unknown[] = []
+    const def = glassEasel.registerElement({
+      template: tmpl(`
+        <div wx:for="{{ list }}" id="{{ item.id }}" bind:customEv="{{ (e) => onSelect(item.id, e.detail) }}" />
+      `),
+      data: {
+        list: [{ id: 1 }, { id: 2 }],
+      },
+      methods: {
+        onSelect(id: number, detail: unknown) {
+          callers.push(this)
+          selected.push([id, detail])
+        },
+      },
+    })
+    const elem = glassEasel.Component.createWithContext('root', def.general(), testBackend)
+    elem.getShadowRoot()!.getElementById('2')!.triggerEvent('customEv', 'a')
+    elem.getShadowRoot()!.getElementById('1')!.triggerEvent('customEv', 'b')
+    expect(selected).toStrictEqual([
+      [2, 'a'],
+      [1, 'b'],
+    ])
+    expect(callers.every((x) => x === elem.getMethodCaller())).toBe(true)
+  })
+
   test('mut-bind bindings', () => {
     const eventOrder: number[] = []
     const def = glassEasel.registerElement({